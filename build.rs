@@ -0,0 +1,23 @@
+use std::{path::Path, process::Command};
+
+/// Embeds the git commit wani was built from, for `wani version`
+fn main() {
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=WANI_GIT_HASH={}", hash);
+
+    // A missing path would make cargo rerun this every build, so outside a git checkout
+    // leave it to cargo's default of rerunning when any package file changes
+    for path in [".git/HEAD", ".git/logs/HEAD"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
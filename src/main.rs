@@ -1,10 +1,11 @@
 mod wanidata;
+mod wanilog;
 mod wanisql;
 
 use crate::wanidata::{Assignment, NewReview, ReviewStatus, Subject, SubjectType, WaniData, WaniResp};
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::collections::hash_map::RandomState;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::collections::HashSet;
 use std::io::BufReader;
 use std::io::Write;
@@ -18,8 +19,9 @@ use wanidata::WaniFmtArgs;
 use wanisql::{parse_review, CacheInfo};
 use std::sync::{Arc, PoisonError}; use std::{fmt::Display, fs::{self, File}, io::{self, BufRead}, path::Path, path::PathBuf};
 use chrono::DateTime;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use chrono::Utc;
+use chrono::Local;
 use itertools::Itertools;
 use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
@@ -30,6 +32,7 @@ use resvg::usvg::{self, Tree};
 use rgb::FromSlice;
 use rodio::{Decoder, OutputStream, Sink};
 use rusqlite::params;
+use rusqlite::DatabaseName;
 use rusqlite::{
     Connection, Error as SqlError
 };
@@ -37,7 +40,7 @@ use thiserror::Error;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc;
 use tokio::join;
-use tokio::task::JoinSet;
+use tokio::task::{JoinHandle, JoinSet};
 use tokio_rusqlite::Connection as AsyncConnection;
 use console:: {
     pad_str, style, Emoji, Term
@@ -48,8 +51,9 @@ use image2ascii::image2ascii;
 use wanidata::RateLimit;
 
 #[derive(Parser)]
+#[command(version = concat!(env!("CARGO_PKG_VERSION"), " (", env!("WANI_GIT_HASH"), ")"))]
 struct Args {
-    /// Subcommand to run. Default is summary
+    /// Subcommand to run. Default is summary, or the default_command from the config file
     #[command(subcommand)]
     command: Option<Command>,
 
@@ -68,6 +72,36 @@ struct Args {
     /// The config file path can also be specified in the WANI_CONFIG_PATH environment variable
     #[arg(short, long, value_name = "FILE")]
     configfile: Option<PathBuf>,
+
+    /// Logs extra diagnostic info, like WaniKani API fields that wani doesn't recognize during sync,
+    /// or radical images that couldn't be shown
+    #[arg(long)]
+    verbose: bool,
+
+    /// Never sends changes to WaniKani: no review submissions, lesson starts, or note edits.
+    /// Reviews are practice only, and finished lessons aren't saved
+    #[arg(long)]
+    read_only: bool,
+
+    /// Number of lessons taught before each lesson quiz. Overrides lesson_batch_size in the config file
+    #[arg(long, value_name = "N")]
+    lesson_batch_size: Option<usize>,
+
+    /// Never plays pronunciation audio, e.g. on a machine without a sound device.
+    /// Can also be set in the wani config file with
+    ///     no_audio: true
+    #[arg(long)]
+    no_audio: bool,
+
+    /// WaniKani API revision to request, like 20170710. Overrides the revision in the config file
+    #[arg(long, value_name = "YYYYMMDD", value_parser = wanidata::parse_api_revision)]
+    revision: Option<String>,
+
+    /// Reads WaniKani API responses from canned JSON files in this directory instead of the network.
+    /// Files are named by endpoint, e.g. subjects.json, assignments.json, user.json.
+    /// For development and testing without a WaniKani account.
+    #[arg(long, value_name = "PATH", hide = true)]
+    fixture_dir: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -77,19 +111,229 @@ enum Command {
     /// a shorthand for the 'summary' command
     S,
     /// Begin or resume a review session.
-    Review,
+    Review(ReviewArgs),
     /// a shorthand for the 'review' command
-    R,
+    R(ReviewArgs),
     /// Begin a lesson session
     Lesson,
     /// A shorthand for the 'lesson' command
     L,
     /// Syncs local data with WaniKani servers
-    Sync,
+    Sync {
+        /// Only sync subjects and assignments up to this level, to speed up the first sync.
+        /// Without a value, syncs up to a few levels past your current level.
+        /// A later uncapped sync fills in the rest.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(1..))]
+        up_to_level: Option<Option<i32>>,
+
+        /// After syncing, download and convert every radical image, so reviews never wait on one
+        #[arg(long)]
+        with_images: bool,
+    },
     /// Forces update of local data instead of only fetching new data
     ForceSync,
     /// Does first-time initialization
     Init,
+    /// Shows how many items are in each Apprentice stage
+    Apprentice,
+    /// Snapshots the local database and config file into a timestamped directory
+    Backup {
+        /// Directory in which to create the backup. Default is <datapath>/backups
+        #[arg(value_name = "PATH")]
+        dest: Option<PathBuf>,
+
+        /// Restores the database and config file from a backup directory instead of creating one
+        #[arg(long, value_name = "PATH")]
+        restore: Option<PathBuf>,
+    },
+    /// Quizzes a random sample of burned items to check long-term retention. Results are not submitted.
+    RetentionCheck {
+        /// Number of burned items to quiz
+        #[arg(short, long, default_value_t = 10)]
+        count: usize,
+    },
+    /// Practices the subjects at some levels, or with some ids, whether or not you've unlocked them.
+    /// Results are never submitted, so this is safe for burned items or upcoming levels.
+    Practice {
+        /// Levels to practice, like 5 or 5,6,7
+        #[arg(long, value_name = "LEVELS", value_delimiter = ',', required_unless_present = "ids")]
+        level: Vec<i32>,
+
+        /// WaniKani subject ids to practice, like 440,441
+        #[arg(long, value_name = "IDS", value_delimiter = ',')]
+        ids: Vec<i32>,
+
+        /// Only practice this many of the subjects, chosen at random
+        #[arg(short, long, value_name = "N")]
+        count: Option<usize>,
+    },
+    /// Leaves a subject out of reviews until it's unsuspended. Lists suspended subjects if no id is given.
+    /// This is local to wani. WaniKani doesn't know about suspended subjects.
+    Suspend {
+        /// WaniKani subject id
+        subject_id: Option<i32>,
+    },
+    /// Puts a suspended subject back into reviews
+    Unsuspend {
+        /// WaniKani subject id
+        subject_id: i32,
+    },
+    /// Skips the lesson for an item you already know by starting it on WaniKani. Its first review
+    /// comes due as usual, since WaniKani only accepts reviews once they're due.
+    /// This changes your real SRS progress and can't be undone.
+    MarkKnown {
+        /// WaniKani subject id
+        subject_id: i32,
+
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Shows the wani version, the git commit it was built from, and the WaniKani API revision it uses
+    Version,
+    /// Checks an answer for a subject without starting a review, and prints the result
+    Grade {
+        /// WaniKani subject id
+        #[arg(long)]
+        subject_id: i32,
+
+        /// The answer to check. Readings can be typed in romaji
+        #[arg(long)]
+        answer: String,
+
+        /// Check the answer against the subject's meanings
+        #[arg(long, conflicts_with = "reading", required_unless_present = "reading")]
+        meaning: bool,
+
+        /// Check the answer against the subject's readings
+        #[arg(long)]
+        reading: bool,
+
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Prints the lesson and review hotkeys without starting a session
+    HelpKeys,
+    /// Prints the final configuration, and where each setting came from
+    DumpConfig,
+    /// Imports your own vocab from a CSV file into a custom deck, replacing the deck if it exists.
+    /// Each line is: characters,meanings,readings. Separate alternative answers with ';'
+    ImportDeck {
+        /// CSV file to import
+        #[arg(value_name = "CSV")]
+        path: PathBuf,
+
+        /// Name of the deck. Default is the file name without its extension
+        #[arg(long)]
+        deck: Option<String>,
+    },
+    /// Quizzes the items in a custom deck. Results are not submitted.
+    Cram {
+        /// Name of the deck to quiz
+        #[arg(long)]
+        deck: String,
+
+        /// Number of items to quiz. Default is the whole deck
+        #[arg(short, long)]
+        count: Option<usize>,
+    },
+    /// Compares reviews stored locally, waiting to be submitted, against the reviews WaniKani has recorded
+    VerifyReviews {
+        /// Removes local reviews that WaniKani already recorded
+        #[arg(long)]
+        remove_recorded: bool,
+    },
+    /// Shows how a subject connects to others: a radical's kanji, a kanji's radicals and vocab,
+    /// or a vocab's kanji
+    Tree {
+        /// WaniKani subject id
+        subject_id: i32,
+    },
+    /// Checks whether this terminal can show wani's Japanese text and colors, and suggests settings
+    Doctor,
+    /// Downloads the radical images and vocab audio for your available lessons and reviews,
+    /// so the sessions work without a connection
+    Preload,
+    /// Summarizes the accuracy of the reviews you've done in wani, overall, by level, and by SRS stage
+    Stats {
+        /// How far back to look: a number of days like 7d or 30d, or all
+        #[arg(long, default_value = "all", value_parser = wanidata::parse_stats_period)]
+        period: wanidata::StatsPeriod,
+
+        /// First day to include (local time). Replaces the start of --period
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<chrono::NaiveDate>,
+
+        /// Last day to include (local time)
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<chrono::NaiveDate>,
+    },
+    /// Shows wrong review answers that were another item's answer
+    Confusions {
+        /// Number of confusions to show
+        #[arg(short, long, default_value_t = 20)]
+        count: usize,
+    },
+}
+
+#[derive(clap::Args, Default, Clone)]
+struct ReviewArgs {
+    /// Only review items that became available today (local time), skipping any older backlog.
+    #[arg(long)]
+    today: bool,
+
+    /// Practice the currently available reviews without submitting any results to WaniKani.
+    /// Answers are not saved, so mistakes won't affect your SRS progress.
+    #[arg(long)]
+    practice: bool,
+
+    /// While practicing, show a vocab's kanji composition after a miss, or on request with 'h'.
+    #[arg(long, requires = "practice")]
+    hints: bool,
+
+    /// While practicing, only accept this type of reading for kanji, to drill onyomi or kunyomi.
+    #[arg(long, requires = "practice")]
+    reading_type: Option<ReadingType>,
+
+    /// While practicing, press '!' before answering to study an item's info first. The item is still
+    /// scored, and listed as assisted at the end of the session.
+    #[arg(long, requires = "practice")]
+    relearn: bool,
+
+    /// Writes the items you got wrong this session to an Anki-importable TSV file
+    #[arg(long, value_name = "FILE")]
+    export_wrong: Option<PathBuf>,
+
+    /// Only review up to this many items this session, starting with the most overdue.
+    #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    limit: Option<usize>,
+
+    /// Only review items in these SRS stages: a stage (1-9), a group (apprentice, guru, master,
+    /// enlightened, burned), a range like 1-4 or guru-master, or a lowest stage like master+
+    #[arg(long, value_name = "RANGE", value_parser = wanidata::parse_srs_range)]
+    srs: Option<std::ops::RangeInclusive<i32>>,
+
+    /// Number of reviews per batch. Results are saved after each batch, so smaller batches save
+    /// more often. Overrides review_batch_size in the config file
+    #[arg(long, value_name = "N")]
+    batch_size: Option<usize>,
+}
+
+/// kanji reading type to drill with `review --practice --reading-type`
+#[derive(Clone, Copy, ValueEnum)]
+enum ReadingType {
+    Onyomi,
+    Kunyomi,
+}
+
+impl From<ReadingType> for wanidata::KanjiType {
+    fn from(t: ReadingType) -> Self {
+        match t {
+            ReadingType::Onyomi => wanidata::KanjiType::Onyomi,
+            ReadingType::Kunyomi => wanidata::KanjiType::Kunyomi,
+        }
+    }
 }
 
 /// Info saved to program config file
@@ -98,6 +342,197 @@ struct ProgramConfig {
     data_path: PathBuf,
     colorblind: bool,
     user: wanidata::UserData,
+    default_command: Command,
+    /// run the last summary, review, or lesson command when no subcommand is given
+    repeat_last_command: bool,
+    review_order: ReviewOrder,
+    shuffle: ShuffleMode,
+    /// audio content types, most preferred first
+    audio_formats: Vec<String>,
+    /// voice actor name or gender whose audio is played first, when a vocab has several voices
+    preferred_voice: Option<String>,
+    /// pronunciation audio volume, from 0.0 (silent) to 1.0 (full)
+    volume: f32,
+    /// don't play pronunciation audio
+    no_audio: bool,
+    /// ask before quitting a lesson session with ctrl+c
+    confirm_lesson_quit: bool,
+    /// only accept primary meanings/readings as correct
+    primary_only: bool,
+    /// accept meanings typed without their accents, like "cafe" for "café"
+    fold_diacritics: bool,
+    /// how far off a meaning can be and still be accepted
+    fuzzy: wanidata::FuzzyMode,
+    /// warn when an answer is submitted faster than this many milliseconds
+    min_answer_ms: Option<u64>,
+    /// mark a review answer wrong if it isn't submitted within this many seconds
+    answer_timeout_secs: Option<u64>,
+    /// suggest a break after this many minutes of reviews
+    break_reminder_minutes: Option<u64>,
+    /// end a review session after this many minutes
+    max_session_minutes: Option<u64>,
+    /// don't start lessons while there are at least this many Apprentice items
+    apprentice_ceiling: Option<usize>,
+    /// ask whether to limit a review session when more than this many reviews are available
+    large_session_prompt: Option<usize>,
+    empty_enter: EmptyEnter,
+    /// answer color for an answer that was a bit off but still accepted
+    fuzzy_correct_color: AnswerColor,
+    /// alignment of the review and lesson quiz screens
+    review_align: console::Alignment,
+    /// re-ask the same half (meaning or reading) of an item after missing it, instead of re-rolling
+    remember_half: bool,
+    /// after a kanji or vocab's first half is answered correctly, ask its other half right away
+    pair_kanji_halves: bool,
+    /// keep the last answer feedback toast on screen, across items, until it's dismissed
+    persistent_toasts: bool,
+    /// shell command run when a review or lesson session ends
+    on_complete_command: Option<String>,
+    /// how the review screen's percent correct is counted
+    accuracy: AccuracyMetric,
+    /// hide the review stats line until it's shown with Tab
+    focus_mode: bool,
+    /// run review and lesson sessions in the terminal's alternate screen, keeping scrollback clean
+    alternate_screen: bool,
+    /// smallest terminal (columns, rows) sessions will run in
+    min_terminal_size: Option<(u16, u16)>,
+    /// rounding of the review screen's percent correct
+    accuracy_precision: wanidata::PercentPrecision,
+    /// list the lesson batch's items before starting its quiz
+    lesson_preview: bool,
+    /// whether lessons alternate flashcards and quizzes in small batches, or show every card first
+    lesson_flow: LessonFlow,
+    /// lessons taught before each quiz, when lesson_flow is batched. May be 0 if misconfigured
+    lesson_batch_size: usize,
+    /// reviews per batch. Results are saved after each batch. May be 0 if misconfigured
+    review_batch_size: usize,
+    /// which answers ring the bell, or show a desktop notification
+    notify_on: NotifyOn,
+    /// resize review batches based on session accuracy. Fixed size when None
+    adaptive_batch: Option<wanidata::AdaptiveBatch>,
+    /// submit finished reviews in the background this often during a session, instead of only
+    /// at the end of each batch
+    auto_submit_seconds: Option<u64>,
+    alt_readings: AltReadings,
+    /// collapse runs of blank lines in info pages and lesson flashcards
+    collapse_blank_lines: bool,
+    /// show the romaji after each kana reading in info pages and lesson flashcards
+    show_romaji: bool,
+    /// show today's finished reviews and accuracy in the review screen header
+    show_today_progress: bool,
+    /// draw the subject's characters at double size
+    large_characters: bool,
+    /// don't show the answer while it's typed, only after Enter
+    blind_input: bool,
+    /// send WaniKani requests through this proxy
+    proxy: Option<reqwest::Proxy>,
+    /// skip TLS certificate checks, e.g. for a proxy with a self-signed certificate
+    danger_accept_invalid_certs: bool,
+    /// WaniKani API revision sent with every request
+    revision: String,
+    verbose: bool,
+    /// never send changes to WaniKani
+    read_only: bool,
+    /// append session events here
+    log_file: Option<PathBuf>,
+    fixture_dir: Option<PathBuf>,
+    config_path: PathBuf,
+    /// every setting's resolved value, written as it would be in the config file, and where it
+    /// came from. For dump-config
+    config_sources: Vec<(&'static str, String, ConfigSource)>,
+    /// keys in the config file that wani doesn't know, and ignores
+    unknown_config_keys: Vec<String>,
+}
+
+/// Where a resolved config value came from
+#[derive(Clone, Copy, PartialEq)]
+enum ConfigSource {
+    Default,
+    ConfigFile,
+    CommandLine,
+}
+
+impl Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::ConfigFile => write!(f, "config file"),
+            ConfigSource::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// Order in which available reviews are batched
+#[derive(Default)]
+enum ReviewOrder {
+    #[default]
+    Random,
+    /// Items that have been available the longest come first
+    OverdueFirst,
+}
+
+/// How lesson flashcards and quizzes are interleaved
+#[derive(Default)]
+enum LessonFlow {
+    /// Flashcards then a quiz for every lesson_batch_size lessons
+    #[default]
+    Batched,
+    /// Flashcards for every lesson in the session, then one quiz on all of them
+    AllCardsFirst,
+}
+
+/// Which answers are signaled with the terminal bell, or a desktop notification
+#[derive(Default, Clone, Copy)]
+enum NotifyOn {
+    #[default]
+    Never,
+    Incorrect,
+    Always,
+}
+
+/// How the percent correct shown during reviews is counted
+#[derive(Default, Clone, Copy)]
+enum AccuracyMetric {
+    /// Percent of answers that were correct. A kanji or vocab's meaning and reading count separately,
+    /// and every wrong try counts
+    #[default]
+    Answers,
+    /// Percent of finished items with no wrong answers, like WaniKani's review summary
+    Items,
+}
+
+/// What pressing Enter without an answer does during reviews
+#[derive(Default)]
+enum EmptyEnter {
+    /// Nothing happens
+    #[default]
+    Ignore,
+    /// Shows the answer and counts it as incorrect
+    Reveal,
+    /// Rings the terminal bell and asks for an answer
+    Beep,
+}
+
+/// What happens when a reading answer is accepted, but isn't the primary reading
+#[derive(Default, PartialEq)]
+enum AltReadings {
+    /// Counts as correct, like any accepted answer
+    #[default]
+    Accept,
+    /// Counts as correct, and shows the primary reading
+    Note,
+    /// Like Note, but while practicing asks again for the primary reading
+    PracticeStrict,
+}
+
+/// How often the items within a batch are reshuffled
+#[derive(Default)]
+enum ShuffleMode {
+    /// Reshuffle after every answer
+    #[default]
+    Item,
+    /// Shuffle once, then keep that order until the batch is done
+    Batch,
 }
 
 /// Info needed to make WaniKani web requests
@@ -105,6 +540,14 @@ struct WaniWebConfig {
     client: Client,
     auth: String,
     revision: String,
+    /// when set, API responses are read from files in this directory. See Args::fixture_dir
+    fixture_dir: Option<PathBuf>,
+    /// WaniKani looked unreachable at the start of the session, so results are only saved locally
+    offline: bool,
+    /// log failed image downloads and conversions
+    verbose: bool,
+    /// only GET requests are sent. See Args::read_only
+    read_only: bool,
 }
 
 impl Clone for WaniWebConfig {
@@ -113,6 +556,10 @@ impl Clone for WaniWebConfig {
             client: self.client.clone(),
             auth: self.auth.clone(),
             revision: self.revision.clone(),
+            fixture_dir: self.fixture_dir.clone(),
+            offline: self.offline,
+            verbose: self.verbose,
+            read_only: self.read_only,
         }
     }
 }
@@ -132,6 +579,8 @@ enum WaniError {
     RateLimit(Option<wanidata::RateLimit>),
     Connection(),
     Unprocessable(),
+    Unauthorized(),
+    NoAudioDevice(),
 }
 
 impl<T> From<PoisonError<T>> for WaniError {
@@ -156,6 +605,8 @@ impl Display for WaniError {
             WaniError::Usvg(e) => e.fmt(f),
             WaniError::Connection() => f.write_str("Error related to request connection."),
             WaniError::Unprocessable() => f.write_str("HTTP 422 Unprocessable Entity"),
+            WaniError::Unauthorized() => f.write_str("HTTP 401: Unauthorized. Make sure your wanikani auth token is correct, and hasn't been expired."),
+            WaniError::NoAudioDevice() => f.write_str("No audio device available"),
             WaniError::RateLimit(r) => {
                 match r {
                     Some(r) => f.write_str(&format!("Rate Limit Exceeded Error: {:?}", r)),
@@ -166,10 +617,12 @@ impl Display for WaniError {
     }
 }
 
+#[derive(Clone, Copy)]
 enum AnswerColor {
     Green,
     Red,
-    Gray
+    Gray,
+    Yellow,
 }
 
 struct SyncResult {
@@ -180,10 +633,13 @@ struct SyncResult {
 struct AudioInfo {
     url: String,
     content_type: String,
+    metadata: wanidata::PronunciationMetadata,
 }
 
 enum AudioMessage {
     PlayAudioMessage(PlayAudioMessage),
+    /// downloads (without playing) the audio for each subject id, so it plays right away later
+    Preload(Vec<(i32, Vec<AudioInfo>)>),
     Quit,
 }
 
@@ -212,7 +668,45 @@ struct ReviewStats {
     done: usize,
     failed: usize,
     guesses: usize,
-    total_reviews: usize
+    total_reviews: usize,
+    practice: bool,
+    /// show kanji composition hints for vocab. Only allowed while practicing
+    hints: bool,
+    /// only accept kanji readings of this type. Only allowed while practicing
+    reading_type: Option<wanidata::KanjiType>,
+    /// subjects answered incorrectly at least once this session
+    wrong_subject_ids: Vec<i32>,
+    /// allow studying an item's info before answering. Only allowed while practicing
+    relearn: bool,
+    /// subjects whose info was studied before answering this session
+    assisted_subject_ids: Vec<i32>,
+    /// when the session started, or when the last break reminder was dismissed
+    break_timer_start: Option<std::time::Instant>,
+    session_start: Option<std::time::Instant>,
+    /// reviews finished today, when shown in the header
+    today: Option<wanidata::TodayProgress>,
+    /// finished items that had no wrong answers
+    done_first_try: usize,
+    accuracy: AccuracyMetric,
+    accuracy_precision: wanidata::PercentPrecision,
+    /// stats line is hidden (focus_mode). Toggled with Tab
+    hide_stats: bool,
+}
+
+impl ReviewStats {
+    /// correct answers or items, and the total they're out of, counted by the accuracy config
+    fn correct_of_total(&self) -> (usize, usize) {
+        match self.accuracy {
+            AccuracyMetric::Answers => (self.guesses - self.failed, self.guesses),
+            AccuracyMetric::Items => (self.done_first_try, self.done),
+        }
+    }
+
+    /// percent correct this session. 100 before the first answer
+    fn correct_percentage(&self) -> i32 {
+        let (correct, total) = self.correct_of_total();
+        if total == 0 { 100 } else { (correct as f64 / total as f64 * 100.0) as i32 }
+    }
 }
 
 #[derive(Default, Debug)]
@@ -222,6 +716,49 @@ struct LoadedReviews {
     in_progress_reviews: Vec<NewReview>,
 }
 
+/// config name of the commands that can run without a subcommand, for repeat_last_command
+fn repeatable_command_name(command: &Command) -> Option<&'static str> {
+    match command {
+        Command::Summary | Command::S => Some("summary"),
+        Command::Review(_) | Command::R(_) => Some("review"),
+        Command::Lesson | Command::L => Some("lesson"),
+        _ => None,
+    }
+}
+
+/// parses the name of a command that can run without a subcommand
+fn parse_default_command(name: &str) -> Option<Command> {
+    match name {
+        "summary" | "s" => Some(Command::Summary),
+        "review" | "r" => Some(Command::Review(ReviewArgs::default())),
+        "lesson" | "l" => Some(Command::Lesson),
+        _ => None,
+    }
+}
+
+/// saves the subcommand so a bare `wani` runs it next time, when repeat_last_command is on
+async fn remember_last_command(args: &Args, p_config: &ProgramConfig, conn: &AsyncConnection) {
+    let Some(name) = args.command.as_ref().and_then(repeatable_command_name) else {
+        return;
+    };
+    if !p_config.repeat_last_command || p_config.read_only {
+        return;
+    }
+    let res = conn.call(move |c| {
+        c.execute(wanisql::INSERT_META, params![wanisql::META_LAST_COMMAND, name])?;
+        Ok(())
+    }).await;
+    if let Err(e) = res {
+        eprintln!("Error saving last command: {}", e);
+    }
+}
+
+fn get_last_command(p_config: &ProgramConfig) -> Option<Command> {
+    let c = setup_connection(p_config).ok()?;
+    let name: String = c.query_row(wanisql::SELECT_META, params![wanisql::META_LAST_COMMAND], |r| r.get(0)).ok()?;
+    parse_default_command(&name)
+}
+
 #[derive(Default)]
 enum RequestMethod {
     #[default]
@@ -237,24 +774,66 @@ struct RequestInfo<'a, T: serde::Serialize + Sized> {
     query: Option<Vec<(&'a str, &'a str)>>,
     headers: Option<Vec<(String, String)>>,
     json: Option<T>,
+    /// keep the untyped response in WaniResp.raw
+    keep_raw: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), WaniError> {
     let args = Args::parse();
 
-    match &args.command {
+    let p_config = get_program_config(&args);
+
+    // Logging is set up before any command runs, so it covers the whole session
+    if let Ok(ProgramConfig { log_file: Some(path), .. }) = &p_config {
+        if let Err(e) = wanilog::init(path) {
+            eprintln!("Could not open log file {}: {}", path.display(), e);
+        }
+    }
+
+    let default_command;
+    let command = match &args.command {
+        Some(c) => Some(c),
+        None => {
+            default_command = p_config.map(|c| {
+                let last_command = if c.repeat_last_command { get_last_command(&c) } else { None };
+                last_command.unwrap_or(c.default_command)
+            });
+            default_command.as_ref().ok()
+        },
+    };
+
+    match command {
         Some(c) => {
             match c {
                 Command::Summary => command_summary(&args).await,
                 Command::S => command_summary(&args).await,
                 Command::Init => command_init(&get_program_config(&args)?),
-                Command::Sync => command_sync(&args, false).await,
-                Command::ForceSync => command_sync(&args, true).await,
-                Command::Review => command_review(&args).await,
-                Command::R => command_review(&args).await,
+                Command::Sync { up_to_level, with_images } => command_sync(&args, false, *up_to_level, *with_images).await,
+                Command::ForceSync => command_sync(&args, true, None, false).await,
+                Command::Review(r) => command_review(&args, r).await,
+                Command::R(r) => command_review(&args, r).await,
                 Command::Lesson => command_lesson(&args).await,
                 Command::L => command_lesson(&args).await,
+                Command::Apprentice => command_apprentice(&args).await,
+                Command::RetentionCheck { count } => command_retention_check(&args, *count).await,
+                Command::Practice { level, ids, count } => command_practice(&args, level, ids, *count).await,
+                Command::Confusions { count } => command_confusions(&args, *count).await,
+                Command::Stats { period, from, to } => command_stats(&args, *period, *from, *to).await,
+                Command::Tree { subject_id } => command_tree(&args, *subject_id).await,
+                Command::VerifyReviews { remove_recorded } => command_verify_reviews(&args, *remove_recorded).await,
+                Command::ImportDeck { path, deck } => command_import_deck(&args, path, deck.clone()).await,
+                Command::Cram { deck, count } => command_cram(&args, deck, *count).await,
+                Command::Version => command_version(&args),
+                Command::HelpKeys => command_help_keys(),
+                Command::Doctor => command_doctor(),
+                Command::Preload => command_preload(&args).await,
+                Command::DumpConfig => command_dump_config(&args),
+                Command::Grade { subject_id, answer, meaning, reading: _, json } => command_grade(&args, *subject_id, answer, *meaning, *json).await,
+                Command::Suspend { subject_id } => command_suspend(&args, *subject_id).await,
+                Command::Unsuspend { subject_id } => command_unsuspend(&args, *subject_id).await,
+                Command::MarkKnown { subject_id, yes } => command_mark_known(&args, *subject_id, *yes).await,
+                Command::Backup { dest, restore } => command_backup(&args, dest, restore),
             };
         },
         None => command_summary(&args).await,
@@ -263,7 +842,24 @@ async fn main() -> Result<(), WaniError> {
     Ok(())
 }
 
-fn play_audio(audio_path: &PathBuf) -> Result<(), WaniError> {
+/// set once opening the audio output device fails, so audio isn't retried for the rest of the run
+static NO_AUDIO_DEVICE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+static AUDIO_DEVICE_CHECK: std::sync::Once = std::sync::Once::new();
+
+/// The first call opens the output device to check for one, so even the first 'j' press can
+/// report a missing device instead of failing silently in the audio task
+fn no_audio_device() -> bool {
+    AUDIO_DEVICE_CHECK.call_once(|| {
+        if OutputStream::try_default().is_err() {
+            NO_AUDIO_DEVICE.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    });
+    NO_AUDIO_DEVICE.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Failing to open the output device returns WaniError::NoAudioDevice, and disables audio from then on.
+/// A file that can't be opened or decoded is only an error for that file
+fn play_audio(audio_path: &PathBuf, volume: f32) -> Result<(), WaniError> {
     match OutputStream::try_default() {
         Ok(t) => {
             let file_res = File::open(&audio_path);
@@ -271,7 +867,11 @@ fn play_audio(audio_path: &PathBuf) -> Result<(), WaniError> {
                 return Err(WaniError::Generic(format!("Could not open audio file: {}", audio_path.display())));
             }
 
-            let sink = Sink::try_new(&t.1).expect("Sink broke");
+            let Ok(sink) = Sink::try_new(&t.1) else {
+                NO_AUDIO_DEVICE.store(true, std::sync::atomic::Ordering::SeqCst);
+                return Err(WaniError::NoAudioDevice());
+            };
+            sink.set_volume(volume);
             let source = Decoder::new(BufReader::new(file_res.unwrap()));
             match source {
                 Ok(s) => {
@@ -285,30 +885,24 @@ fn play_audio(audio_path: &PathBuf) -> Result<(), WaniError> {
             }
 
         },
-        Err(e) => {
-            return Err(WaniError::Generic(format!("Error opening default output stream. {}", e)));
+        Err(_) => {
+            NO_AUDIO_DEVICE.store(true, std::sync::atomic::Ordering::SeqCst);
+            return Err(WaniError::NoAudioDevice());
         }
     }
 }
 
-async fn print_lesson_screen(term: &Term, meaning_line: &Option<String>, rev_type: &ReviewType, subject: &Subject, image_cache: &PathBuf, web_config: &WaniWebConfig) -> Result<(usize, usize, Vec<String>), WaniError> {
-    let width = term.size().1;
-    let radical_width = u32::from(width * 5 / 8);
-    let width = width.into();
+async fn print_lesson_screen(term: &Term, meaning_line: &Option<String>, rev_type: &ReviewType, subject: &Subject, image_cache: &PathBuf, web_config: &WaniWebConfig, large_characters: bool) -> Result<(usize, usize, Vec<String>), WaniError> {
+    let radical_width = radical_width(term);
+    let width = term.size().1.into();
 
     term.clear_screen()?;
     if let ReviewType::Lesson(subj_counts) = rev_type {
         print_lesson_status(subj_counts, term, width)?;
     }
 
-    let char_line = get_chars_for_subj(&subject, image_cache, radical_width, web_config).await?;
-    let char_lines = get_chars_for_subj(&subject, image_cache, radical_width, web_config).await?;
-    let padded_chars = char_lines.iter().map(|l| pad_str(l, width, console::Alignment::Center, None));
-    let char_lines = padded_chars.map(|pc| match subject {
-        Subject::Radical(_) => style(pc).white().on_blue().to_string(),
-        Subject::Kanji(_) => style(pc).white().on_red().to_string(),
-        _ => style(pc).white().on_magenta().to_string(),
-    }).collect_vec();
+    let char_line = wanidata::fallback_char_lines(subject, get_chars_for_subj(&subject, image_cache, radical_width, web_config).await?);
+    let char_lines = style_char_lines(subject, &char_line, width, console::Alignment::Center, large_characters);
     for line in &char_lines {
         term.write_line(line)?;
     }
@@ -319,17 +913,25 @@ async fn print_lesson_screen(term: &Term, meaning_line: &Option<String>, rev_typ
     Ok((width, width * 5 / 8, char_line))
 }
 
-async fn print_review_screen<'a>(term: &Term, rev_type: &mut ReviewType, align: console::Alignment, subject: &Subject, review_type_text: &str, toast: &Option<&str>, image_cache: &PathBuf, web_config: &WaniWebConfig, input: &str, color: Option<&AnswerColor>) -> Result<(usize, usize, Vec<String>), WaniError> {
+async fn print_review_screen<'a>(term: &Term, rev_type: &mut ReviewType, align: console::Alignment, subject: &Subject, review_type_text: &str, toast: &Option<&str>, image_cache: &PathBuf, web_config: &WaniWebConfig, input: &str, color: Option<&AnswerColor>, large_characters: bool) -> Result<(usize, usize, Vec<String>), WaniError> {
     term.clear_screen()?;
-    let (_, width) = term.size();
-    let radical_width = u32::from(width * 5 / 8);
-    let width: usize = usize::from(width);
+    let radical_width = radical_width(term);
+    let width: usize = usize::from(term.size().1);
 
     // Top line changes based on review type
     match rev_type {
+        // The empty line keeps the rest of the screen where it would be
+        ReviewType::Review(ReviewStats { hide_stats: true, .. }) => {
+            term.write_line("")?;
+        },
         ReviewType::Review(stats) => {
-            let correct_percentage = if stats.guesses == 0 { 100 } else { ((stats.guesses as f64 - stats.failed as f64) / stats.guesses as f64 * 100.0) as i32 };
-            term.write_line(pad_str(&format!("{}: {}%, {}: {}, {}: {}", 
+            let (correct, total) = stats.correct_of_total();
+            let correct_percentage = wanidata::format_accuracy(correct, total, stats.accuracy_precision);
+            let practice_label = if stats.practice { "PRACTICE (not submitted) " } else { "" };
+            let today_label = stats.today.map(|t| format!("{} | ", t)).unwrap_or_default();
+            term.write_line(pad_str(&format!("{}{}{}: {}, {}: {}, {}: {}", 
+                                             practice_label,
+                                             today_label,
                                              Emoji("\u{1F44D}", "Correct"), correct_percentage, 
                                              Emoji("\u{2705}", "Done"), stats.done, 
                                              Emoji("\u{1F4E9}", "Remaining"), stats.total_reviews - stats.done), 
@@ -341,13 +943,8 @@ async fn print_review_screen<'a>(term: &Term, rev_type: &mut ReviewType, align:
         },
     }
 
-    let char_lines = get_chars_for_subj(&subject, image_cache, radical_width, web_config).await?;
-    let padded_chars = char_lines.iter().map(|l| pad_str(l, width, align, None));
-    let char_lines = padded_chars.map(|pc| match subject {
-        Subject::Radical(_) => style(pc).white().on_blue().to_string(),
-        Subject::Kanji(_) => style(pc).white().on_red().to_string(),
-        _ => style(pc).white().on_magenta().to_string(),
-    }).collect_vec();
+    let char_lines = wanidata::fallback_char_lines(subject, get_chars_for_subj(&subject, image_cache, radical_width, web_config).await?);
+    let char_lines = style_char_lines(subject, &char_lines, width, align, large_characters);
     for char_line in &char_lines {
         term.write_line(char_line)?;
     }
@@ -364,6 +961,9 @@ async fn print_review_screen<'a>(term: &Term, rev_type: &mut ReviewType, align:
         AnswerColor::Gray => {
             style(input_line.deref()).white().on_color256(238).to_string()
         },
+        AnswerColor::Yellow => {
+            style(input_line.deref()).black().on_yellow().to_string()
+        },
     } } else { input_line.to_string() };
 
     term.write_line(&input_formatted)?;
@@ -374,6 +974,24 @@ async fn print_review_screen<'a>(term: &Term, rev_type: &mut ReviewType, align:
     Ok((width, width * 5 / 8, char_lines))
 }
 
+/// pads and colors the subject's character lines. With large_characters, a single line of
+/// characters is drawn at double width and height (DEC double-size lines), taking up two rows
+fn style_char_lines(subject: &Subject, char_lines: &[String], width: usize, align: console::Alignment, large_characters: bool) -> Vec<String> {
+    let color = |s: String| match subject {
+        Subject::Radical(_) => style(s).white().on_blue().to_string(),
+        Subject::Kanji(_) => style(s).white().on_red().to_string(),
+        _ => style(s).white().on_magenta().to_string(),
+    };
+
+    if let (true, [line]) = (large_characters, char_lines) {
+        // double-size lines fit half as many columns
+        let padded = color(pad_str(line, width / 2, align, None).to_string());
+        return vec![format!("\x1b#3{}", padded), format!("\x1b#4{}", padded)];
+    }
+
+    char_lines.iter().map(|l| color(pad_str(l, width, align, None).to_string())).collect_vec()
+}
+
 fn print_lesson_status(subj_counts: &SubjectCounts, term: &Term, width: usize) -> Result<(), WaniError> {
     let msg_emoji = Emoji("\u{1F4E9}", " ");
     let line = &format!("R{}{} K{}{} V{}{}", 
@@ -385,6 +1003,10 @@ fn print_lesson_status(subj_counts: &SubjectCounts, term: &Term, width: usize) -
 }
 
 async fn save_lessons(reviews: HashMap<i32, NewReview>, rate_limit: RateLimitBox, web_config: WaniWebConfig, conn: AsyncConnection) -> Result<(), WaniError> {
+    if web_config.read_only {
+        // Saved lessons would be started on WaniKani by a later session
+        return Ok(());
+    }
     let reviews = Arc::new(reviews);
     let rev = reviews.clone();
     conn.call(move |conn| {
@@ -412,6 +1034,10 @@ async fn save_lessons(reviews: HashMap<i32, NewReview>, rate_limit: RateLimitBox
 
 async fn save_lessons_to_wanikani<'a, I>(lessons: I, rate_limit: &RateLimitBox, web_config: &WaniWebConfig, conn: &AsyncConnection) -> Result<(), WaniError> 
 where I: Iterator<Item = &'a NewReview> {
+    if web_config.offline || web_config.read_only {
+        return Ok(());
+    }
+
     let mut join_set = JoinSet::new();
     let mut saved_assignments = vec![];
     for review in lessons {
@@ -504,12 +1130,103 @@ async fn save_reviews(reviews: HashMap<i32, NewReview>, conn: AsyncConnection, w
     Ok(())
 }
 
+/// replaces the local copies of the reviews
+async fn store_reviews_locally(reviews: Vec<NewReview>, conn: &AsyncConnection) -> Result<(), tokio_rusqlite::Error> {
+    if reviews.is_empty() {
+        return Ok(());
+    }
+
+    conn.call(move |conn| {
+        let mut tx = conn.transaction()?;
+        for review in &reviews {
+            tx.execute(wanisql::REMOVE_REVIEW, [review.assignment_id])?;
+            if let Err(e) = wanisql::store_review(review, &mut tx) {
+                eprintln!("Error saving review locally: {}", e);
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }).await
+}
+
+/// submits finished reviews from the local DB every interval until told to stop,
+/// then one last time
+fn spawn_review_submitter(interval: std::time::Duration, conn: &AsyncConnection, web_config: &WaniWebConfig, rate_limit: &RateLimitBox) -> (tokio::sync::oneshot::Sender<()>, JoinHandle<()>) {
+    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel();
+    let conn = conn.clone();
+    let web_config = web_config.clone();
+    let rate_limit = rate_limit.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            let stop = tokio::select! {
+                _ = &mut stop_rx => true,
+                _ = tokio::time::sleep(interval) => false,
+            };
+            let done_reviews = select_data(wanisql::SELECT_REVIEWS, &conn, wanisql::parse_review, []).await
+                .map(|reviews| reviews.into_iter().filter(|r| matches!(r.status, ReviewStatus::Done)).collect_vec());
+            if let Ok(done_reviews) = done_reviews {
+                let _ = save_reviews_to_wanikani(done_reviews.iter(), &rate_limit, &web_config, &conn, false).await;
+            }
+            if stop {
+                break;
+            }
+        }
+    });
+    (stop_tx, task)
+}
+
+async fn stop_review_submitter(submitter: Option<(tokio::sync::oneshot::Sender<()>, JoinHandle<()>)>) -> Result<(), WaniError> {
+    if let Some((stop_tx, task)) = submitter {
+        let _ = stop_tx.send(());
+        task.await?;
+    }
+    Ok(())
+}
+
+/// assignment ids of reviews being submitted, so the background submitter and the end of a
+/// batch don't both submit the same review
+static SUBMITTING_REVIEWS: std::sync::Mutex<BTreeSet<i32>> = std::sync::Mutex::new(BTreeSet::new());
+
+/// reviews claimed for submission, released when dropped so no exit path leaves one claimed
+struct ReviewClaims(Vec<i32>);
+
+impl ReviewClaims {
+    /// claims the review, unless it's already being submitted
+    fn claim(&mut self, id: i32) -> bool {
+        let mut submitting = SUBMITTING_REVIEWS.lock().unwrap_or_else(PoisonError::into_inner);
+        if !submitting.insert(id) {
+            return false;
+        }
+        self.0.push(id);
+        true
+    }
+}
+
+impl Drop for ReviewClaims {
+    fn drop(&mut self) {
+        let mut submitting = SUBMITTING_REVIEWS.lock().unwrap_or_else(PoisonError::into_inner);
+        for id in &self.0 {
+            submitting.remove(id);
+        }
+    }
+}
+
 async fn save_reviews_to_wanikani<'a, I>(reviews: I, rate_limit: &RateLimitBox, web_config: &WaniWebConfig, conn: &AsyncConnection, debug: bool) -> Result<Vec<wanidata::Review>, WaniError>
 where I: Iterator<Item = &'a NewReview> {
+    if web_config.offline || web_config.read_only {
+        return Ok(vec![]);
+    }
+
+    // Declared before the JoinSet so in-flight requests are aborted before the claims are released.
+    // The local copy is only removed once WaniKani has the review.
+    let mut claims = ReviewClaims(vec![]);
     let mut join_set = JoinSet::new();
     for review in reviews {
         if let ReviewStatus::Done = review.status {
             let id = review.assignment_id;
+            if !claims.claim(id) {
+                continue;
+            }
             let new_review = wanidata::NewReviewRequest {
                 review: review.clone()
             };
@@ -520,6 +1237,7 @@ where I: Iterator<Item = &'a NewReview> {
                 json: Some(new_review),
                 query: None,
                 headers: None,
+                keep_raw: false,
             };
 
             let rate_limit = rate_limit.clone();
@@ -611,6 +1329,12 @@ async fn command_lesson(args: &Args) {
         return;
     }
     let mut p_config = p_config.unwrap();
+    if p_config.read_only {
+        println!("Read-only mode: lessons you finish won't be saved or started on WaniKani.");
+    }
+    if p_config.lesson_batch_size == 0 {
+        println!("lesson_batch_size must be at least 1. Using batches of 1 lesson.");
+    }
 
     let rate_limit = Arc::new(Mutex::new(None));
     let web_config = get_web_config(&p_config);
@@ -618,12 +1342,13 @@ async fn command_lesson(args: &Args) {
         eprintln!("{}", e);
         return;
     }
-    let web_config = web_config.unwrap();
+    let mut web_config = web_config.unwrap();
 
     let conn = setup_async_connection(&p_config).await;
     match conn {
         Err(e) => eprintln!("{}", e),
         Ok(c) => {
+            remember_last_command(args, &p_config, &c).await;
             let mut ass_cache_info = CacheInfo { id: wanisql::CACHE_TYPE_SUBJECTS, ..Default::default() };
             let mut c_infos = wanisql::get_all_cache_infos(&c, false).await;
             if let Ok(c_infos) = &mut c_infos {
@@ -632,10 +1357,26 @@ async fn command_lesson(args: &Args) {
                 }
             }
 
-            println!("Syncing assignments. . .");
-            cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+            check_online(&mut web_config, &c, &rate_limit).await;
+            if !web_config.offline {
+                println!("Syncing assignments. . .");
+                cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+                let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, get_max_sync_level(&p_config.user, None)).await;
+            }
+            if let Some(ceiling) = p_config.apprentice_ceiling {
+                match select_data(wanisql::SELECT_APPRENTICE_COUNTS, &c, wanisql::parse_srs_stage_count, []).await {
+                    Ok(counts) => {
+                        let apprentice: usize = counts.iter().map(|(_, count)| count).sum();
+                        if apprentice >= ceiling {
+                            println!("You have {} Apprentice items, and your apprentice_ceiling is {}.", apprentice, ceiling);
+                            println!("Do some reviews to bring that down before starting new lessons.");
+                            return;
+                        }
+                    },
+                    Err(e) => eprintln!("Error loading Apprentice count. Error: {}", e),
+                }
+            }
             let is_user_restricted = p_config.user.is_restricted();
-            let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, is_user_restricted).await;
             let assignments = select_data(wanisql::SELECT_LESSON_ASSIGNMENTS, &c, wanisql::parse_assignment, []).await;
             if let Err(e) = assignments {
                 eprintln!("Error loading assignments. Error: {}", e);
@@ -644,6 +1385,7 @@ async fn command_lesson(args: &Args) {
             let assignments = assignments.unwrap();
             if assignments.len() == 0 {
                 println!("No assignments for now.");
+                print_next_review_time(&c, Utc::now()).await;
                 return;
             }
 
@@ -684,6 +1426,7 @@ async fn command_lesson(args: &Args) {
                 return;
             }
             let subjects_by_id = subjects_by_id.unwrap();
+            let subjects_by_id = resync_empty_subjects(&assignments, subjects_by_id, &c, &web_config, &p_config, &rate_limit).await;
 
             let audio_cache = get_audio_path(&p_config);
             if let Err(e) = audio_cache {
@@ -734,10 +1477,15 @@ async fn command_lesson(args: &Args) {
                         }}).collect_vec();
             }
 
+            // Ctrl+C only interrupts input, so lessons can confirm and save before quitting
+            let _ = ctrlc::set_handler(|| {
+                println!("\nreceived Ctrl+C!\nQuitting lessons...");
+            });
+
             let res = do_lessons(assignments, subjects_by_id, audio_cache, &web_config, &p_config, &image_cache, &c, &rate_limit).await;
             match res {
                 Ok(_) => {},
-                Err(e) => {eprintln!("{:?}", e)},
+                Err(e) => {eprintln!("{}", e)},
             }
 
             if let Err(e) = save_initial_lessons_task.await {
@@ -748,29 +1496,14 @@ async fn command_lesson(args: &Args) {
 }
 
 async fn do_lessons(mut assignments: Vec<Assignment>, subjects_by_id: HashMap<i32, Subject>, audio_cache: PathBuf, web_config: &WaniWebConfig, p_config: &ProgramConfig, image_cache: &PathBuf, c: &AsyncConnection, rate_limit: &RateLimitBox) -> Result<(), WaniError> {
+    check_terminal_size(&Term::stdout(), p_config)?;
+    let alt_screen = AltScreen::enter(p_config.alternate_screen);
     assignments.reverse();
-    let ideal_batch_size = 5;
-    let (audio_tx, mut rx) = mpsc::channel::<AudioMessage>(5);
-    let audio_web_config = web_config.clone();
-    let audio_task = tokio::spawn(async move {
-        let audio_cache = audio_cache;
-        let mut last_finish_time = std::time::Instant::now();
-        while let Some(m) = rx.recv().await {
-            match m {
-                AudioMessage::PlayAudioMessage(msg) => {
-                    if msg.send_time < last_finish_time {
-                        continue;
-                    }
-                    let _ = play_audio_for_subj(msg.id, msg.audios, &audio_cache, &audio_web_config).await;
-                    last_finish_time = std::time::Instant::now();
-                },
-
-                AudioMessage::Quit => {
-                    break;
-                },
-            }
-        }
-    });
+    let ideal_batch_size = match p_config.lesson_flow {
+        LessonFlow::Batched => max(1, p_config.lesson_batch_size),
+        LessonFlow::AllCardsFirst => assignments.len(),
+    };
+    let (audio_tx, audio_task) = spawn_audio_task(audio_cache, web_config, p_config);
 
     let mut subject_counts = SubjectCounts::default();
     for ass in &assignments {
@@ -782,6 +1515,8 @@ async fn do_lessons(mut assignments: Vec<Assignment>, subjects_by_id: HashMap<i3
         }
     }
 
+    let total_lessons = assignments.len();
+    let session_start = std::time::Instant::now();
     let mut rev_type = ReviewType::Lesson(subject_counts);
     let mut save_lesson_tasks = JoinSet::new();
     while assignments.len() > 0 {
@@ -791,13 +1526,176 @@ async fn do_lessons(mut assignments: Vec<Assignment>, subjects_by_id: HashMap<i3
             batch.push(assignments.remove(i));
         }
 
-        let _ = do_lesson_batch(batch, &mut rev_type, &subjects_by_id, image_cache, web_config, c, &audio_tx, p_config, rate_limit, &mut save_lesson_tasks).await;
+        let res = do_lesson_batch(batch, &mut rev_type, &subjects_by_id, image_cache, web_config, c, &audio_tx, p_config, rate_limit, &mut save_lesson_tasks).await;
+        if let Err(WaniError::Io(e)) = &res {
+            if e.kind() == io::ErrorKind::Interrupted {
+                println!("Saving lessons...");
+                break;
+            }
+        }
     }
 
+    drop(alt_screen);
     while let Some(_) = save_lesson_tasks.join_next().await {
         // Join all
     }
 
+    if let ReviewType::Lesson(remaining) = &rev_type {
+        let done = total_lessons - (remaining.radical_count + remaining.kanji_count + remaining.vocab_count);
+        run_on_complete_command(p_config, "lesson", done, None, session_start.elapsed());
+    }
+    stop_audio_task(&audio_tx, audio_task).await
+}
+
+/// runs the on_complete_command config, if set, with the session's results in WANI_* environment
+/// variables. The command runs in the background, and wani doesn't wait for it to finish
+fn run_on_complete_command(p_config: &ProgramConfig, session: &str, done: usize, accuracy: Option<i32>, duration: std::time::Duration) {
+    let Some(command) = &p_config.on_complete_command else {
+        return;
+    };
+
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let res = std::process::Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .env("WANI_SESSION", session)
+        .env("WANI_REVIEWS_DONE", done.to_string())
+        .env("WANI_ACCURACY", accuracy.map(|a| a.to_string()).unwrap_or_default())
+        .env("WANI_DURATION_SECONDS", duration.as_secs().to_string())
+        .stdin(std::process::Stdio::null())
+        .spawn();
+    if let Err(e) = res {
+        eprintln!("Could not run on_complete_command: {}", e);
+    }
+}
+
+/// runs the on_complete_command for a finished review or practice session
+fn run_review_complete_command(p_config: &ProgramConfig, rev_type: &ReviewType) {
+    if let ReviewType::Review(stats) = rev_type {
+        let session = if stats.practice { "practice" } else { "review" };
+        let duration = stats.session_start.map(|s| s.elapsed()).unwrap_or_default();
+        run_on_complete_command(p_config, session, stats.done, Some(stats.correct_percentage()), duration);
+    }
+}
+
+/// signals an answer's result with a desktop notification when built with the
+/// desktop-notifications feature, falling back to the terminal bell
+fn notify_answer(term: &Term, correct: bool) {
+    #[cfg(feature = "desktop-notifications")]
+    {
+        let body = if correct { "Correct" } else { "Incorrect" };
+        if notify_rust::Notification::new().summary("wani").body(body).show().is_ok() {
+            return;
+        }
+    }
+    #[cfg(not(feature = "desktop-notifications"))]
+    let _ = correct;
+
+    let _ = term.write_str("\x07");
+}
+
+/// the alternate screen is in use, and should be left before anything else is printed
+static ALT_SCREEN_ACTIVE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+fn leave_alt_screen() {
+    if ALT_SCREEN_ACTIVE.swap(false, std::sync::atomic::Ordering::SeqCst) {
+        let _ = Term::stdout().write_str("\x1b[?1049l");
+    }
+}
+
+/// switches the terminal to its alternate screen (like less or vim do) while alive, so a session
+/// doesn't leave anything in the scrollback. Leaves it when dropped, including on ctrl+c (which
+/// ends sessions normally) and panics
+struct AltScreen;
+
+impl AltScreen {
+    fn enter(enabled: bool) -> Option<AltScreen> {
+        let term = Term::stdout();
+        if !enabled || !term.is_term() || term.write_str("\x1b[?1049h").is_err() {
+            return None;
+        }
+        ALT_SCREEN_ACTIVE.store(true, std::sync::atomic::Ordering::SeqCst);
+
+        static PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+        PANIC_HOOK.call_once(|| {
+            // Leave first, so the panic message isn't printed to the alternate screen and lost
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                leave_alt_screen();
+                default_hook(info);
+            }));
+        });
+        Some(AltScreen)
+    }
+}
+
+impl Drop for AltScreen {
+    fn drop(&mut self) {
+        leave_alt_screen();
+    }
+}
+
+/// spawns a task that plays subject audio sent to the returned channel
+/// With no_audio, there's no task, and messages sent to the channel are dropped
+fn spawn_audio_task(audio_cache: PathBuf, web_config: &WaniWebConfig, p_config: &ProgramConfig) -> (Sender<AudioMessage>, Option<JoinHandle<()>>) {
+    let (audio_tx, mut rx) = mpsc::channel::<AudioMessage>(5);
+    if p_config.no_audio {
+        return (audio_tx, None);
+    }
+    let audio_web_config = web_config.clone();
+    let audio_formats = p_config.audio_formats.clone();
+    let preferred_voice = p_config.preferred_voice.clone();
+    let volume = p_config.volume;
+    let audio_task = tokio::spawn(async move {
+        const MAX_CONCURRENT_PRELOADS: usize = 4;
+        let audio_cache = audio_cache;
+        let mut last_finish_time = std::time::Instant::now();
+        // Dropped when the task quits, which aborts any preloads still running
+        let mut preloads = JoinSet::new();
+        let preload_limit = Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_PRELOADS));
+        while let Some(m) = rx.recv().await {
+            match m {
+                AudioMessage::PlayAudioMessage(msg) => {
+                    if msg.send_time < last_finish_time {
+                        continue;
+                    }
+                    let _ = play_audio_for_subj(msg.id, msg.audios, &audio_cache, &audio_web_config, &audio_formats, preferred_voice.as_deref(), volume).await;
+                    last_finish_time = std::time::Instant::now();
+                },
+
+                AudioMessage::Preload(subjects) => {
+                    if audio_web_config.offline || no_audio_device() {
+                        continue;
+                    }
+                    while preloads.try_join_next().is_some() {}
+                    for (id, audios) in subjects {
+                        let audio_cache = audio_cache.clone();
+                        let web_config = audio_web_config.clone();
+                        let audio_formats = audio_formats.clone();
+                        let preferred_voice = preferred_voice.clone();
+                        let preload_limit = preload_limit.clone();
+                        preloads.spawn(async move {
+                            let Ok(_permit) = preload_limit.acquire_owned().await else {
+                                return;
+                            };
+                            let _ = preload_audio(id, &audios, &audio_cache, &web_config, &audio_formats, preferred_voice.as_deref()).await;
+                        });
+                    }
+                },
+
+                AudioMessage::Quit => {
+                    break;
+                },
+            }
+        }
+    });
+    (audio_tx, Some(audio_task))
+}
+
+async fn stop_audio_task(audio_tx: &Sender<AudioMessage>, audio_task: Option<JoinHandle<()>>) -> Result<(), WaniError> {
+    let Some(audio_task) = audio_task else {
+        return Ok(());
+    };
     match audio_tx.send(AudioMessage::Quit).await {
         Ok(_) => {
             audio_task.await?;
@@ -806,45 +1704,162 @@ async fn do_lessons(mut assignments: Vec<Assignment>, subjects_by_id: HashMap<i3
             audio_task.abort();
         },
     }
-
     Ok(())
 }
 
-fn show_lesson_help(term: &Term, align: console::Alignment) {
-    let width = term.size().1.into();
-    let _ = term.clear_screen();
-    let _ = term.write_line(pad_str("Hotkeys", width, align, None).deref());
-    let _ = term.write_line(pad_str("?: Show hotkeys menu", width, align, None).deref());
-    let _ = term.write_line(pad_str("'n' and 'N' toggle through flashcard pages", width, align, None).deref());
-    let _ = term.write_line(pad_str("'a' and 'd' also toggle through flashcard pages", width, align, None).deref());
-    let _ = term.write_line(pad_str("arrow keys also toggle through flashcard pages", width, align, None).deref());
-    let _ = term.write_line(pad_str("j: play subject audio", width, align, None).deref());
-    let _ = term.write_line(pad_str("g: skip to next subject flashcard", width, align, None).deref());
-    let _ = term.write_line(pad_str("q: skip to quiz", width, align, None).deref());
-    let _ = term.flush();
-    let _ = term.read_key();
+/// hotkeys on lesson flashcards. Shown by '?' and the help-keys command
+const LESSON_HOTKEYS: &[&str] = &[
+    "?: Show hotkeys menu",
+    "'n' and 'N' toggle through flashcard pages",
+    "'a' and 'd' also toggle through flashcard pages",
+    "arrow keys also toggle through flashcard pages",
+    "j: play subject audio",
+    "g: skip to next subject flashcard",
+    "q: skip to quiz",
+];
+
+/// hotkeys after answering in a review or lesson quiz. Shown by '?' and the help-keys command
+const REVIEW_HOTKEYS: &[&str] = &[
+    "?: Show hotkeys menu",
+    "j: play subject audio",
+    "f: open/close subject information",
+    "'n' and 'N' toggle through information pages",
+    "h: show/hide kanji composition hint (practice with --hints only)",
+    "!: study the item's info before answering (practice with --relearn only)",
+    "e: edit your meaning/reading note",
+    "Tab: show/hide session stats (focus_mode only, also while typing)",
+    "Esc: dismiss a message (persistent_toasts only)",
+];
+
+/// reads keys on another thread, so waiting for one can time out. A read that timed out keeps
+/// waiting, and the next read takes its key instead of racing it for the terminal
+#[derive(Default)]
+struct KeyReader {
+    pending: Option<std::sync::mpsc::Receiver<io::Result<console::Key>>>,
 }
 
-fn show_review_help(term: &Term, align: console::Alignment) {
+impl KeyReader {
+    fn read_key(&mut self, term: &Term) -> io::Result<console::Key> {
+        match self.pending.take() {
+            Some(rx) => rx.recv().unwrap_or_else(|_| Err(io::ErrorKind::BrokenPipe.into())),
+            None => term.read_key(),
+        }
+    }
+
+    /// returns None if no key was pressed before the deadline
+    fn read_key_until(&mut self, deadline: std::time::Instant) -> io::Result<Option<console::Key>> {
+        let rx = self.pending.take().unwrap_or_else(|| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                let _ = tx.send(Term::stdout().read_key());
+            });
+            rx
+        });
+        match rx.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+            Ok(key) => key.map(Some),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                self.pending = Some(rx);
+                Ok(None)
+            },
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
+
+fn show_hotkeys(term: &Term, align: console::Alignment, hotkeys: &[&str]) {
     let width = term.size().1.into();
     let _ = term.clear_screen();
     let _ = term.write_line(pad_str("Hotkeys", width, align, None).deref());
-    let _ = term.write_line(pad_str("?: Show hotkeys menu", width, align, None).deref());
-    let _ = term.write_line(pad_str("j: play subject audio", width, align, None).deref());
-    let _ = term.write_line(pad_str("f: open/close subject information", width, align, None).deref());
-    let _ = term.write_line(pad_str("'n' and 'N' toggle through information pages", width, align, None).deref());
+    for hotkey in hotkeys {
+        let _ = term.write_line(pad_str(hotkey, width, align, None).deref());
+    }
     let _ = term.flush();
     let _ = term.read_key();
 }
 
-async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewType, subjects: &HashMap<i32, Subject>, image_cache: &PathBuf, web_config: &WaniWebConfig, conn: &AsyncConnection, audio_tx: &Sender<AudioMessage>, p_config: &ProgramConfig, rate_limit: &RateLimitBox, save_lesson_tasks: &mut JoinSet<Result<(), WaniError>>) -> Result<(), WaniError> {
-    if batch.len() == 0 {
-        return Ok(());
-    }
+fn show_lesson_help(term: &Term, align: console::Alignment) {
+    show_hotkeys(term, align, LESSON_HOTKEYS);
+}
+
+/// lists the items in a lesson batch, and waits for a keypress to start the quiz
+fn show_lesson_quiz_preview(term: &Term, batch: &[Assignment], subjects: &HashMap<i32, Subject>, p_config: &ProgramConfig) -> Result<(), WaniError> {
+    let width = term.size().1.into();
+    let align = console::Alignment::Center;
+    loop {
+        term.clear_screen()?;
+        term.write_line(&pad_str("Up next in the quiz:", width, align, None))?;
+        term.write_line("")?;
+        for subject in batch.iter().filter_map(|a| subjects.get(&a.data.subject_id)) {
+            let (characters, meaning) = match subject {
+                Subject::Radical(r) => (r.data.characters.clone().unwrap_or_else(|| r.data.slug.clone()), r.primary_meanings().next()),
+                Subject::Kanji(k) => (k.data.characters.clone(), k.primary_meanings().next()),
+                Subject::Vocab(v) => (v.data.characters.clone(), v.primary_meanings().next()),
+                Subject::KanaVocab(kv) => (kv.data.characters.clone(), kv.primary_meanings().next()),
+            };
+            let line = format!("{}  {}", characters, meaning.map(|m| m.as_str()).unwrap_or_default());
+            term.write_line(&pad_str(&line, width, align, None))?;
+        }
+        term.write_line("")?;
+        term.write_line(&pad_str("Press any key to start the quiz", width, align, None))?;
+        term.flush()?;
+
+        match term.read_key() {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                if confirm_lesson_quit(term, p_config)? {
+                    return Err(e.into());
+                }
+            },
+            key => {
+                key?;
+                return Ok(());
+            },
+        }
+    }
+}
+
+/// asks whether to quit the lesson session. Returns true to quit.
+fn confirm_lesson_quit(term: &Term, p_config: &ProgramConfig) -> Result<bool, WaniError> {
+    if !p_config.confirm_lesson_quit {
+        return Ok(true);
+    }
+
+    confirm_prompt(term, &["Quit lessons?", "Completed lessons will be saved. Unfinished lessons will be available next time."])
+}
+
+/// shows the lines with a (y/n) prompt. Returns true for yes.
+fn confirm_prompt(term: &Term, lines: &[&str]) -> Result<bool, WaniError> {
+    let width = term.size().1.into();
+    let align = console::Alignment::Center;
+    term.clear_screen()?;
+    for line in lines {
+        term.write_line(pad_str(line, width, align, None).deref())?;
+    }
+    term.write_line(pad_str("(y/n)", width, align, None).deref())?;
+    term.flush()?;
+    loop {
+        match term.read_key() {
+            Ok(console::Key::Char('y' | 'Y')) => return Ok(true),
+            Ok(console::Key::Char('n' | 'N')) | Ok(console::Key::Escape) => return Ok(false),
+            // ctrl+c counts as yes
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(true),
+            Err(e) => return Err(e.into()),
+            _ => {},
+        }
+    }
+}
+
+fn show_review_help(term: &Term, align: console::Alignment) {
+    show_hotkeys(term, align, REVIEW_HOTKEYS);
+}
+
+async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewType, subjects: &HashMap<i32, Subject>, image_cache: &PathBuf, web_config: &WaniWebConfig, conn: &AsyncConnection, audio_tx: &Sender<AudioMessage>, p_config: &ProgramConfig, rate_limit: &RateLimitBox, save_lesson_tasks: &mut JoinSet<Result<(), WaniError>>) -> Result<(), WaniError> {
+    if batch.len() == 0 {
+        return Ok(());
+    }
 
     let term = Term::buffered_stdout();
     let align = console::Alignment::Center;
-    let wfmt_args = get_wfmt_args(&term);
+    let wfmt_args = get_wfmt_args(&term, p_config);
 
     let mut index = 0;
     'flashcards: loop {
@@ -878,19 +1893,33 @@ async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewTyp
                 })
             } else { None };
 
-            let (width, text_width, _) = print_lesson_screen(&term, &meaning_line, subj_counts, &subject, image_cache, web_config).await?;
+            let (width, text_width, _) = print_lesson_screen(&term, &meaning_line, subj_counts, &subject, image_cache, web_config, p_config.large_characters).await?;
             let lines = get_lesson_info_lines(subject, card_page, &wfmt_args, text_width, conn, width).await;
             if let None = lines {
                 index += 1;
                 break 'card;
             }
+            let mut lines = lines.unwrap();
+            if p_config.collapse_blank_lines {
+                lines = wanidata::collapse_blank_lines(lines);
+            }
 
-            for line in &lines.unwrap() {
+            for line in &lines {
                 term.write_line(&pad_str(line, width, align, None))?;
             }
             term.flush()?;
 
-            match term.read_key()? {
+            let key = match term.read_key() {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    if confirm_lesson_quit(&term, p_config)? {
+                        return Err(e.into());
+                    }
+                    continue 'card;
+                },
+                key => key?,
+            };
+
+            match key {
                 console::Key::ArrowLeft => {
                     if card_page > 0 {
                         card_page -= 1;
@@ -934,12 +1963,7 @@ async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewTyp
                                 let _ = audio_tx.send(AudioMessage::PlayAudioMessage(PlayAudioMessage{
                                     send_time: std::time::Instant::now(),
                                     id,
-                                    audios: audios.iter()
-                                        .map(|a| AudioInfo {
-                                            url: a.url.clone(),
-                                            content_type: a.content_type.clone(),
-                                        }).collect_vec(),
-
+                                    audios: audio_infos(&audios),
                                 })).await;
                             }
                         },
@@ -951,6 +1975,10 @@ async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewTyp
         }
     }
 
+    if p_config.lesson_preview {
+        show_lesson_quiz_preview(&term, &batch, subjects, p_config)?;
+    }
+
     let now = Utc::now();
     let mut reviews = HashMap::with_capacity(batch.len());
     for a in &batch {
@@ -962,10 +1990,27 @@ async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewTyp
             incorrect_reading_answers: 0,
             status: wanidata::ReviewStatus::NotStarted,
             available_at: None, // Lesson reviews should not be available
+            is_lesson: true,
+            srs_stage: None,
         });
     }
 
-    do_reviews_inner(subjects, web_config, p_config, image_cache, &mut reviews, &mut batch, subj_counts, audio_tx, conn).await?;
+    loop {
+        match do_reviews_inner(subjects, web_config, p_config, image_cache, &mut reviews, &mut batch, subj_counts, audio_tx, conn).await {
+            Err(WaniError::Io(e)) if e.kind() == io::ErrorKind::Interrupted => {
+                if confirm_lesson_quit(&term, p_config)? {
+                    // Only lessons that passed the quiz are started on WaniKani
+                    reviews.retain(|_, r| matches!(r.status, ReviewStatus::Done));
+                    save_lessons(reviews, rate_limit.clone(), web_config.clone(), conn.clone()).await?;
+                    return Err(WaniError::Io(e));
+                }
+            },
+            res => {
+                res?;
+                break;
+            },
+        }
+    }
 
     let rate_limit = rate_limit.clone();
     let web_config = web_config.clone();
@@ -978,17 +2023,92 @@ async fn do_lesson_batch(mut batch: Vec<Assignment>, subj_counts: &mut ReviewTyp
 async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &WaniWebConfig, p_config: &ProgramConfig, image_cache: &PathBuf, reviews: &mut HashMap<i32, NewReview>, batch: &mut Vec<Assignment>, rev_type: &mut ReviewType, audio_tx: &Sender<AudioMessage>, connection: &AsyncConnection) -> Result<(), WaniError> {
     let term = Term::buffered_stdout();
     let rng = &mut thread_rng();
-    let align = console::Alignment::Center;
+    let align = p_config.review_align;
     let correct_msg = if p_config.colorblind { Some("Correct") } else { None };
     let incorrect_msg = if p_config.colorblind { Some("Incorrect") } else { None };
-    let wfmt_args = get_wfmt_args(&term);
+    let mut key_reader = KeyReader::default();
+    let wfmt_args = get_wfmt_args(&term, p_config);
     let mut input = String::new();
+    let mut last_assignment_id = None;
+    // half (is_meaning) last asked for each assignment that hasn't had a half done yet
+    let mut last_halves = HashMap::new();
+    // assignment whose first half was just answered correctly, when pairing halves
+    let mut paired_assignment_id = None;
+    // with persistent_toasts, the last answer feedback stays on screen until it's dismissed with Esc
+    let mut persisted_toast: Option<String> = None;
+    if let ShuffleMode::Batch = p_config.shuffle {
+        batch.shuffle(rng);
+    }
+    let batch_audios = batch.iter()
+        .filter_map(|a| match subjects.get(&a.data.subject_id) {
+            Some(Subject::Vocab(v)) => Some((v.id, &v.data.pronunciation_audios)),
+            Some(Subject::KanaVocab(v)) => Some((v.id, &v.data.pronunciation_audios)),
+            _ => None,
+        })
+        .map(|(id, audios)| (id, audio_infos(audios)))
+        .collect_vec();
+    if !batch_audios.is_empty() {
+        let _ = audio_tx.send(AudioMessage::Preload(batch_audios)).await;
+    }
     'subject: loop {
+        if p_config.auto_submit_seconds.is_some() && matches!(rev_type, ReviewType::Review(ReviewStats { practice: false, .. })) {
+            // Hand finished reviews to the background submitter
+            let done = reviews.iter()
+                .filter(|(_, r)| matches!(r.status, ReviewStatus::Done))
+                .map(|(id, _)| *id)
+                .collect_vec();
+            store_reviews_locally(done.iter().filter_map(|id| reviews.remove(id)).collect_vec(), connection).await?;
+        }
         if batch.is_empty() {
             break 'subject;
         }
-        batch.shuffle(rng);
+        if let (ReviewType::Review(ReviewStats { session_start: Some(start), practice, .. }), Some(minutes)) = (&*rev_type, p_config.max_session_minutes) {
+            if start.elapsed().as_secs() >= minutes * 60 {
+                term.clear_screen()?;
+                if *practice {
+                    term.write_line(&format!("You've reached your {} minute session limit. Ending the practice session.", minutes))?;
+                }
+                else {
+                    term.write_line(&format!("You've reached your {} minute session limit. Your progress will be saved, and you can resume next time.", minutes))?;
+                }
+                term.flush()?;
+                // Ending the session is handled just like quitting with ctrl+c
+                return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+            }
+        }
+        if let (ReviewType::Review(stats), Some(minutes)) = (&mut *rev_type, p_config.break_reminder_minutes) {
+            if let Some(start) = stats.break_timer_start {
+                if start.elapsed().as_secs() >= minutes * 60 {
+                    let msg = format!("You've been reviewing for {} minutes. Take a break?", minutes);
+                    let save_msg = if stats.practice {
+                        "This ends the practice session. Practice answers aren't saved."
+                    }
+                    else {
+                        "Your progress will be saved, and you can resume next time."
+                    };
+                    if confirm_prompt(&term, &[&msg, save_msg])? {
+                        // Pausing is handled just like quitting with ctrl+c
+                        return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+                    }
+                    stats.break_timer_start = Some(std::time::Instant::now());
+                }
+            }
+        }
+        // The item is still last in the batch, so not shuffling asks its other half next
+        let ask_other_half = paired_assignment_id.take().is_some_and(|id| batch.last().map(|a| a.id) == Some(id));
+        match p_config.shuffle {
+            _ if ask_other_half => {},
+            ShuffleMode::Item => batch.shuffle(rng),
+            ShuffleMode::Batch => {
+                // An item that still needs answers goes to the back of the line
+                if batch.last().map(|a| a.id) == last_assignment_id {
+                    batch.rotate_right(1);
+                }
+            },
+        }
         let assignment = batch.last().unwrap();
+        last_assignment_id = Some(assignment.id);
+        let subject_id = assignment.data.subject_id;
         let review = reviews.get_mut(&assignment.id).unwrap();
         let subject = subjects.get(&assignment.data.subject_id);
         if let None = subject {
@@ -996,6 +2116,7 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
             break 'subject;
         }
         let subject = subject.unwrap();
+        let user_synonyms = get_study_material(connection, subject_id).await.map(|s| s.data.meaning_synonyms).unwrap_or_default();
         let characters = get_chars_for_subj(subject, image_cache, 100, web_config).await;
         if let Err(_) = characters {
             batch.pop();
@@ -1004,58 +2125,124 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
 
         let is_meaning = match subject {
             Subject::Radical(_) => true,
-            Subject::Kanji(_) => {
-                match review.status {
-                    wanidata::ReviewStatus::NotStarted => rng.gen_bool(0.5),
-                    wanidata::ReviewStatus::MeaningDone => false,
-                    wanidata::ReviewStatus::ReadingDone => true,
-                    wanidata::ReviewStatus::Done => panic!(),
-                }
-            },
-            Subject::Vocab(_) => {
-                match review.status {
-                    wanidata::ReviewStatus::NotStarted => rng.gen_bool(0.5),
-                    wanidata::ReviewStatus::MeaningDone => false,
-                    wanidata::ReviewStatus::ReadingDone => true,
-                    wanidata::ReviewStatus::Done => panic!(),
-                }
+            Subject::Kanji(_) | Subject::Vocab(_) => {
+                let last_half = if p_config.remember_half { last_halves.get(&assignment.id).copied() } else { None };
+                let is_meaning = wanidata::choose_is_meaning(review.status, last_half, || rng.gen_bool(0.5));
+                last_halves.insert(assignment.id, is_meaning);
+                is_meaning
             },
             Subject::KanaVocab(_) => true,
         };
         let review_type_text = match subject {
             Subject::Radical(_) => "Radical Name",
-            Subject::Kanji(_) => if is_meaning { "Kanji Meaning" } else { "Kanji Reading" },
+            Subject::Kanji(_) => match (is_meaning, rev_type.deref()) {
+                (true, _) => "Kanji Meaning",
+                (false, ReviewType::Review(ReviewStats { reading_type: Some(wanidata::KanjiType::Onyomi), .. })) => "Kanji Onyomi",
+                (false, ReviewType::Review(ReviewStats { reading_type: Some(wanidata::KanjiType::Kunyomi), .. })) => "Kanji Kunyomi",
+                (false, _) => "Kanji Reading",
+            },
             Subject::Vocab(_) => if is_meaning { "Vocab Meaning" } else { "Vocab Reading" },
             Subject::KanaVocab(_) => "Vocab Meaning",
         };
 
         let mut toast = None;
+        let primary_reading = match (subject, is_meaning) {
+            (Subject::Kanji(k), false) => k.primary_readings().next(),
+            (Subject::Vocab(v), false) => v.primary_readings().next(),
+            _ => None,
+        };
+        let alt_reading_msg = primary_reading
+            .map(|r| format!("Correct \u{2014} but the primary reading is {}.", r))
+            .unwrap_or_default();
+
+        // The time limit is for the whole item, so retrying a badly formatted answer doesn't restart it
+        let mut deadline = match (rev_type.deref(), p_config.answer_timeout_secs) {
+            (ReviewType::Review(_), Some(secs)) => Some(std::time::Instant::now() + std::time::Duration::from_secs(secs)),
+            _ => None,
+        };
+        let mut timed_out = false;
 
         'input: loop {
             input.clear();
-            let (width, _, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast, image_cache, web_config, "", None).await?;
-            term.move_cursor_to(width / 2, 2 + char_lines.len())?;
+            wait_for_terminal_size(&term, p_config)?;
+            let (width, _, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast.or(persisted_toast.as_deref()), image_cache, web_config, "", None, p_config.large_characters).await?;
+            term.move_cursor_to(input_cursor_column(align, width, 0), 2 + char_lines.len())?;
             term.flush()?;
+            let shown_at = std::time::Instant::now();
 
             let mut vis_input = &input;
             let mut kana_input = String::new();
 
             'line_of_input: loop {
-                let char = term.read_key()?;
+                let char = match deadline {
+                    Some(deadline) => match key_reader.read_key_until(deadline)? {
+                        Some(key) => key,
+                        None => {
+                            timed_out = true;
+                            break 'line_of_input;
+                        },
+                    },
+                    None => key_reader.read_key(&term)?,
+                };
                 match char {
                     console::Key::Enter => {
-                        break 'line_of_input;
+                        if is_meaning {
+                            break 'line_of_input;
+                        }
+                        // Don't score a reading while romaji is still waiting to be converted
+                        match wanidata::settle_kana_input(&input) {
+                            wanidata::KanaSettle::Settled(_) => break 'line_of_input,
+                            wanidata::KanaSettle::Unsettled => toast = Some("Finish typing the reading first."),
+                        }
                     },
                     console::Key::Backspace => {
                         input.pop();
                     },
+                    console::Key::Escape => {
+                        persisted_toast = None;
+                    },
+                    console::Key::Tab if p_config.focus_mode => toggle_stats(rev_type),
                     console::Key::Char(c) => {
                         if input.len() > 0 {
                             input.push(c);
                         }
                         else {
                             match c {
-                                '?' => show_review_help(&term, align),
+                                '?' => {
+                                    // Time spent reading the help doesn't count against the time limit
+                                    let help_at = std::time::Instant::now();
+                                    show_review_help(&term, align);
+                                    deadline = deadline.map(|d| d + help_at.elapsed());
+                                },
+                                '!' if matches!(rev_type, ReviewType::Review(ReviewStats { relearn: true, .. })) => {
+                                    if let ReviewType::Review(stats) = rev_type {
+                                        if !stats.assisted_subject_ids.contains(&subject_id) {
+                                            stats.assisted_subject_ids.push(subject_id);
+                                        }
+                                    }
+                                    // Study the info pages until a key other than 'n' or 'N' is pressed.
+                                    // Like the help, studying doesn't count against the time limit
+                                    let study_at = std::time::Instant::now();
+                                    let study_toast = Some("Studying: 'n'/'N' for more, any other key to answer");
+                                    let mut page = 0usize;
+                                    loop {
+                                        let (width, text_width, _) = print_review_screen(&term, rev_type, align, subject, review_type_text, &study_toast, image_cache, web_config, "", None, p_config.large_characters).await?;
+                                        let mut lines = get_info_lines(subject, page, &wfmt_args, is_meaning, connection, text_width, width).await;
+                                        if p_config.collapse_blank_lines {
+                                            lines = wanidata::collapse_blank_lines(lines);
+                                        }
+                                        for line in &lines {
+                                            term.write_line(&pad_str(line, width, align, None))?;
+                                        }
+                                        term.flush()?;
+                                        match term.read_key()? {
+                                            console::Key::Char('n') => page = page.wrapping_add(1),
+                                            console::Key::Char('N') => page = page.wrapping_sub(1),
+                                            _ => break,
+                                        }
+                                    }
+                                    deadline = deadline.map(|d| d + study_at.elapsed());
+                                },
                                 _ => input.push(c),
                             }
                         }
@@ -1068,28 +2255,87 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                     ..Default::default()
                 });
                 vis_input = if is_meaning { &input } else { &kana_input };
-                let (width, _, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast, image_cache, web_config, &vis_input, None).await?;
-                let input_width = console::measure_text_width(&vis_input);
-                term.move_cursor_to((width + input_width) / 2, 2 + char_lines.len())?;
+                // Romaji that isn't kana yet (like the "ky" of "kyo") is dimmed after the converted kana
+                let (committed, pending) = wanidata::split_pending_romaji(vis_input);
+                let typed_input = if is_meaning || pending.is_empty() { vis_input.to_owned() } else { format!("{}{}", committed, style(pending).dim()) };
+                // with blind_input, the answer is only shown once it's submitted
+                let shown_input = if p_config.blind_input { "" } else { typed_input.as_str() };
+                let (width, _, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast.or(persisted_toast.as_deref()), image_cache, web_config, shown_input, None, p_config.large_characters).await?;
+                let input_width = console::measure_text_width(shown_input);
+                term.move_cursor_to(input_cursor_column(align, width, input_width), 2 + char_lines.len())?;
                 term.flush()?;
             }
 
-            if input.is_empty() {
+            let reveal = timed_out || (input.is_empty() && matches!(p_config.empty_enter, EmptyEnter::Reveal));
+            if input.is_empty() && !reveal {
+                if let EmptyEnter::Beep = p_config.empty_enter {
+                    term.write_str("\x07")?;
+                    term.flush()?;
+                    toast = Some("Type an answer");
+                }
                 continue 'input;
             }
 
-            let guess = vis_input.trim().to_lowercase();
-            let answer_result = wanidata::is_correct_answer(subject, &guess, is_meaning, &kana_input);
+            if !is_meaning {
+                if let wanidata::KanaSettle::Settled(k) = wanidata::settle_kana_input(&input) {
+                    kana_input = k;
+                }
+                vis_input = &kana_input;
+            }
+
+            let revealed;
+            let answer_result = if reveal {
+                // Show the answer in place of the guess, and count it as a miss
+                revealed = match (subject, is_meaning) {
+                    (Subject::Radical(r), _) => r.primary_meanings().next(),
+                    (Subject::KanaVocab(kv), _) => kv.primary_meanings().next(),
+                    (Subject::Kanji(k), true) => k.primary_meanings().next(),
+                    (Subject::Kanji(k), false) => k.primary_readings().next(),
+                    (Subject::Vocab(v), true) => v.primary_meanings().next(),
+                    (Subject::Vocab(v), false) => v.primary_readings().next(),
+                }.cloned().unwrap_or_default();
+                vis_input = &revealed;
+                wanidata::AnswerResult::Incorrect
+            } else {
+                let guess = vis_input.trim().to_lowercase();
+                match (subject, is_meaning, rev_type.deref()) {
+                    (Subject::Kanji(k), false, ReviewType::Review(ReviewStats { reading_type: Some(t), .. })) => {
+                        wanidata::is_correct_kanji_reading_of_type(k, &guess, *t, p_config.primary_only)
+                    },
+                    _ => wanidata::is_correct_answer(subject, &guess, is_meaning, &kana_input, &user_synonyms, answer_options(p_config)),
+                }
+            };
+            let hint = match (subject, rev_type.deref()) {
+                (Subject::Vocab(v), ReviewType::Review(ReviewStats { hints: true, .. })) => {
+                    Some(vocab_kanji_composition(v, connection, "Hint:").await)
+                },
+                _ => None,
+            };
+            let is_incorrect = matches!(answer_result, wanidata::AnswerResult::Incorrect);
+            let mut show_hint = hint.is_some() && is_incorrect;
+            let is_alt_reading = p_config.alt_readings != AltReadings::Accept
+                && !is_meaning
+                && matches!(answer_result, wanidata::AnswerResult::Correct)
+                && wanidata::is_alt_reading(subject, vis_input.trim());
+            let retry_alt_reading = is_alt_reading
+                && p_config.alt_readings == AltReadings::PracticeStrict
+                && matches!(rev_type, ReviewType::Review(ReviewStats { practice: true, .. }));
 
             // Tuple (retry, toast, answer_color)
             let tuple = match answer_result {
                 wanidata::AnswerResult::BadFormatting => (true, Some("Try again!"), AnswerColor::Gray),
                 wanidata::AnswerResult::KanaWhenMeaning => (true, Some("We want the reading, not the meaning."), AnswerColor::Gray),
+                wanidata::AnswerResult::Correct if retry_alt_reading => (true, Some("That's an alternate reading. What's the primary reading?"), AnswerColor::Gray),
 
                 wanidata::AnswerResult::FuzzyCorrect | wanidata::AnswerResult::Correct => {
                     let mut toast = correct_msg;
+                    let mut answer_color = AnswerColor::Green;
                     if let wanidata::AnswerResult::FuzzyCorrect = answer_result {
                         toast = Some("Answer was a bit off. . .");
+                        answer_color = p_config.fuzzy_correct_color;
+                    }
+                    if is_alt_reading && !alt_reading_msg.is_empty() {
+                        toast = Some(&alt_reading_msg);
                     }
                     review.created_at = Utc::now();
                     review.status = match subject {
@@ -1113,6 +2359,9 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                         Subject::Kanji(_) | Subject::Vocab(_) => {
                             match review.status {
                                 wanidata::ReviewStatus::NotStarted => {
+                                    if p_config.pair_kanji_halves {
+                                        paired_assignment_id = last_assignment_id;
+                                    }
                                     if is_meaning { 
                                         ReviewStatus::MeaningDone
                                     }
@@ -1139,11 +2388,29 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                             }
                         },
                     };
-                    (false, toast, AnswerColor::Green)
+                    if let (ReviewStatus::Done, ReviewType::Review(stats)) = (review.status, &mut *rev_type) {
+                        let correct = review.incorrect_meaning_answers == 0 && review.incorrect_reading_answers == 0;
+                        if correct {
+                            stats.done_first_try += 1;
+                        }
+                        if !stats.practice {
+                            if let Some(today) = &mut stats.today {
+                                today.record(correct);
+                            }
+                            log_review_history(connection, subject_id, correct, review.incorrect_meaning_answers, review.incorrect_reading_answers).await;
+                        }
+                    }
+                    (false, toast, answer_color)
                 },
                 wanidata::AnswerResult::Incorrect => {
                     if let ReviewType::Review(stats) = rev_type {
                         stats.failed += 1;
+                        if !stats.wrong_subject_ids.contains(&subject_id) {
+                            stats.wrong_subject_ids.push(subject_id);
+                        }
+                        if !reveal && !wanidata::is_custom_subject_id(subject_id) {
+                            log_wrong_answer(connection, subject_id, is_meaning, vis_input.trim().to_lowercase()).await;
+                        }
                     }
                     if is_meaning {
                         review.incorrect_meaning_answers += 1;
@@ -1155,17 +2422,39 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                 },
                 wanidata::AnswerResult::MatchesNonAcceptedAnswer => (true, Some("Answer not accepted. Try again"), AnswerColor::Gray),
             };
-            toast = tuple.1;
+            toast = if timed_out { Some("Out of time!") } else { tuple.1 };
+            if let (true, Some(t)) = (p_config.persistent_toasts, toast) {
+                // Correct/Incorrect only describe the current answer's color
+                if toast != correct_msg && toast != incorrect_msg && !timed_out {
+                    persisted_toast = Some(format!("{} (Esc to dismiss)", t));
+                }
+            }
 
             if !tuple.0 {
                 if let ReviewType::Review(stats) = rev_type {
                     stats.guesses += 1;
                 }
+                match p_config.notify_on {
+                    NotifyOn::Always => notify_answer(&term, !is_incorrect),
+                    NotifyOn::Incorrect if is_incorrect => notify_answer(&term, false),
+                    _ => {},
+                }
+
+                if let Some(min_ms) = p_config.min_answer_ms {
+                    if toast.is_none() && shown_at.elapsed().as_millis() < min_ms.into() {
+                        toast = Some("That was fast \u{2014} make sure you're reading it.");
+                    }
+                }
             }
 
-            let (width, _, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast, image_cache, web_config, &vis_input, Some(&tuple.2)).await?;
+            let (width, _, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast.or(persisted_toast.as_deref()), image_cache, web_config, &vis_input, Some(&tuple.2), p_config.large_characters).await?;
+            if let (true, Some(hint)) = (show_hint, &hint) {
+                for line in hint {
+                    term.write_line(&pad_str(line, width, align, None))?;
+                }
+            }
             let input_width = console::measure_text_width(&vis_input);
-            term.move_cursor_to((width + input_width) / 2, 2 + char_lines.len())?;
+            term.move_cursor_to(input_cursor_column(align, width, input_width), 2 + char_lines.len())?;
             term.flush()?;
 
             enum InfoStatus {
@@ -1174,8 +2463,9 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
             }
             let mut info_status = InfoStatus::Hidden;
             'after_input: loop {
-                match term.read_key()? {
+                match key_reader.read_key(&term)? {
                     console::Key::Enter | console::Key::Backspace=> { break 'after_input; },
+                    console::Key::Tab if p_config.focus_mode => toggle_stats(rev_type),
                     console::Key::Char(c) => {
                         match c {
                             '?' => if !tuple.0 {
@@ -1208,6 +2498,18 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                                     };
                                 }
                             },
+                            'h' | 'H' => {
+                                show_hint = hint.is_some() && !show_hint;
+                            },
+                            'e' | 'E' if !tuple.0 => {
+                                toast = Some(edit_user_note(&term, subject_id, is_meaning, connection, web_config).await?);
+                            },
+                            'j' | 'J' if p_config.no_audio => {
+                                toast = Some("Audio disabled");
+                            },
+                            'j' | 'J' if no_audio_device() => {
+                                toast = Some("No audio device available");
+                            },
                             'j' | 'J' => {
                                 let mut can_play_audio = !is_meaning && review.incorrect_reading_answers > 0;
                                 can_play_audio = !tuple.0 && can_play_audio || match review.status {
@@ -1227,10 +2529,7 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                                         let _ = audio_tx.send(AudioMessage::PlayAudioMessage(PlayAudioMessage {
                                             send_time: std::time::Instant::now(),
                                             id,
-                                            audios: audios.iter().map(|a| AudioInfo {
-                                                url: a.url.clone(),
-                                                content_type: a.content_type.clone(),
-                                            }).collect_vec(),
+                                            audios: audio_infos(&audios),
                                         })).await;
                                     }
                                 }
@@ -1241,17 +2540,25 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
                     _ => {},
                 }
 
-                let (width, text_width, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast, image_cache, web_config, &vis_input, Some(&tuple.2)).await?;
+                let (width, text_width, char_lines) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast.or(persisted_toast.as_deref()), image_cache, web_config, &vis_input, Some(&tuple.2), p_config.large_characters).await?;
                 if let InfoStatus::Open(info_status) = info_status {
-                    let lines = get_info_lines(&subject, info_status, &wfmt_args, is_meaning, connection, text_width, width).await;
+                    let mut lines = get_info_lines(&subject, info_status, &wfmt_args, is_meaning, connection, text_width, width).await;
+                    if p_config.collapse_blank_lines {
+                        lines = wanidata::collapse_blank_lines(lines);
+                    }
                     for line in &lines {
                         term.write_line(&pad_str(line, width, align, None))?;
                     }
 
                 }
+                else if let (true, Some(hint)) = (show_hint, &hint) {
+                    for line in hint {
+                        term.write_line(&pad_str(line, width, align, None))?;
+                    }
+                }
 
                 let input_width = console::measure_text_width(&vis_input);
-                term.move_cursor_to((width + input_width) / 2, 2 + char_lines.len())?;
+                term.move_cursor_to(input_cursor_column(align, width, input_width), 2 + char_lines.len())?;
                 term.flush()?;
             }
 
@@ -1260,8 +2567,8 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
             }
 
             toast = None;
-            let (width, _, char_line) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast, image_cache, web_config, &"", None).await?;
-            term.move_cursor_to(width / 2, 2 + char_line.len())?;
+            let (width, _, char_line) = print_review_screen(&term, rev_type, align, subject, review_type_text, &toast.or(persisted_toast.as_deref()), image_cache, web_config, &"", None, p_config.large_characters).await?;
+            term.move_cursor_to(input_cursor_column(align, width, 0), 2 + char_line.len())?;
             term.flush()?;
         }
     }
@@ -1269,7 +2576,61 @@ async fn do_reviews_inner<'a>(subjects: &HashMap<i32, Subject>, web_config: &Wan
     Ok(())
 }
 
-fn get_wfmt_args(term: &Term) -> WaniFmtArgs {
+/// shows or hides the review stats line, for focus_mode
+fn toggle_stats(rev_type: &mut ReviewType) {
+    if let ReviewType::Review(stats) = rev_type {
+        stats.hide_stats = !stats.hide_stats;
+    }
+}
+
+/// the terminal's size, when it's smaller than min_terminal_size
+fn terminal_too_small(term: &Term, p_config: &ProgramConfig) -> Option<(u16, u16)> {
+    let (min_cols, min_rows) = p_config.min_terminal_size?;
+    let (rows, cols) = term.size_checked()?;
+    if cols < min_cols || rows < min_rows { Some((cols, rows)) } else { None }
+}
+
+/// errors instead of starting a session in a terminal too small to lay it out
+fn check_terminal_size(term: &Term, p_config: &ProgramConfig) -> Result<(), WaniError> {
+    match (terminal_too_small(term, p_config), p_config.min_terminal_size) {
+        (Some((cols, rows)), Some((min_cols, min_rows))) => Err(WaniError::Generic(format!(
+            "The terminal is {}x{}, but wani needs at least {}x{} to show a session. Enlarge the terminal and try again, or change min_terminal_size in the config file.",
+            cols, rows, min_cols, min_rows))),
+        _ => Ok(()),
+    }
+}
+
+/// pauses a session while the terminal has been resized too small, until it's enlarged again
+fn wait_for_terminal_size(term: &Term, p_config: &ProgramConfig) -> Result<(), WaniError> {
+    while let (Some((cols, rows)), Some((min_cols, min_rows))) = (terminal_too_small(term, p_config), p_config.min_terminal_size) {
+        term.clear_screen()?;
+        term.write_line(&format!("Terminal too small ({}x{}).", cols, rows))?;
+        term.write_line(&format!("Enlarge it to at least {}x{}, then press any key.", min_cols, min_rows))?;
+        term.flush()?;
+        term.read_key()?;
+    }
+    Ok(())
+}
+
+/// the answer checking settings from the config file
+fn answer_options(p_config: &ProgramConfig) -> wanidata::AnswerOptions {
+    wanidata::AnswerOptions {
+        primary_only: p_config.primary_only,
+        fold_diacritics: p_config.fold_diacritics,
+        fuzzy: p_config.fuzzy,
+    }
+}
+
+/// column just past the end of the answer input, for the given review screen alignment
+fn input_cursor_column(align: console::Alignment, width: usize, input_width: usize) -> usize {
+    match align {
+        console::Alignment::Left => input_width,
+        console::Alignment::Center => (width + input_width) / 2,
+        console::Alignment::Right => width,
+    }
+}
+
+fn get_wfmt_args(term: &Term, p_config: &ProgramConfig) -> WaniFmtArgs {
     let blue_tag = format!("\x1b[{}m", 4 + 40);
     let red_tag = format!("\x1b[{}m", 1 + 40);
     let magenta_tag = format!("\x1b[{}m", 5 + 40);
@@ -1302,192 +2663,259 @@ fn get_wfmt_args(term: &Term) -> WaniFmtArgs {
                 open_tag: green_tag,
                 close_tag: "\x1b[0m".into(),
             },
+            show_romaji: p_config.show_romaji,
         }
     }
     else {
-        WaniFmtArgs::default()
-    }
-}
-
-async fn command_review(args: &Args) {
-    async fn do_reviews(assignments: &mut Vec<Assignment>, subjects: HashMap<i32, Subject>, audio_cache: PathBuf, web_config: &WaniWebConfig, p_config: &ProgramConfig, image_cache: &PathBuf, conn: &AsyncConnection, rate_limit: &RateLimitBox, first_batch: Option<Vec<(Assignment, NewReview)>>) -> Result<(), WaniError> {
-        assignments.reverse();
-        let total_assignments = assignments.len() + if let Some(batch) = &first_batch { batch.len() } else { 0 };
-        let mut first_batch = first_batch;
-        let ideal_batch_size = 20;
-        let mut batch_size;
-        let (audio_tx, mut rx) = mpsc::channel::<AudioMessage>(5);
-        let audio_web_config = web_config.clone();
-        let audio_task = tokio::spawn(async move {
-            let audio_cache = audio_cache;
-            let mut last_finish_time = std::time::Instant::now();
-            while let Some(m) = rx.recv().await {
-                match m {
-                    AudioMessage::PlayAudioMessage(msg) => {
-                        if msg.send_time < last_finish_time {
+        WaniFmtArgs { show_romaji: p_config.show_romaji, ..Default::default() }
+    }
+}
+
+async fn do_reviews(assignments: &mut Vec<Assignment>, subjects: HashMap<i32, Subject>, audio_cache: PathBuf, web_config: &WaniWebConfig, p_config: &ProgramConfig, image_cache: &PathBuf, conn: &AsyncConnection, rate_limit: &RateLimitBox, first_batch: Option<Vec<(Assignment, NewReview)>>, review_args: &ReviewArgs) -> Result<Vec<NewReview>, WaniError> {
+    check_terminal_size(&Term::stdout(), p_config)?;
+    let alt_screen = AltScreen::enter(p_config.alternate_screen);
+    assignments.reverse();
+    let practice = review_args.practice;
+    let total_assignments = assignments.len() + if let Some(batch) = &first_batch { batch.len() } else { 0 };
+    let mut first_batch = first_batch;
+    let review_batch_size = max(1, review_args.batch_size.unwrap_or(p_config.review_batch_size));
+    let mut ideal_batch_size = p_config.adaptive_batch.map_or(review_batch_size, |a| review_batch_size.clamp(a.min_size, a.max_size));
+    let mut batch_size;
+    let (audio_tx, audio_task) = spawn_audio_task(audio_cache, web_config, p_config);
+    let review_submitter = match (p_config.auto_submit_seconds, practice) {
+        (Some(secs), false) => Some(spawn_review_submitter(std::time::Duration::from_secs(secs), conn, web_config, rate_limit)),
+        _ => None,
+    };
+
+    let mut review_result = None;
+    let mut first_reviews = None;
+    let stats = ReviewStats {
+        total_reviews: total_assignments,
+        practice,
+        hints: practice && review_args.hints,
+        reading_type: review_args.reading_type.filter(|_| practice).map(wanidata::KanjiType::from),
+        relearn: practice && review_args.relearn,
+        break_timer_start: Some(std::time::Instant::now()),
+        session_start: Some(std::time::Instant::now()),
+        today: if p_config.show_today_progress && !practice { load_today_progress(conn).await } else { None },
+        accuracy: p_config.accuracy,
+        hide_stats: p_config.focus_mode,
+        accuracy_precision: p_config.accuracy_precision,
+        ..Default::default()
+    };
+    let mut stats = ReviewType::Review(stats);
+    let mut save_review_tasks = JoinSet::new();
+    let mut practiced = vec![];
+    loop {
+        if let None = first_batch {
+            if assignments.len() == 0 {
+                break;
+            }
+        }
+
+        let mut batch = match first_batch { 
+            None => { 
+                if let (Some(adaptive), ReviewType::Review(s)) = (&p_config.adaptive_batch, &stats) {
+                    ideal_batch_size = adaptive.next_size(ideal_batch_size, s.guesses, s.failed);
+                }
+                batch_size = min(ideal_batch_size, assignments.len());
+                let mut b = Vec::with_capacity(batch_size);
+                assignments.shuffle(&mut thread_rng());
+
+                if let ReviewOrder::OverdueFirst = p_config.review_order {
+                    // batches are taken from the back of the list, so put the oldest last
+                    assignments.sort_by_key(|a| std::cmp::Reverse(a.data.available_at));
+                }
+                // move current level assignments to back of list so we work on them first
+                else if p_config.user.level > 0 {
+                    let mut write = assignments.len()-1;
+                    for i in (0..assignments.len()).rev() {
+                        let assignment = &assignments[i];
+                        let subject = subjects.get(&assignment.data.subject_id);
+                        if let None = subject {
                             continue;
                         }
-                        let _ = play_audio_for_subj(msg.id, msg.audios, &audio_cache, &audio_web_config).await;
-                        last_finish_time = std::time::Instant::now();
-                    },
-                    AudioMessage::Quit => {
-                        break;
+                        let level = match subject.unwrap() {
+                            Subject::Radical(r) => {
+                                r.data.level
+                            },
+                            Subject::Kanji(k) => {
+                                k.data.level
+                            },
+                            Subject::Vocab(v) => {
+                                v.data.level
+                            },
+                            Subject::KanaVocab(kv) => {
+                                kv.data.level
+                            },
+                        };
+
+                        if level != p_config.user.level {
+                            continue;
+                        }
+
+                        let swap = assignments[i];
+                        assignments[i] = assignments[write];
+                        assignments[write] = swap;
+                        write -= 1;
                     }
                 }
-            }
-        });
 
-        let mut review_result = None;
-        let mut first_reviews = None;
-        let stats = ReviewStats {
-            total_reviews: total_assignments,
-            ..Default::default()
-        };
-        let mut stats = ReviewType::Review(stats);
-        let mut save_review_tasks = JoinSet::new();
-        loop {
-            if let None = first_batch {
-                if assignments.len() == 0 {
-                    break;
+                for i in (assignments.len() - batch_size..assignments.len()).rev() {
+                    b.push(assignments.remove(i));
+                }
+                b
+            },
+            Some(b) => {
+                println!("Resuming saved batch of reviews");
+                let mut batch = Vec::with_capacity(b.len());
+                let mut revs = HashMap::with_capacity(b.len());
+                for (assignment, review) in b {
+                    batch.push(assignment);
+                    revs.insert(review.assignment_id, review);
                 }
+                first_batch = None;
+                first_reviews = Some(revs);
+                batch
             }
+        };
 
-            let mut batch = match first_batch { 
-                None => { 
-                    batch_size = min(ideal_batch_size, assignments.len());
-                    let mut b = Vec::with_capacity(batch_size);
-                    assignments.shuffle(&mut thread_rng());
+        let mut reviews = if let Some(r) = first_reviews { 
+            first_reviews = None;
+            r
+        } else {
+            let mut reviews = HashMap::with_capacity(batch.len());
+            let now = Utc::now();
+            for nr in batch.iter().map(|a| wanidata::NewReview {
+                id: None,
+                assignment_id: a.id,
+                created_at: now,
+                incorrect_meaning_answers: 0,
+                incorrect_reading_answers: 0,
+                status: wanidata::ReviewStatus::NotStarted,
+                available_at: a.data.available_at,
+                is_lesson: false,
+                srs_stage: Some(a.data.srs_stage),
+            }) {
+                reviews.insert(nr.assignment_id, nr);
+            }
+            reviews
+        };
 
-                    // move current level assignments to back of list so we work on them first
-                    if p_config.user.level > 0 {
-                        let mut write = assignments.len()-1;
-                        for i in (0..assignments.len()).rev() {
-                            let assignment = &assignments[i];
-                            let subject = subjects.get(&assignment.data.subject_id);
-                            if let None = subject {
-                                continue;
+        let res = do_reviews_inner(&subjects, web_config, p_config, image_cache, &mut reviews, &mut batch, &mut stats, &audio_tx, conn).await;
+        if let Err(e) = &res {
+            match &e {
+                WaniError::Io(err) => {
+                    match err.kind() {
+                        io::ErrorKind::Interrupted => {
+                            drop(alt_screen);
+                            if practice {
+                                practiced.extend(reviews.into_values());
                             }
-                            let level = match subject.unwrap() {
-                                Subject::Radical(r) => {
-                                    r.data.level
-                                },
-                                Subject::Kanji(k) => {
-                                    k.data.level
-                                },
-                                Subject::Vocab(v) => {
-                                    v.data.level
-                                },
-                                Subject::KanaVocab(kv) => {
-                                    kv.data.level
-                                },
-                            };
-
-                            if level != p_config.user.level {
-                                continue;
+                            else {
+                                save_reviews(reviews, conn.clone(), web_config.clone(), rate_limit.clone(), true).await?;
+                            }
+                            while let Some(_) = save_review_tasks.join_next().await {
+                                // Join all
                             }
 
-                            let swap = assignments[i];
-                            assignments[i] = assignments[write];
-                            assignments[write] = swap;
-                            write -= 1;
-                        }
-                    }
-
-                    for i in (assignments.len() - batch_size..assignments.len()).rev() {
-                        b.push(assignments.remove(i));
-                    }
-                    b
-                },
-                Some(b) => {
-                    println!("Resuming saved batch of reviews");
-                    let mut batch = Vec::with_capacity(b.len());
-                    let mut revs = HashMap::with_capacity(b.len());
-                    for (assignment, review) in b {
-                        batch.push(assignment);
-                        revs.insert(review.assignment_id, review);
+                            stop_review_submitter(review_submitter).await?;
+                            stop_audio_task(&audio_tx, audio_task).await?;
+                            export_wrong_items(&stats, &subjects, review_args);
+                            print_assisted_items(&stats, &subjects);
+                            run_review_complete_command(p_config, &stats);
+                            return Ok(practiced);
+                        },
+                        _ => {},
                     }
-                    first_batch = None;
-                    first_reviews = Some(revs);
-                    batch
-                }
-            };
+                },
+                _ => {},
+            }
+        }
 
-            let mut reviews = if let Some(r) = first_reviews { 
-                first_reviews = None;
-                r
-            } else {
-                let mut reviews = HashMap::with_capacity(batch.len());
-                let now = Utc::now();
-                for nr in batch.iter().map(|a| wanidata::NewReview {
-                    id: None,
-                    assignment_id: a.id,
-                    created_at: now,
-                    incorrect_meaning_answers: 0,
-                    incorrect_reading_answers: 0,
-                    status: wanidata::ReviewStatus::NotStarted,
-                    available_at: a.data.available_at,
-                }) {
-                    reviews.insert(nr.assignment_id, nr);
-                }
-                reviews
-            };
+        review_result = Some(res);
+        if practice {
+            // Practice results are never saved so they don't affect SRS progress
+            practiced.extend(reviews.into_values());
+            continue;
+        }
+        let conn = conn.clone();
+        let web_config = web_config.clone();
+        let rate_limit = rate_limit.clone();
+        save_review_tasks.spawn(save_reviews(reviews, conn, web_config, rate_limit, false));
+    }
 
-            let res = do_reviews_inner(&subjects, web_config, p_config, image_cache, &mut reviews, &mut batch, &mut stats, &audio_tx, conn).await;
-            if let Err(e) = &res {
-                match &e {
-                    WaniError::Io(err) => {
-                        match err.kind() {
-                            io::ErrorKind::Interrupted => {
-                                save_reviews(reviews, conn.clone(), web_config.clone(), rate_limit.clone(), true).await?;
-                                while let Some(_) = save_review_tasks.join_next().await {
-                                    // Join all
-                                }
+    drop(alt_screen);
+    while let Some(_) = save_review_tasks.join_next().await {
+        // Join all
+    }
+    stop_review_submitter(review_submitter).await?;
+    stop_audio_task(&audio_tx, audio_task).await?;
+    export_wrong_items(&stats, &subjects, review_args);
+    print_assisted_items(&stats, &subjects);
+    run_review_complete_command(p_config, &stats);
+    review_result.unwrap_or(Ok(()))?;
+    Ok(practiced)
+}
 
-                                match audio_tx.send(AudioMessage::Quit).await {
-                                    Ok(_) => {
-                                        audio_task.await?;
-                                    },
-                                    Err(_) => {
-                                        audio_task.abort();
-                                    },
-                                }
+/// lists the items that were studied before answering in a --relearn session
+fn print_assisted_items(stats: &ReviewType, subjects: &HashMap<i32, Subject>) {
+    let ReviewType::Review(stats) = stats else {
+        return;
+    };
+    if stats.assisted_subject_ids.is_empty() {
+        return;
+    }
 
-                                return Ok(())
-                            },
-                            _ => {},
-                        }
-                    },
-                    _ => {},
-                }
-            }
+    let characters = stats.assisted_subject_ids.iter()
+        .filter_map(|id| subjects.get(id))
+        .map(|subject| match subject {
+            Subject::Radical(r) => r.data.characters.clone().unwrap_or_else(|| r.data.slug.clone()),
+            Subject::Kanji(k) => k.data.characters.clone(),
+            Subject::Vocab(v) => v.data.characters.clone(),
+            Subject::KanaVocab(kv) => kv.data.characters.clone(),
+        })
+        .join(", ");
+    println!("Assisted (studied before answering): {}", characters);
+}
 
-            review_result = Some(res);
-            let conn = conn.clone();
-            let web_config = web_config.clone();
-            let rate_limit = rate_limit.clone();
-            save_review_tasks.spawn(save_reviews(reviews, conn, web_config, rate_limit, false));
-        }
+/// writes the session's wrong items to the --export-wrong file, if one was given
+fn export_wrong_items(stats: &ReviewType, subjects: &HashMap<i32, Subject>, review_args: &ReviewArgs) {
+    let (path, stats) = match (&review_args.export_wrong, stats) {
+        (Some(path), ReviewType::Review(stats)) => (path, stats),
+        _ => return,
+    };
+    if stats.wrong_subject_ids.is_empty() {
+        return;
+    }
 
-        while let Some(_) = save_review_tasks.join_next().await {
-            // Join all
-        }
-        match audio_tx.send(AudioMessage::Quit).await {
-            Ok(_) => {
-                audio_task.await?;
-            },
-            Err(_) => {
-                audio_task.abort();
-            },
-        }
-        review_result.unwrap_or(Ok(()))
+    let mut tsv = String::new();
+    for subject in stats.wrong_subject_ids.iter().filter_map(|id| subjects.get(id)) {
+        tsv.push_str(&wanidata::anki_tsv_row(subject));
+        tsv.push('\n');
     }
+    match fs::write(path, tsv) {
+        Ok(_) => println!("Wrote {} wrong items to {}", stats.wrong_subject_ids.len(), path.display()),
+        Err(e) => eprintln!("Error writing wrong items to {}: {}", path.display(), e),
+    }
+}
 
+async fn command_review(args: &Args, review_args: &ReviewArgs) {
     let p_config = get_program_config(args);
     if let Err(e) = &p_config {
         eprintln!("{}", e);
         return;
     }
     let mut p_config = p_config.unwrap();
+    let practice_args;
+    let review_args = if p_config.read_only && !review_args.practice {
+        println!("Read-only mode: this is a practice session. Results won't be saved or submitted to WaniKani.");
+        practice_args = ReviewArgs { practice: true, ..review_args.clone() };
+        &practice_args
+    } else { review_args };
+    if review_args.batch_size.unwrap_or(p_config.review_batch_size) == 0 {
+        println!("The review batch size must be at least 1. Using batches of 1 review.");
+    }
 
     let rate_limit = Arc::new(Mutex::new(None));
     let web_config = get_web_config(&p_config);
@@ -1495,7 +2923,7 @@ async fn command_review(args: &Args) {
         eprintln!("{}", e);
         return;
     }
-    let web_config = web_config.unwrap();
+    let mut web_config = web_config.unwrap();
 
     let conn = setup_async_connection(&p_config).await;
     match conn {
@@ -1503,6 +2931,7 @@ async fn command_review(args: &Args) {
             eprintln!("{}", e);
         }
         Ok(c) => {
+            remember_last_command(args, &p_config, &c).await;
             let mut ass_cache_info = CacheInfo { id: wanisql::CACHE_TYPE_SUBJECTS, ..Default::default() };
             let mut c_infos = wanisql::get_all_cache_infos(&c, false).await;
             if let Ok(c_infos) = &mut c_infos {
@@ -1511,21 +2940,40 @@ async fn command_review(args: &Args) {
                 }
             }
 
-            println!("Syncing assignments. . .");
-            cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+            check_online(&mut web_config, &c, &rate_limit).await;
+            if !web_config.offline {
+                println!("Syncing assignments. . .");
+                cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+                let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, get_max_sync_level(&p_config.user, None)).await;
+            }
             let is_user_restricted = p_config.user.is_restricted();
-            let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, is_user_restricted).await;
 
-            let assignments = select_data(wanisql::SELECT_AVAILABLE_ASSIGNMENTS, &c, wanisql::parse_assignment, [Utc::now().timestamp()]).await;
+            let now = Utc::now();
+            let assignments = if review_args.today {
+                let start_of_day = get_start_of_local_day(now);
+                select_data(wanisql::SELECT_AVAILABLE_ASSIGNMENTS_BETWEEN, &c, wanisql::parse_assignment, [start_of_day.timestamp(), now.timestamp()]).await
+            } else {
+                select_data(wanisql::SELECT_AVAILABLE_ASSIGNMENTS, &c, wanisql::parse_assignment, [now.timestamp()]).await
+            };
 
             if let Err(e) = assignments {
                 eprintln!("{}", e);
                 return;
             };
             let mut assignments = assignments.unwrap();
-            if assignments.len() == 0 {
-                println!("No assignments for now.");
-                return;
+            match select_data(wanisql::SELECT_SUSPENDED, &c, wanisql::parse_suspended, []).await {
+                Ok(suspended) => assignments.retain(|a| !suspended.contains(&a.data.subject_id)),
+                Err(e) => eprintln!("Error loading suspended subjects: {}", e),
+            }
+            if assignments.len() == 0 {
+                if review_args.today {
+                    println!("No reviews from today for now.");
+                }
+                else {
+                    println!("No assignments for now.");
+                }
+                print_next_review_time(&c, now).await;
+                return;
             }
 
             let existing_reviews = load_existing_reviews(&c, &assignments).await;
@@ -1562,8 +3010,21 @@ async fn command_review(args: &Args) {
                 return;
             }
             let subjects_by_id = subjects_by_id.unwrap();
+            let subjects_by_id = resync_empty_subjects(&assignments, subjects_by_id, &c, &web_config, &p_config, &rate_limit).await;
+
+            if let Some(srs) = &review_args.srs {
+                let available = assignments.len();
+                assignments.retain(|a| srs.contains(&a.data.srs_stage));
+                println!("Reviewing {} of {} available items in SRS stages {}-{}.", assignments.len(), available, srs.start(), srs.end());
+                if assignments.is_empty() {
+                    if let Err(e) = save_inital_reviews_task.await {
+                        eprintln!("Error saving initial reviews: {}", e);
+                    }
+                    return;
+                }
+            }
 
-            let first_batch = if existing_reviews.in_progress_reviews.len() == 0 { None } else {
+            let first_batch = if review_args.practice || existing_reviews.in_progress_reviews.len() == 0 { None } else {
                 let mut first_batch = Vec::with_capacity(existing_reviews.in_progress_reviews.len());
                 for rev in existing_reviews.in_progress_reviews {
                     if let Some((index, _)) = assignments.iter().find_position(|a| a.id == rev.assignment_id) {
@@ -1593,8 +3054,14 @@ async fn command_review(args: &Args) {
             }
             let image_cache = image_cache.unwrap();
 
+            let practice = review_args.practice;
             let _ = ctrlc::set_handler(move || {
-                println!("\nreceived Ctrl+C!\nSaving reviews...");
+                if practice {
+                    println!("\nreceived Ctrl+C!\nEnding practice session...");
+                }
+                else {
+                    println!("\nreceived Ctrl+C!\nSaving reviews...");
+                }
             });
 
             let mut missing_subjs = false; 
@@ -1626,7 +3093,20 @@ async fn command_review(args: &Args) {
                         }}).collect_vec();
             }
 
-            let res = do_reviews(&mut assignments, subjects_by_id, audio_cache, &web_config, &p_config, &image_cache, &c, &rate_limit, first_batch).await;
+            let in_progress = first_batch.as_ref().map_or(0, |b| b.len());
+            let total = assignments.len() + in_progress;
+            let limit = match (review_args.limit, p_config.large_session_prompt) {
+                (Some(limit), _) => Some(limit),
+                (None, Some(threshold)) if total > threshold => prompt_session_limit(total),
+                _ => None,
+            };
+            if let Some(limit) = limit {
+                // reviews already in progress always finish, so they count toward the limit
+                assignments.sort_by_key(|a| a.data.available_at);
+                assignments.truncate(limit.saturating_sub(in_progress));
+            }
+
+            let res = do_reviews(&mut assignments, subjects_by_id, audio_cache, &web_config, &p_config, &image_cache, &c, &rate_limit, first_batch, review_args).await;
             match res {
                 Ok(_) => {},
                 Err(e) => {
@@ -1641,8 +3121,60 @@ async fn command_review(args: &Args) {
     };
 }
 
+/// asks how many of a large review queue to do this session. Returns None to review them all
+fn prompt_session_limit(total: usize) -> Option<usize> {
+    let term = Term::stdout();
+    loop {
+        print!("You have {} reviews. Start all, or limit to how many? (Enter for all) ", total);
+        let _ = io::stdout().flush();
+        let input = match term.read_line() {
+            Ok(input) => input,
+            Err(_) => return None,
+        };
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+        match input.parse::<usize>() {
+            Ok(n) if n > 0 => return Some(n),
+            _ => println!("Enter a number of reviews, or press Enter to start all {}.", total),
+        }
+    }
+}
+
+/// if there are assignments but no subjects were found for any of them (e.g. an interrupted
+/// first sync), syncs the subjects again and reloads them
+async fn resync_empty_subjects(assignments: &[Assignment], subjects_by_id: HashMap<i32, Subject>, c: &AsyncConnection, web_config: &WaniWebConfig, p_config: &ProgramConfig, rate_limit: &RateLimitBox) -> HashMap<i32, Subject> {
+    if assignments.is_empty() || !subjects_by_id.is_empty() {
+        return subjects_by_id;
+    }
+
+    if web_config.offline {
+        println!("No subject data found for your assignments, and WaniKani can't be reached to fetch it.");
+        return subjects_by_id;
+    }
+
+    println!("No subject data found for your assignments. Syncing subjects. . .");
+    // start from scratch, since the cache info may claim subjects were already synced
+    let cache_info = CacheInfo { id: wanisql::CACHE_TYPE_SUBJECTS, ..Default::default() };
+    match sync_subjects(c, web_config, cache_info, rate_limit, get_max_sync_level(&p_config.user, None), p_config.verbose).await {
+        Ok(r) => println!("Synced {} subjects.", r.success_count),
+        Err(e) => {
+            eprintln!("Error syncing subjects: {}", e);
+            return subjects_by_id;
+        },
+    }
+
+    match get_subjects_for_assignments(assignments, c).await {
+        Ok(subjects) => subjects,
+        Err(e) => {
+            eprintln!("Error loading subjects: {}", e);
+            subjects_by_id
+        },
+    }
+}
+
 async fn get_subjects_for_assignments(assignments: &[Assignment], c: &AsyncConnection) -> Result<HashMap<i32, Subject>, WaniError> {
-    let mut subjects_by_id = HashMap::new();
     let mut r_ids = vec![];
     let mut k_ids = vec![];
     let mut v_ids = vec![];
@@ -1656,6 +3188,12 @@ async fn get_subjects_for_assignments(assignments: &[Assignment], c: &AsyncConne
         }
     }
 
+    get_subjects_by_ids(r_ids, k_ids, v_ids, kv_ids, c).await
+}
+
+/// loads subjects from the local DB, given the ids of each type of subject to load
+async fn get_subjects_by_ids(r_ids: Vec<i32>, k_ids: Vec<i32>, v_ids: Vec<i32>, kv_ids: Vec<i32>, c: &AsyncConnection) -> Result<HashMap<i32, Subject>, WaniError> {
+    let mut subjects_by_id = HashMap::new();
     let radicals = c.call(move |c| { 
         let stmt = c.prepare(&wanisql::select_radicals_by_id(r_ids.len()));
         match stmt {
@@ -1965,7 +3503,7 @@ async fn get_lesson_info_lines(subject: &Subject, card_page: usize, wfmt_args: &
 
 async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniFmtArgs, is_meaning: bool, conn: &AsyncConnection, text_width: usize, width: usize) -> Vec<String> {
     match subject {
-        // 0 - radical name, mnemonic, user synonyms, user note
+        // 0 - radical name, mnemonic, user synonyms, user meaning note
         // 1 - found in kanji
         Subject::Radical(r) => {
             match info_status % 2 {
@@ -1982,6 +3520,7 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
                     let mnemonic = wanidata::format_wani_text(&r.data.meaning_mnemonic, wfmt_args);
                     lines.push("---".to_owned());
                     split_str_by_len(&mnemonic, text_width, &mut lines);
+                    lines.extend(user_note_lines(conn, r.id, true, text_width).await);
                     lines
                 },
                 1 => {
@@ -1992,9 +3531,9 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
             }
         },
 
-        // 0 - kanji meaning, mnemonic, TODO hint, meaning/reading hint, user note
-        // TODO 1 - user synonym, user note
-        // 1 - kanji reading, mnemonic, TODO hint
+        // 0 - kanji meaning, mnemonic, TODO hint, meaning/reading hint, user meaning note
+        // TODO 1 - user synonym
+        // 1 - kanji reading, mnemonic, TODO hint, user reading note
         // 2 - visually similar kanji
         // 3 - found in vocab
         // 4 - radical combination
@@ -2013,10 +3552,14 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
             };
             match info_status {
                 0 => {
-                    kanji_meaning_lines(k, text_width, wfmt_args)
+                    let mut lines = kanji_meaning_lines(k, text_width, wfmt_args);
+                    lines.extend(user_note_lines(conn, k.id, true, text_width).await);
+                    lines
                 },
                 1 => {
-                    kanji_reading_lines(k, text_width, wfmt_args)
+                    let mut lines = kanji_reading_lines(k, text_width, wfmt_args);
+                    lines.extend(user_note_lines(conn, k.id, false, text_width).await);
+                    lines
                 },
                 2 => {
                     let label = "Visually Similar Kanji:";
@@ -2034,9 +3577,9 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
             }
         },
 
-        // 0 - vocab meaning, mnemonic, TODO hint, part of speech
-        // TODO 1 - user synonym, user note
-        // 1 - vocab reading, mnemonic, TODO hint
+        // 0 - vocab meaning, mnemonic, TODO hint, part of speech, user meaning note
+        // TODO 1 - user synonym
+        // 1 - vocab reading, mnemonic, TODO hint, user reading note
         // TODO 2 - Context Pt 1:
         //      - patterns for use
         //      - common word combinations
@@ -2058,10 +3601,14 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
             };
             match info_status {
                 0 => {
-                    vocab_meaning_lines(v, text_width, wfmt_args)
+                    let mut lines = vocab_meaning_lines(v, text_width, wfmt_args);
+                    lines.extend(user_note_lines(conn, v.id, true, text_width).await);
+                    lines
                 },
                 1 => {
-                    vocab_reading_lines(v, text_width, wfmt_args)
+                    let mut lines = vocab_reading_lines(v, text_width, wfmt_args);
+                    lines.extend(user_note_lines(conn, v.id, false, text_width).await);
+                    lines
                 },
                 2 => {
                     get_context_sentences(&v.data.context_sentences, text_width, width)
@@ -2073,8 +3620,8 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
             }
         },
 
-        // 0 - kana vocab meaning, mnemonic, TODO hint, part of speech
-        // TODO 1 - user synonym, user note
+        // 0 - kana vocab meaning, mnemonic, TODO hint, part of speech, user meaning note
+        // TODO 1 - user synonym
         // 1 - context sentences
         Subject::KanaVocab(kv) => {
             let num_choices = 2;
@@ -2082,7 +3629,9 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
 
             match info_status {
                 0 => {
-                    kana_vocab_meaning_lines(kv, text_width, wfmt_args)
+                    let mut lines = kana_vocab_meaning_lines(kv, text_width, wfmt_args);
+                    lines.extend(user_note_lines(conn, kv.id, true, text_width).await);
+                    lines
                 },
                 1 => {
                     get_context_sentences(&kv.data.context_sentences, text_width, width)
@@ -2094,6 +3643,88 @@ async fn get_info_lines(subject: &Subject, info_status: usize, wfmt_args: &WaniF
     }
 }
 
+async fn get_study_material(conn: &AsyncConnection, subject_id: i32) -> Option<wanidata::StudyMaterial> {
+    select_data(wanisql::SELECT_STUDY_MATERIAL, conn, wanisql::parse_study_material, [subject_id]).await
+        .ok()
+        .and_then(|mut s| s.pop())
+}
+
+/// the user's meaning or reading note for a subject, ready to add to an info page
+async fn user_note_lines(conn: &AsyncConnection, subject_id: i32, is_meaning: bool, text_width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let note = get_study_material(conn, subject_id).await
+        .and_then(|s| if is_meaning { s.data.meaning_note } else { s.data.reading_note });
+    if let Some(note) = note.filter(|n| !n.trim().is_empty()) {
+        lines.push("---".to_owned());
+        lines.push(if is_meaning { "Meaning Note:" } else { "Reading Note:" }.to_owned());
+        split_str_by_len(&note, text_width, &mut lines);
+    }
+    lines
+}
+
+/// prompts for a new meaning or reading note and saves it to WaniKani.
+/// returns a toast describing how it went.
+async fn edit_user_note(term: &Term, subject_id: i32, is_meaning: bool, conn: &AsyncConnection, web_config: &WaniWebConfig) -> Result<&'static str, WaniError> {
+    if wanidata::is_custom_subject_id(subject_id) {
+        return Ok("Custom deck items don't have notes");
+    }
+    if web_config.offline {
+        return Ok("Can't save notes while offline");
+    }
+    if web_config.read_only {
+        return Ok("Can't save notes in read-only mode");
+    }
+
+    let existing = get_study_material(conn, subject_id).await;
+    let current = existing.as_ref()
+        .and_then(|s| if is_meaning { s.data.meaning_note.clone() } else { s.data.reading_note.clone() })
+        .unwrap_or_default();
+    term.clear_screen()?;
+    term.write_line(if is_meaning { "Meaning Note (Enter to save):" } else { "Reading Note (Enter to save):" })?;
+    term.flush()?;
+    let note = term.read_line_initial_text(&current)?;
+    let note = note.trim();
+    if note == current.trim() {
+        return Ok("Note unchanged");
+    }
+
+    let key = if is_meaning { "meaning_note" } else { "reading_note" };
+    let value = if note.is_empty() { serde_json::Value::Null } else { note.into() };
+    let info = match &existing {
+        Some(s) => RequestInfo {
+            url: format!("https://api.wanikani.com/v2/study_materials/{}", s.id),
+            method: RequestMethod::Put,
+            json: Some(serde_json::json!({ "study_material": { key: value } })),
+            ..Default::default()
+        },
+        None => RequestInfo {
+            url: "https://api.wanikani.com/v2/study_materials".to_owned(),
+            method: RequestMethod::Post,
+            json: Some(serde_json::json!({ "study_material": { "subject_id": subject_id, key: value } })),
+            ..Default::default()
+        },
+    };
+
+    match send_throttled_request(info, Arc::new(Mutex::new(None)), web_config.clone()).await {
+        Ok((wani, _)) => {
+            match wani.data {
+                WaniData::StudyMaterial(s) => {
+                    conn.call(move |c| {
+                        let mut tx = c.transaction()?;
+                        wanisql::store_study_material(s, &mut tx)
+                            .map_err(|e| tokio_rusqlite::Error::Other(Box::new(e)))?;
+                        tx.commit()?;
+                        Ok(())
+                    }).await?;
+                    Ok("Note saved")
+                },
+                _ => Ok("Unexpected response when saving note"),
+            }
+        },
+        Err(_) => Ok("Couldn't save note to WaniKani"),
+    }
+}
+
 fn kana_vocab_meaning_lines(kv: &wanidata::KanaVocab, text_width: usize, wfmt_args: &WaniFmtArgs) -> Vec<String> {
     let mut lines = vec![];
     let meanings = kv.primary_meanings()
@@ -2106,6 +3737,9 @@ fn kana_vocab_meaning_lines(kv: &wanidata::KanaVocab, text_width: usize, wfmt_ar
     if alt_meanings.len() > 0 {
         lines.push(alt_meanings);
     }
+    if let Some(voices) = wanidata::audio_voices_line(&kv.data.pronunciation_audios) {
+        lines.push(format!("{} (j to play)", voices));
+    }
     lines.push("---".to_owned());
     let mnemonic = wanidata::format_wani_text(&kv.data.meaning_mnemonic, &wfmt_args);
     split_str_by_len(&mnemonic, text_width, &mut lines);
@@ -2145,16 +3779,21 @@ async fn vocab_kanji_composition(v: &wanidata::Vocab, conn: &AsyncConnection, la
 
 fn vocab_reading_lines(v: &wanidata::Vocab, text_width: usize, wfmt_args: &WaniFmtArgs) -> Vec<String> {
     let mut lines = vec![];
-    let readings = v.primary_readings()
-        .join(", ");
+    let readings = wanidata::display_readings(v.primary_readings(), wfmt_args.show_romaji);
     if readings.len() > 0 {
         lines.push(readings);
     }
-    let alt_readings = v.alt_readings()
-        .join(", ");
+    let alt_readings = wanidata::display_readings(v.alt_readings(), wfmt_args.show_romaji);
     if alt_readings.len() > 0 {
         lines.push(alt_readings);
     }
+    let not_accepted = wanidata::display_readings(v.not_accepted_readings(), wfmt_args.show_romaji);
+    if !not_accepted.is_empty() {
+        lines.push(format!("{} {}", not_accepted, style("\u{2717} not accepted").red()));
+    }
+    if let Some(voices) = wanidata::audio_voices_line(&v.data.pronunciation_audios) {
+        lines.push(format!("{} (j to play)", voices));
+    }
     lines.push("---".to_owned());
     let mnemonic = wanidata::format_wani_text(&v.data.reading_mnemonic, &wfmt_args);
     split_str_by_len(&mnemonic, text_width, &mut lines);
@@ -2185,16 +3824,18 @@ fn vocab_meaning_lines(v: &wanidata::Vocab, text_width: usize, wfmt_args: &WaniF
 
 fn kanji_reading_lines(k: &wanidata::Kanji, text_width: usize, wfmt_args: &WaniFmtArgs) -> Vec<String> {
     let mut lines = vec![];
-    let readings = k.primary_readings()
-        .join(", ");
+    let readings = wanidata::display_readings(k.primary_readings(), wfmt_args.show_romaji);
     if readings.len() > 0 {
         lines.push(readings);
     }
-    let alt_readings = k.alt_readings()
-        .join(", ");
+    let alt_readings = wanidata::display_readings(k.alt_readings(), wfmt_args.show_romaji);
     if alt_readings.len() > 0 {
         lines.push(alt_readings);
     }
+    let not_accepted = wanidata::display_readings(k.not_accepted_readings(), wfmt_args.show_romaji);
+    if !not_accepted.is_empty() {
+        lines.push(format!("{} {}", not_accepted, style("\u{2717} not accepted").red()));
+    }
     lines.push("---".to_owned());
     let mnemonic = wanidata::format_wani_text(&k.data.reading_mnemonic, &wfmt_args);
     split_str_by_len(&mnemonic, text_width, &mut lines);
@@ -2300,7 +3941,7 @@ async fn lookup_kanji(conn: &AsyncConnection, ids: Vec<i32>) -> Result<Vec<wanid
     }).await?)
 }
 
-async fn try_download_text<F>(url: &str, web_config: &WaniWebConfig, path: &PathBuf, modify_content: F) -> Result<(), WaniError> 
+async fn try_download_text<F>(url: &str, web_config: &WaniWebConfig, path: &Path, modify_content: F) -> Result<(), WaniError> 
 where F: Fn(&str) -> String {
     let request = web_config.client
         .get(url);
@@ -2313,11 +3954,12 @@ where F: Fn(&str) -> String {
             if request.status() != reqwest::StatusCode::OK {
                 Err(WaniError::Generic(format!("Error fetching file. HTTP {}", request.status())))
             } else {
-                if let Ok(mut f) = tokio::fs::File::create(&path).await {
+                let partial_path = get_partial_download_path(path);
+                if let Ok(mut f) = tokio::fs::File::create(&partial_path).await {
                     let mut body = request.text().await?;
                     body = modify_content(&body);
-                    let _ = tokio::io::copy(&mut body.as_bytes(), &mut f).await?;
-                    Ok(())
+                    let res = tokio::io::copy(&mut body.as_bytes(), &mut f).await;
+                    finish_download(res.map(|_| ()), f, &partial_path, path).await
                 }
                 else {
                     Err(WaniError::Generic("Error opening file to save downloaded content.".into()))
@@ -2327,7 +3969,38 @@ where F: Fn(&str) -> String {
     }
 }
 
-async fn try_download_file(url: &str, web_config: &WaniWebConfig, path: &PathBuf) -> Result<(), WaniError> {
+/// a lock per file being downloaded, so downloads of the same file don't write the same .part file
+static DOWNLOAD_LOCKS: std::sync::Mutex<BTreeMap<PathBuf, Arc<Mutex<()>>>> = std::sync::Mutex::new(BTreeMap::new());
+
+/// If the file is already being downloaded, waits for that download instead of starting another
+async fn try_download_file(url: &str, web_config: &WaniWebConfig, path: &Path) -> Result<(), WaniError> {
+    let lock = DOWNLOAD_LOCKS.lock().unwrap_or_else(PoisonError::into_inner)
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone();
+
+    let res = match lock.try_lock() {
+        Ok(_guard) => download_file(url, web_config, path).await,
+        Err(_) => {
+            let _guard = lock.lock().await;
+            if path.exists() {
+                Ok(())
+            }
+            else {
+                download_file(url, web_config, path).await
+            }
+        },
+    };
+
+    // Only the map and this call hold the lock, so no one else is waiting on it
+    let mut locks = DOWNLOAD_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+    if Arc::strong_count(&lock) == 2 {
+        locks.remove(path);
+    }
+    res
+}
+
+async fn download_file(url: &str, web_config: &WaniWebConfig, path: &Path) -> Result<(), WaniError> {
     let request = web_config.client
         .get(url);
 
@@ -2340,15 +4013,14 @@ async fn try_download_file(url: &str, web_config: &WaniWebConfig, path: &PathBuf
                 Err(WaniError::Generic(format!("Error fetching file. HTTP {}", request.status())))
             }
             else {
-                if let Ok(f) = tokio::fs::File::create(&path).await {
-                    let mut reader = tokio::io::BufWriter::new(f);
-                    let res = reader.write_all_buf(&mut request.bytes().await?).await;
-                    if let Err(e) = res {
-                        Err(WaniError::Generic(format!("Error downloading file. {}", e)))
-                    }
-                    else {
-                        Ok(())
+                let partial_path = get_partial_download_path(path);
+                if let Ok(f) = tokio::fs::File::create(&partial_path).await {
+                    let mut writer = tokio::io::BufWriter::new(f);
+                    let mut res = writer.write_all_buf(&mut request.bytes().await?).await;
+                    if res.is_ok() {
+                        res = writer.flush().await;
                     }
+                    finish_download(res, writer.into_inner(), &partial_path, path).await
                 }
                 else {
                     Err(WaniError::Generic("Error opening file to save downloaded content.".into()))
@@ -2358,6 +4030,32 @@ async fn try_download_file(url: &str, web_config: &WaniWebConfig, path: &PathBuf
     }
 }
 
+/// returns the path downloads are written to before they're complete
+fn get_partial_download_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// moves a finished download into place, so an interrupted download never leaves a truncated file
+/// at the cached path
+async fn finish_download(write_result: io::Result<()>, file: tokio::fs::File, partial_path: &Path, path: &Path) -> Result<(), WaniError> {
+    let res = match write_result {
+        Ok(_) => file.sync_all().await,
+        Err(e) => Err(e),
+    };
+    drop(file);
+    let res = match res {
+        Ok(_) => tokio::fs::rename(partial_path, path).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = res {
+        let _ = tokio::fs::remove_file(partial_path).await;
+        return Err(WaniError::Generic(format!("Error downloading file. {}", e)));
+    }
+    Ok(())
+}
+
 async fn get_radical_image(radical: &wanidata::Radical, image_cache: &PathBuf, target_width: u32, web_config: &WaniWebConfig) -> Result<io::Lines<io::BufReader<File>>, WaniError> {
     fn try_convert_image_png(path: &PathBuf, output_path: &PathBuf) -> Result<(), WaniError> {
         let svg = fs::read_to_string(path)?;
@@ -2393,10 +4091,14 @@ async fn get_radical_image(radical: &wanidata::Radical, image_cache: &PathBuf, t
             let res = image2ascii(p, target_width, Some(50.0), None);
             match res {
                 Ok(a) => {
-                    let mut file = fs::File::create(text_path)?;
+                    // Written to a temp file first, so an interrupted write never leaves a partial .txt
+                    let tmp_path = text_path.with_extension("txt.tmp");
+                    let mut file = fs::File::create(&tmp_path)?;
                     for line in a.to_lines() {
                         writeln!(file, "{}", line)?;
                     }
+                    file.sync_all()?;
+                    fs::rename(&tmp_path, text_path)?;
                     Ok(())
                 },
                 Err(e) => {
@@ -2414,6 +4116,11 @@ async fn get_radical_image(radical: &wanidata::Radical, image_cache: &PathBuf, t
         {
             for entry in entries {
                 if let Ok(path) = entry {
+                    // A broken cached image is removed, and converted again below
+                    if !wanidata::cached_ascii_is_valid(&path) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
                     let txt_path = path.to_str();
                     if let Some(txt_path) = txt_path {
                         return Ok(read_lines(&txt_path)?)
@@ -2455,6 +4162,8 @@ async fn get_radical_image(radical: &wanidata::Radical, image_cache: &PathBuf, t
         })
     .collect::<Vec<_>>();
 
+    let mut last_err = None;
+
     for i in 0..png_paths.len() {
         let res = try_asciify_image(&png_paths[i], target_width, &txt_paths[i]);
         if let Ok(_) = res {
@@ -2483,71 +4192,120 @@ async fn get_radical_image(radical: &wanidata::Radical, image_cache: &PathBuf, t
         let f = |body: &str| {
             body.replace("var(--color-text, #000)", "rgb(0,0,0)")
         };
-        let res = try_download_text(&radical.data.character_images[i].url, web_config, &svg_paths[i], f).await;
-        if let Ok(_) = res {
-            let res = try_convert_image_png(&svg_paths[i], &png_paths[i]);
-            if let Ok(_) = res {
-                let res = try_asciify_image(&png_paths[i], target_width, &txt_paths[i]);
-                if let Ok(_) = res {
-                    return Ok(read_lines(&txt_paths[i])?)
-                }
-            }
+        let res = try_download_text(&radical.data.character_images[i].url, web_config, &svg_paths[i], f).await
+            .and_then(|_| try_convert_image_png(&svg_paths[i], &png_paths[i]))
+            .and_then(|_| try_asciify_image(&png_paths[i], target_width, &txt_paths[i]));
+        match res {
+            Ok(_) => return Ok(read_lines(&txt_paths[i])?),
+            Err(e) => last_err = Some(e),
         }
     }
 
-    Err(WaniError::Generic("Failed to convert any images.".into()))
+    Err(WaniError::Generic(match last_err {
+        Some(e) => format!("Failed to convert any images. Last error: {}", e),
+        None => "Failed to convert any images.".into(),
+    }))
 }
 
-async fn play_audio_for_subj(id: i32, audios: Vec<AudioInfo>, audio_cache: &PathBuf, web_config: &WaniWebConfig) -> Result<(), WaniError> {
-    fn get_audio_path(audio: &AudioInfo, audio_cache: &PathBuf, id: i32, index: usize) -> Option<PathBuf> {
-        let ext;
-        const MPEG: &str = "audio/mpeg";
-        const OGG: &str = "audio/ogg";
-        const WEBM: &str = "audio/webm";
-        if audio.content_type == MPEG {
-            ext = Some(".mpeg");
-        }
-        else if audio.content_type == OGG {
-            ext = Some(".ogg");
-        }
-        else if audio.content_type == WEBM {
-            ext = Some(".webm");
-        }
-        else {
-            ext = None;
-        }
+fn get_cached_audio_path(audio: &AudioInfo, audio_cache: &PathBuf, id: i32, index: usize) -> Option<PathBuf> {
+    let ext;
+    const MPEG: &str = "audio/mpeg";
+    const OGG: &str = "audio/ogg";
+    const WEBM: &str = "audio/webm";
+    if audio.content_type == MPEG {
+        ext = Some(".mpeg");
+    }
+    else if audio.content_type == OGG {
+        ext = Some(".ogg");
+    }
+    else if audio.content_type == WEBM {
+        ext = Some(".webm");
+    }
+    else {
+        ext = None;
+    }
 
-        if let None = ext {
-            return None;
+    if let None = ext {
+        return None;
+    }
+    let ext = ext.unwrap();
+
+    let mut audio_path = audio_cache.clone();
+    audio_path.push(format!("{}_{}{}", id, index, ext));
+    Some(audio_path)
+}
+
+fn audio_infos(audios: &[wanidata::PronunciationAudio]) -> Vec<AudioInfo> {
+    audios.iter()
+        .map(|a| AudioInfo {
+            url: a.url.clone(),
+            content_type: a.content_type.clone(),
+            metadata: a.metadata.clone(),
+        })
+        .collect_vec()
+}
+
+/// Try the preferred voice's audio first, then the formats rodio decodes most reliably first.
+/// Cached file names keep the original index, so only the order of attempts changes.
+fn get_audio_play_order(audios: &[AudioInfo], audio_formats: &[String], preferred_voice: Option<&str>) -> Vec<usize> {
+    (0..audios.len())
+        .sorted_by_key(|i| (
+            !preferred_voice.is_some_and(|v| wanidata::is_preferred_voice(&audios[*i].metadata, v)),
+            audio_formats.iter()
+                .position(|f| *f == audios[*i].content_type)
+                .unwrap_or(audio_formats.len())))
+        .collect_vec()
+}
+
+/// makes sure the audio that would be played for a subject is in the cache, downloading it if needed
+async fn preload_audio(id: i32, audios: &[AudioInfo], audio_cache: &PathBuf, web_config: &WaniWebConfig, audio_formats: &[String], preferred_voice: Option<&str>) -> Result<(), WaniError> {
+    let audio_paths = get_audio_play_order(audios, audio_formats, preferred_voice).into_iter()
+        .filter_map(|i| get_cached_audio_path(&audios[i], audio_cache, id, i).map(|p| (i, p)))
+        .collect_vec();
+
+    if audio_paths.iter().any(|(_, p)| p.exists()) {
+        return Ok(());
+    }
+    let mut last_err = WaniError::Generic(format!("No playable audio for subject {}", id));
+    for (i, path) in audio_paths {
+        match try_download_file(&audios[i].url, web_config, &path).await {
+            Ok(_) => return Ok(()),
+            Err(e) => last_err = e,
         }
-        let ext = ext.unwrap();
+    }
+    Err(last_err)
+}
 
-        let mut audio_path = audio_cache.clone();
-        audio_path.push(format!("{}_{}{}", id, index, ext));
-        Some(audio_path)
+async fn play_audio_for_subj(id: i32, audios: Vec<AudioInfo>, audio_cache: &PathBuf, web_config: &WaniWebConfig, audio_formats: &[String], preferred_voice: Option<&str>, volume: f32) -> Result<(), WaniError> {
+    if no_audio_device() {
+        return Err(WaniError::NoAudioDevice());
     }
 
     let audio_paths = audios.iter()
         .enumerate()
-        .map(|(i, a)| get_audio_path(a, audio_cache, id, i))
+        .map(|(i, a)| get_cached_audio_path(a, audio_cache, id, i))
         .collect::<Vec<_>>();
 
-    for i in 0..audio_paths.len() {
+    let order = get_audio_play_order(&audios, audio_formats, preferred_voice);
+
+    for &i in &order {
         if let Some(path) = &audio_paths[i] {
-            let res = play_audio(&path);
-            if let Ok(_) = res {
-                return Ok(());
+            match play_audio(&path, volume) {
+                Ok(_) => return Ok(()),
+                Err(WaniError::NoAudioDevice()) => return Err(WaniError::NoAudioDevice()),
+                Err(_) => {},
             }
         }
     }
 
-    for i in 0..audios.len() {
+    for &i in &order {
         if let Some(path) = &audio_paths[i] {
             let res = try_download_file(&audios[i].url, web_config, &path).await;
             if let Ok(_) = res {
-                let play_res = play_audio(&path);
-                if let Ok(_) = play_res {
-                    return Ok(());
+                match play_audio(&path, volume) {
+                    Ok(_) => return Ok(()),
+                    Err(WaniError::NoAudioDevice()) => return Err(WaniError::NoAudioDevice()),
+                    Err(_) => {},
                 }
             }
         }
@@ -2624,7 +4382,7 @@ async fn load_existing_reviews(c: &AsyncConnection, assignments: &Vec<wanidata::
     let mut available_at_by_id = HashMap::with_capacity(assignments.len());
     for ass in assignments {
         if let Some(available_at) = ass.data.available_at {
-            available_at_by_id.insert(ass.id, available_at);
+            available_at_by_id.insert(ass.id, (available_at, ass.data.srs_stage));
         }
     }
 
@@ -2650,13 +4408,18 @@ async fn load_existing_reviews(c: &AsyncConnection, assignments: &Vec<wanidata::
                                 println!("Error loading review: {}", r);
                                 continue;
                             }
-                            let r = r.unwrap();
+                            let mut r = r.unwrap();
 
                             if let Some(available_at) = r.available_at {
-                                if let Some(ass_available) = available_at_by_id.get(&r.assignment_id) {
+                                if let Some((ass_available, ass_srs_stage)) = available_at_by_id.get(&r.assignment_id) {
                                     if ass_available != &available_at {
-                                        loaded_revs.invalid_reviews.push(r);
-                                        continue;
+                                        if r.srs_stage != Some(*ass_srs_stage) {
+                                            // Reviewed elsewhere since, so these answers are for an old SRS stage
+                                            loaded_revs.invalid_reviews.push(r);
+                                            continue;
+                                        }
+                                        // WaniKani only re-timed the assignment. Keep the progress and adopt the new time.
+                                        r.available_at = Some(*ass_available);
                                     }
                                 }
                                 else {
@@ -2711,7 +4474,7 @@ where T: Send + Sync + 'static, F : Send + Sync + 'static + Fn(&rusqlite::Row<'_
     }).await;
 }
 
-async fn command_sync(args: &Args, ignore_cache: bool) {
+async fn command_sync(args: &Args, ignore_cache: bool, up_to_level: Option<Option<i32>>, with_images: bool) {
     let p_config = get_program_config(args);
     if let Err(e) = &p_config {
         eprintln!("{}", e);
@@ -2728,12 +4491,71 @@ async fn command_sync(args: &Args, ignore_cache: bool) {
     match conn {
         Err(e) => eprintln!("{}", e),
         Ok(c) => {
-            sync_all(&mut p_config, &web_config, &c, ignore_cache).await;
+            sync_all(&mut p_config, &web_config, &c, ignore_cache, up_to_level).await;
+            if with_images {
+                cache_radical_images(&p_config, &web_config, &c).await;
+            }
+        },
+    };
+}
+
+/// converts the images of every synced radical that's shown as an image, a few at a time
+async fn cache_radical_images(p_config: &ProgramConfig, web_config: &WaniWebConfig, conn: &AsyncConnection) {
+    const MAX_CONCURRENT_IMAGES: usize = 4;
+
+    let image_cache = match get_image_cache(p_config) {
+        Ok(p) => Arc::new(p),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+    let radicals = match select_data(wanisql::SELECT_RADICALS, conn, wanisql::parse_radical, []).await {
+        Ok(radicals) => radicals.into_iter().filter(radical_uses_image).collect_vec(),
+        Err(e) => {
+            eprintln!("Error loading radicals. Error: {}", e);
+            return;
         },
     };
+    if radicals.is_empty() {
+        return;
+    }
+
+    let term = Term::stdout();
+    // The ascii art is cached at this width, so match what review sessions in this terminal draw
+    let radical_width = radical_width(&term);
+    let total = radicals.len();
+    let mut cached = 0;
+    let mut failed = 0;
+    let mut radicals = radicals.into_iter();
+    let mut join_set = JoinSet::new();
+    loop {
+        while join_set.len() < MAX_CONCURRENT_IMAGES {
+            let Some(radical) = radicals.next() else {
+                break;
+            };
+            let image_cache = image_cache.clone();
+            let web_config = web_config.clone();
+            join_set.spawn(async move {
+                get_radical_image(&radical, &image_cache, radical_width, &web_config).await.map(|_| ())
+            });
+        }
+
+        match join_set.join_next().await {
+            Some(Ok(Ok(_))) => cached += 1,
+            Some(_) => failed += 1,
+            None => break,
+        }
+        let _ = term.clear_line();
+        let _ = term.write_str(&format!("Caching radical images. . . {}/{}", cached + failed, total));
+    }
+    let _ = term.clear_line();
+    println!("Cached Radical Images: {}, Errors: {}", cached, failed);
 }
 
-async fn sync_assignments(conn: &AsyncConnection, web_config: &WaniWebConfig, cache_info: CacheInfo, rate_limit: &RateLimitBox, is_user_restricted: bool) -> Result<SyncResult, WaniError> {
+async fn sync_assignments(conn: &AsyncConnection, web_config: &WaniWebConfig, cache_info: CacheInfo, rate_limit: &RateLimitBox, max_level: Option<i32>) -> Result<SyncResult, WaniError> {
+    let cache_info = get_sync_cache_info(cache_info, max_level);
+    let levels = max_level.map(wanisql::levels_query);
     let mut next_url = Some("https://api.wanikani.com/v2/assignments".to_owned());
 
     let mut assignments = vec![];
@@ -2745,8 +4567,8 @@ async fn sync_assignments(conn: &AsyncConnection, web_config: &WaniWebConfig, ca
         if let Some(after) = &cache_info.updated_after {
             query.push(("updated_after", after));
         }
-        if is_user_restricted {
-            query.push(("levels", "1,2,3"));
+        if let Some(levels) = &levels {
+            query.push(("levels", levels));
         }
         
         let info = RequestInfo::<()> {
@@ -2824,7 +4646,11 @@ async fn sync_assignments(conn: &AsyncConnection, web_config: &WaniWebConfig, ca
             }
         }
 
-        match update_cache(last_modified, wanisql::CACHE_TYPE_ASSIGNMENTS, time, etag, &conn).await {
+        let res = match update_cache(last_modified, wanisql::CACHE_TYPE_ASSIGNMENTS, time, etag, &conn).await {
+            Ok(_) => set_cache_level_cap(wanisql::CACHE_TYPE_ASSIGNMENTS, max_level, conn).await,
+            Err(e) => Err(e),
+        };
+        match res {
             Ok(_) => (),
             Err(e) => { 
                 eprintln!("Failed to update assignment cache. Error: {}", e);
@@ -2838,68 +4664,219 @@ async fn sync_assignments(conn: &AsyncConnection, web_config: &WaniWebConfig, ca
     });
 }
 
-async fn cache_user_info(config: &mut ProgramConfig, web_config: &WaniWebConfig, conn: &AsyncConnection, rate_limit: &RateLimitBox) {
-    match get_user_info(web_config, conn, rate_limit).await {
-        Ok(user) => {
-            config.user = user.data
-        },
-        Err(_) => {
-        },
-    }
-}
-
-async fn get_user_info(web_config: &WaniWebConfig, conn: &AsyncConnection, rate_limit: &RateLimitBox) -> Result<wanidata::User, WaniError> {
-    let mut cache_info = conn.call(|conn| {
-        let mut stmt = conn.prepare("select i.id, i.last_modified, i.updated_after, i.etag from cache_info i;")?;
-        let infos = stmt.query_map([],
-                                   |r| Ok(CacheInfo {
-                                       id: r.get::<usize, usize>(0)?,
-                                       last_modified: r.get::<usize, Option<String>>(1)?, 
-                                       updated_after: r.get::<usize, Option<String>>(2)?,
-                                       etag: r.get::<usize, Option<String>>(3)? }))?;
+async fn sync_study_materials(conn: &AsyncConnection, web_config: &WaniWebConfig, cache_info: CacheInfo, rate_limit: &RateLimitBox) -> Result<SyncResult, WaniError> {
+    let mut next_url = Some("https://api.wanikani.com/v2/study_materials".to_owned());
 
-        let mut map = HashMap::new();
-        for info in infos {
-            if let Ok(i) = info {
-                map.insert(i.id, i);
-            }
+    let mut study_materials = vec![];
+    let mut last_request_time: Option<DateTime<Utc>> = None;
+    let mut headers = None;
+    while let Some(url) = next_url {
+        next_url = None;
+        let mut query: Vec<(&str, &str)> = vec![];
+        if let Some(after) = &cache_info.updated_after {
+            query.push(("updated_after", after));
         }
-        return Ok(map);
-    }).await?;
-    let user_cache = cache_info.remove(&wanisql::CACHE_TYPE_USER);
-
-    let users = select_data(wanisql::SELECT_USER, conn, wanisql::parse_user, []).await?;
+        
+        let info = RequestInfo::<()> {
+            url,
+            method: RequestMethod::Get,
+            query: if !query.is_empty() { Some(query) } else { None },
+            headers: cache_info.etag.as_ref()
+                .map(|etag| vec![(reqwest::header::ETAG.to_string(), etag.to_owned())])
+                .or_else(|| cache_info.last_modified.as_ref()
+                    .map(|tag| vec![(reqwest::header::IF_MODIFIED_SINCE.to_string(), tag.to_owned())])),
+            ..Default::default()
+        };
 
-    let mut load_user = users.len() == 0;
-    if !load_user {
-        load_user = match &user_cache {
-            Some(user_cache) => { 
-                match &user_cache.updated_after {
-                    Some(updated_after) => {
-                        match DateTime::parse_from_rfc3339(updated_after) {
-                            Ok(updated_after) => {
-                                let expiration = Utc::now() - chrono::Duration::days(1);
-                                updated_after.with_timezone(&Utc) < expiration
-                            },
-                            Err(_) => { 
-                                true
+        last_request_time = Some(Utc::now());
+        match send_throttled_request(info, rate_limit.clone(), web_config.clone()).await {
+            Ok(t) => {
+                headers = Some(t.1);
+                match t.0.data {
+                    WaniData::Collection(c) => {
+                        next_url = c.pages.next_url;
+                        for wd in c.data {
+                            if let WaniData::StudyMaterial(s) = wd {
+                                study_materials.push(s);
                             }
                         }
                     },
-                    None => {
-                        true
-                    }
+                    _ => {
+                        last_request_time = None; // clear last request time to avoid invalidate
+                                                  // cache
+                        eprintln!("Unexpected response when fetching study material data. {:?}", t.0.data);
+                    },
                 }
             },
-            None => {
-                true
+            Err(e) => {
+                return Err(e);
             }
-        };
+        }
     }
-    if !load_user {
-        if let Some(period_end) = users[0].data.subscription.period_ends_at {
-            if period_end < Utc::now() {
-                load_user = true;
+
+    let study_count = study_materials.len();
+    let study_fail = conn.call(|c| {
+        let tx = c.transaction();
+        if let Err(e) = tx {
+            return Err(tokio_rusqlite::Error::Rusqlite(e));
+        }
+        let mut tx = tx.unwrap();
+        let mut study_fail = 0;
+        for s in study_materials {
+            match wanisql::store_study_material(s, &mut tx) {
+                Ok(_) => {},
+                Err(_) => study_fail += 1,
+            };
+        }
+        tx.commit()?;
+        Ok(study_fail)
+    }).await?; // Await this before updating cache so we don't update cache if there's a
+               // problem inserting
+
+    if let Some(time) = last_request_time {
+        let mut last_modified = None;
+        let mut etag = None;
+        if let Some(h) = &headers {
+            if let Some(tag) = h.get(reqwest::header::LAST_MODIFIED) {
+                if let Ok(t) = tag.to_str() {
+                    last_modified = Some(t.to_owned());
+                }
+            }
+
+            if let Some(tag) = h.get(reqwest::header::ETAG) {
+                etag = Some(tag);
+            }
+        }
+
+        if let Err(e) = update_cache(last_modified, wanisql::CACHE_TYPE_STUDY_MATERIALS, time, etag, conn).await {
+            eprintln!("Failed to update study material cache. Error: {}", e);
+        }
+    }
+
+    Ok(SyncResult {
+        success_count: study_count,
+        fail_count: study_fail,
+    })
+}
+
+/// levels past the user's current level to include in a level-capped sync
+const SYNC_LEVEL_BUFFER: i32 = 2;
+
+/// returns the highest level to sync, or None to sync all levels.
+/// up_to_level is Some(None) when a cap was requested without a level.
+fn get_max_sync_level(user: &wanidata::UserData, up_to_level: Option<Option<i32>>) -> Option<i32> {
+    let cap = match up_to_level {
+        None => None,
+        Some(Some(level)) => Some(level),
+        Some(None) => Some(max(user.level, 1) + SYNC_LEVEL_BUFFER),
+    };
+    match (cap, user.is_restricted()) {
+        (Some(cap), true) => Some(min(cap, 3)),
+        (None, true) => Some(3),
+        (cap, false) => cap,
+    }
+}
+
+/// ignores cached request info from a previous sync that covered fewer levels than this one,
+/// so the levels it skipped get filled in
+fn get_sync_cache_info(cache_info: CacheInfo, max_level: Option<i32>) -> CacheInfo {
+    match (cache_info.level_cap, max_level) {
+        (Some(prev_cap), Some(max_level)) if max_level <= prev_cap => cache_info,
+        (Some(_), _) => CacheInfo { id: cache_info.id, ..Default::default() },
+        (None, _) => cache_info,
+    }
+}
+
+async fn set_cache_level_cap(cache_type: usize, max_level: Option<i32>, conn: &AsyncConnection) -> Result<(), tokio_rusqlite::Error> {
+    conn.call(move |c| {
+        c.execute(wanisql::SET_CACHE_LEVEL_CAP, params![max_level, cache_type])?;
+        Ok(())
+    }).await
+}
+
+/// checks whether WaniKani is reachable before a session, so the user knows up front when
+/// results can't be submitted. Sets web_config.offline, which skips submitting results.
+async fn check_online(web_config: &mut WaniWebConfig, conn: &AsyncConnection, rate_limit: &RateLimitBox) {
+    // A conditional request for the user is cheap: usually a 304 with no body
+    let etag = match wanisql::get_all_cache_infos(conn, false).await {
+        Ok(mut infos) => infos.remove(&wanisql::CACHE_TYPE_USER).and_then(|i| i.etag),
+        Err(_) => None,
+    };
+    let info = RequestInfo::<()> {
+        url: "https://api.wanikani.com/v2/user".to_owned(),
+        headers: etag.map(|e| vec![("If-None-Match".to_owned(), e)]),
+        ..Default::default()
+    };
+
+    if let Err(WaniError::Connection()) = send_throttled_request(info, rate_limit.clone(), web_config.clone()).await {
+        web_config.offline = true;
+        println!("You appear offline; results will be saved locally and submitted later");
+    }
+}
+
+async fn cache_user_info(config: &mut ProgramConfig, web_config: &WaniWebConfig, conn: &AsyncConnection, rate_limit: &RateLimitBox) {
+    match get_user_info(web_config, conn, rate_limit).await {
+        Ok(user) => {
+            config.user = user.data
+        },
+        Err(_) => {
+        },
+    }
+}
+
+async fn get_user_info(web_config: &WaniWebConfig, conn: &AsyncConnection, rate_limit: &RateLimitBox) -> Result<wanidata::User, WaniError> {
+    let mut cache_info = conn.call(|conn| {
+        let mut stmt = conn.prepare("select i.id, i.last_modified, i.updated_after, i.etag from cache_info i;")?;
+        let infos = stmt.query_map([],
+                                   |r| Ok(CacheInfo {
+                                       id: r.get::<usize, usize>(0)?,
+                                       last_modified: r.get::<usize, Option<String>>(1)?, 
+                                       updated_after: r.get::<usize, Option<String>>(2)?,
+                                       etag: r.get::<usize, Option<String>>(3)?,
+                                       level_cap: None }))?;
+
+        let mut map = HashMap::new();
+        for info in infos {
+            if let Ok(i) = info {
+                map.insert(i.id, i);
+            }
+        }
+        return Ok(map);
+    }).await?;
+    let user_cache = cache_info.remove(&wanisql::CACHE_TYPE_USER);
+
+    let users = select_data(wanisql::SELECT_USER, conn, wanisql::parse_user, []).await?;
+
+    let mut load_user = users.len() == 0;
+    if !load_user {
+        load_user = match &user_cache {
+            Some(user_cache) => { 
+                match &user_cache.updated_after {
+                    Some(updated_after) => {
+                        match DateTime::parse_from_rfc3339(updated_after) {
+                            Ok(updated_after) => {
+                                let expiration = Utc::now() - chrono::Duration::days(1);
+                                updated_after.with_timezone(&Utc) < expiration
+                            },
+                            Err(_) => { 
+                                true
+                            }
+                        }
+                    },
+                    None => {
+                        true
+                    }
+                }
+            },
+            None => {
+                true
+            }
+        };
+    }
+    if !load_user {
+        if let Some(period_end) = users[0].data.subscription.period_ends_at {
+            if period_end < Utc::now() {
+                load_user = true;
             }
         }
     }
@@ -2973,157 +4950,128 @@ async fn load_user_from_wk(web_config: &WaniWebConfig, conn: &AsyncConnection, r
     }
 }
 
-async fn sync_all(p_config: &mut ProgramConfig, web_config: &WaniWebConfig, conn: &AsyncConnection, ignore_cache: bool) {
-    async fn sync_subjects(conn: &AsyncConnection, 
-                           web_config: &WaniWebConfig, subjects_cache: CacheInfo, rate_limit: &RateLimitBox, is_user_restricted: bool) -> Result<SyncResult, WaniError> {
-        let mut next_url: Option<String> = Some("https://api.wanikani.com/v2/subjects".into());
-        let mut total_parse_fails = 0;
-        let mut updated_resources = 0;
-        let mut headers: Option<reqwest::header::HeaderMap> = None;
-        let mut last_request_time = Utc::now();
-        while let Some(url) = next_url {
-            let mut query: Vec<(&str, &str)> = vec![];
-            if let Some(after) = &subjects_cache.updated_after {
-                query.push(("updated_after", after));
-            }
-            if is_user_restricted {
-                query.push(("levels", "1,2,3"));
-            }
-            let info = RequestInfo::<()> {
-                url,
-                method: RequestMethod::Get,
-                query: if query.len() > 0 { Some(query) } else { None },
-                headers: if let Some(etag) = &subjects_cache.etag {
-                    Some(vec![(reqwest::header::ETAG.to_string(), etag.to_owned())])
-                } else if let Some(tag) = &subjects_cache.last_modified {
-                    Some(vec![(reqwest::header::IF_MODIFIED_SINCE.to_string(), tag.to_owned())])
-                } else { None },
-                ..Default::default()
-            };
-
-            last_request_time = Utc::now();
-            next_url = None;
-            let resp = send_throttled_request(info, rate_limit.clone(), web_config.clone()).await;
-            match resp {
-                Ok(t) => {
-                    let wr = t.0;
-                    headers = Some(t.1);
-
-                    match wr.data {
-                        WaniData::Collection(c) => {
-                            next_url = c.pages.next_url;
-                            let mut radicals: Vec<wanidata::Radical> = vec![];
-                            let mut kanji: Vec<wanidata::Kanji> = vec![];
-                            let mut vocab: Vec<wanidata::Vocab> = vec![];
-                            let mut kana_vocab: Vec<wanidata::KanaVocab> = vec![];
-                            for wd in c.data {
-                                match wd {
-                                    WaniData::Radical(r) => {
-                                        radicals.push(r);
-                                    }, 
-                                    WaniData::Kanji(k) => {
-                                        kanji.push(k);
-                                    },
-                                    WaniData::Vocabulary(v) => {
-                                        vocab.push(v);
-                                    },
-                                    WaniData::KanaVocabulary(kv) => {
-                                        kana_vocab.push(kv);
-                                    },
-                                    _ => {},
-                                }
-                            }
-
-                            let fut = conn.call(move |conn| {
-                                let mut parse_fails = 0;
-                                let mut tx = conn.transaction()?;
+async fn sync_subjects(conn: &AsyncConnection, 
+                       web_config: &WaniWebConfig, subjects_cache: CacheInfo, rate_limit: &RateLimitBox, max_level: Option<i32>, verbose: bool) -> Result<SyncResult, WaniError> {
+    let subjects_cache = get_sync_cache_info(subjects_cache, max_level);
+    let levels = max_level.map(wanisql::levels_query);
+    let mut next_url: Option<String> = Some("https://api.wanikani.com/v2/subjects".into());
+    let mut total_parse_fails = 0;
+    let mut unrecognized_keys = BTreeSet::new();
+    let mut updated_resources = 0;
+    let mut headers: Option<reqwest::header::HeaderMap> = None;
+    let mut last_request_time = Utc::now();
+    while let Some(url) = next_url {
+        let mut query: Vec<(&str, &str)> = vec![];
+        if let Some(after) = &subjects_cache.updated_after {
+            query.push(("updated_after", after));
+        }
+        if let Some(levels) = &levels {
+            query.push(("levels", levels));
+        }
+        let info = RequestInfo::<()> {
+            url,
+            method: RequestMethod::Get,
+            query: if query.len() > 0 { Some(query) } else { None },
+            headers: if let Some(etag) = &subjects_cache.etag {
+                Some(vec![(reqwest::header::ETAG.to_string(), etag.to_owned())])
+            } else if let Some(tag) = &subjects_cache.last_modified {
+                Some(vec![(reqwest::header::IF_MODIFIED_SINCE.to_string(), tag.to_owned())])
+            } else { None },
+            keep_raw: verbose,
+            ..Default::default()
+        };
 
-                                let rad_len = radicals.len();
-                                for r in radicals {
-                                    match wanisql::store_radical(r, &mut tx) {
-                                        Err(_) => {
-                                            parse_fails += 1;
-                                        }
-                                        Ok(_) => {},
-                                    }
-                                }
+        last_request_time = Utc::now();
+        next_url = None;
+        let resp = send_throttled_request(info, rate_limit.clone(), web_config.clone()).await;
+        match resp {
+            Ok(t) => {
+                let wr = t.0;
+                headers = Some(t.1);
 
-                                let kanji_len = kanji.len();
-                                for k in kanji {
-                                    match wanisql::store_kanji(k, &mut tx) {
-                                        Err(_) => {
-                                            parse_fails += 1;
-                                        }
-                                        Ok(_) => {},
-                                    }
-                                }
+                // Compare the raw subjects against what we parse to catch API changes early
+                if let Some(subjects) = wr.raw.as_ref().and_then(|r| r.get("data")).and_then(|d| d.as_array()) {
+                    for subject in subjects {
+                        unrecognized_keys.extend(wanidata::unrecognized_subject_keys(subject));
+                    }
+                }
 
-                                let vocab_len = vocab.len();
-                                for v in vocab {
-                                    match wanisql::store_vocab(v, &mut tx) {
-                                        Err(_) => {
-                                            parse_fails += 1;
-                                        }
-                                        Ok(_) => {},
-                                    }
-                                }
+                match wr.data {
+                    WaniData::Collection(c) => {
+                        next_url = c.pages.next_url;
+                        // store each subject straight from the page, reusing one cached insert
+                        // statement per type
+                        let fut = conn.call(move |conn| {
+                            let mut parse_fails = 0;
+                            let mut stored = 0;
+                            let mut tx = conn.transaction()?;
 
-                                let kana_vocab_len = kana_vocab.len();
-                                for v in kana_vocab {
-                                    match wanisql::store_kana_vocab(v, &mut tx) {
-                                        Err(_) => {
-                                            parse_fails += 1;
-                                        }
-                                        Ok(_) => {},
-                                    }
+                            for wd in c.data {
+                                let res = match wd {
+                                    WaniData::Radical(r) => wanisql::store_radical(r, &mut tx),
+                                    WaniData::Kanji(k) => wanisql::store_kanji(k, &mut tx),
+                                    WaniData::Vocabulary(v) => wanisql::store_vocab(v, &mut tx),
+                                    WaniData::KanaVocabulary(kv) => wanisql::store_kana_vocab(kv, &mut tx),
+                                    _ => continue,
+                                };
+                                match res {
+                                    Ok(_) => stored += 1,
+                                    Err(_) => parse_fails += 1,
                                 }
+                            }
 
-                                tx.commit()?;
+                            tx.commit()?;
 
-                                Ok(SyncResult {
-                                    success_count: rad_len + kanji_len + vocab_len + kana_vocab_len - parse_fails,
-                                    fail_count: parse_fails,
-                                })
-                            });
-                            let r = fut.await?;
-                            updated_resources += r.success_count;
-                            total_parse_fails += r.fail_count;
-                        },
-                        _ => {
-                            eprintln!("Unexpected data returned while updating resources cache: {:?}", wr.data)
-                        },
-                    }
+                            Ok(SyncResult {
+                                success_count: stored,
+                                fail_count: parse_fails,
+                            })
+                        });
+                        let r = fut.await?;
+                        updated_resources += r.success_count;
+                        total_parse_fails += r.fail_count;
+                    },
+                    _ => {
+                        eprintln!("Unexpected data returned while updating resources cache: {:?}", wr.data)
+                    },
                 }
-                Err(s) => {
-                    headers = None; // clear out headers to skip updating cache_info.last_modified if any
-                                    // requests fail.
-                    eprintln!("{}", s);
-                },
             }
+            Err(s) => {
+                headers = None; // clear out headers to skip updating cache_info.last_modified if any
+                                // requests fail.
+                eprintln!("{}", s);
+            },
         }
+    }
 
-        let mut etag = None;
-        if let Some(h) = &headers { 
-            if let Some(tag) = h.get(reqwest::header::ETAG) {
-                etag = Some(tag);
-            }
+    let mut etag = None;
+    if let Some(h) = &headers { 
+        if let Some(tag) = h.get(reqwest::header::ETAG) {
+            etag = Some(tag);
+        }
 
-            if let Some(tag) = h.get(reqwest::header::LAST_MODIFIED) {
-                if let Ok(t) = tag.to_str() {
-                    update_cache(Some(t.to_owned()), wanisql::CACHE_TYPE_SUBJECTS, last_request_time, etag, &conn).await?;
-                }
-                else {
-                    update_cache(None, wanisql::CACHE_TYPE_SUBJECTS, last_request_time, etag, &conn).await?;
-                }
+        if let Some(tag) = h.get(reqwest::header::LAST_MODIFIED) {
+            if let Ok(t) = tag.to_str() {
+                update_cache(Some(t.to_owned()), wanisql::CACHE_TYPE_SUBJECTS, last_request_time, etag, &conn).await?;
+            }
+            else {
+                update_cache(None, wanisql::CACHE_TYPE_SUBJECTS, last_request_time, etag, &conn).await?;
             }
+            set_cache_level_cap(wanisql::CACHE_TYPE_SUBJECTS, max_level, conn).await?;
         }
+    }
 
-        return Ok(SyncResult {
-            success_count: updated_resources,
-            fail_count: total_parse_fails,
-        });
+    for key in unrecognized_keys {
+        eprintln!("Unrecognized subject field from WaniKani API: {}", key);
     }
 
+    return Ok(SyncResult {
+        success_count: updated_resources,
+        fail_count: total_parse_fails,
+    });
+}
+
+async fn sync_all(p_config: &mut ProgramConfig, web_config: &WaniWebConfig, conn: &AsyncConnection, ignore_cache: bool, up_to_level: Option<Option<i32>>) {
     let c_infos = wanisql::get_all_cache_infos(&conn, ignore_cache).await;
     if let Err(e) = c_infos {
         eprintln!("Error fetching cache infos. Error: {}", e);
@@ -3133,13 +5081,24 @@ async fn sync_all(p_config: &mut ProgramConfig, web_config: &WaniWebConfig, conn
 
     let rate_limit = Arc::new(Mutex::new(None));
     cache_user_info(p_config, &web_config, conn, &rate_limit).await;
-    let is_user_restricted = p_config.user.is_restricted();
+    let max_level = get_max_sync_level(&p_config.user, up_to_level);
+    if let Some(level) = max_level {
+        println!("Syncing up to level {}", level);
+    }
     println!("Syncing subjects. . .");
-    let subj_future = sync_subjects(&conn, &web_config, c_infos.remove(&wanisql::CACHE_TYPE_SUBJECTS).unwrap_or(CacheInfo { id: wanisql::CACHE_TYPE_SUBJECTS, ..Default::default()}), &rate_limit, is_user_restricted);
+    let subj_future = sync_subjects(&conn, &web_config, c_infos.remove(&wanisql::CACHE_TYPE_SUBJECTS).unwrap_or(CacheInfo { id: wanisql::CACHE_TYPE_SUBJECTS, ..Default::default()}), &rate_limit, max_level, p_config.verbose);
     println!("Syncing assignments. . .");
-    let ass_future = sync_assignments(&conn, &web_config, c_infos.remove(&wanisql::CACHE_TYPE_ASSIGNMENTS).unwrap_or(CacheInfo { id: wanisql::CACHE_TYPE_ASSIGNMENTS, ..Default::default()}), &rate_limit, is_user_restricted);
-    let res = join![subj_future, ass_future];
+    let ass_future = sync_assignments(&conn, &web_config, c_infos.remove(&wanisql::CACHE_TYPE_ASSIGNMENTS).unwrap_or(CacheInfo { id: wanisql::CACHE_TYPE_ASSIGNMENTS, ..Default::default()}), &rate_limit, max_level);
+    println!("Syncing study materials. . .");
+    let study_future = sync_study_materials(conn, web_config, c_infos.remove(&wanisql::CACHE_TYPE_STUDY_MATERIALS).unwrap_or(CacheInfo { id: wanisql::CACHE_TYPE_STUDY_MATERIALS, ..Default::default()}), &rate_limit);
+    let res = join![subj_future, ass_future, study_future];
 
+    for (name, res) in [("subjects", &res.0), ("assignments", &res.1), ("study_materials", &res.2)] {
+        match res {
+            Ok(sync_res) => wanilog::log("sync", &[("type", &name), ("synced", &sync_res.success_count), ("errors", &sync_res.fail_count)]),
+            Err(e) => wanilog::log("sync_failed", &[("type", &name), ("error", e)]),
+        }
+    }
     match res.0 {
         Ok(sync_res) => {
             println!("Synced Subjects: {}, Errors: {}", sync_res.success_count, sync_res.fail_count);
@@ -3156,6 +5115,14 @@ async fn sync_all(p_config: &mut ProgramConfig, web_config: &WaniWebConfig, conn
             eprintln!("Error syncing assignments: {}", e);
         },
     };
+    match res.2 {
+        Ok(sync_res) => {
+            println!("Synced Study Materials: {}, Errors: {}", sync_res.success_count, sync_res.fail_count);
+        },
+        Err(e) => {
+            eprintln!("Error syncing study materials: {}", e);
+        },
+    };
 }
 
 async fn update_cache(last_modified: Option<String>, cache_type: usize, last_request_time: DateTime<Utc>, etag: Option<&HeaderValue>, conn: &AsyncConnection) -> Result<(), tokio_rusqlite::Error> {
@@ -3174,27 +5141,224 @@ async fn update_cache(last_modified: Option<String>, cache_type: usize, last_req
     }).await;
 }
 
-fn command_init(p_config: &ProgramConfig) {
-    let conn = setup_connection(&p_config);
+async fn command_preload(args: &Args) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut p_config = p_config.unwrap();
+
+    let rate_limit = Arc::new(Mutex::new(None));
+    let web_config = get_web_config(&p_config);
+    if let Err(e) = web_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut web_config = web_config.unwrap();
+
+    let image_cache = match get_image_cache(&p_config) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+    let audio_cache = match get_audio_path(&p_config) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+
+    let conn = setup_async_connection(&p_config).await;
     match conn {
         Err(e) => eprintln!("{}", e),
         Ok(c) => {
-            match wanisql::setup_db(&c) {
-                Ok(_) => {},
+            check_online(&mut web_config, &c, &rate_limit).await;
+            if web_config.offline {
+                println!("Preloading needs a connection to WaniKani.");
+                return;
+            }
+
+            let mut ass_cache_info = CacheInfo { id: wanisql::CACHE_TYPE_SUBJECTS, ..Default::default() };
+            if let Ok(mut c_infos) = wanisql::get_all_cache_infos(&c, false).await {
+                if let Some(info) = c_infos.remove(&wanisql::CACHE_TYPE_SUBJECTS) {
+                    ass_cache_info = info;
+                }
+            }
+            println!("Syncing assignments. . .");
+            cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+            let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, get_max_sync_level(&p_config.user, None)).await;
+
+            let mut assignments = match select_data(wanisql::SELECT_LESSON_ASSIGNMENTS, &c, wanisql::parse_assignment, []).await {
+                Ok(a) => a,
                 Err(e) => {
-                    eprintln!("Error setting up SQLite DB: {}", e.to_string())
+                    eprintln!("Error loading assignments. Error: {}", e);
+                    return;
+                },
+            };
+            match select_data(wanisql::SELECT_AVAILABLE_ASSIGNMENTS, &c, wanisql::parse_assignment, [Utc::now().timestamp()]).await {
+                Ok(a) => assignments.extend(a),
+                Err(e) => {
+                    eprintln!("Error loading assignments. Error: {}", e);
+                    return;
                 },
             }
-        },
-    };
-}
-
-fn build_request<'a, T: serde::Serialize + Sized>(info: &RequestInfo<'a, T>, web_config: &WaniWebConfig) -> reqwest::RequestBuilder {
-    let request = match info.method {
-        RequestMethod::Get => web_config.client.get(info.url.clone()),
-        RequestMethod::Post => web_config.client.post(info.url.clone()),
-        RequestMethod::Put => web_config.client.put(info.url.clone()),
-    };
+            let subjects = match get_subjects_for_assignments(&assignments, &c).await {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Error loading subjects. Error: {}", e);
+                    return;
+                },
+            };
+
+            let total = subjects.values()
+                .filter(|s| match s {
+                    Subject::Radical(r) => radical_uses_image(r),
+                    Subject::Vocab(_) | Subject::KanaVocab(_) => true,
+                    Subject::Kanji(_) => false,
+                })
+                .count();
+            if total == 0 {
+                println!("Nothing to preload.");
+                return;
+            }
+
+            let term = Term::stdout();
+            let radical_width = radical_width(&term);
+            let mut fetched = 0;
+            let mut failed = 0;
+            for subject in subjects.values() {
+                let res = match subject {
+                    Subject::Radical(r) if radical_uses_image(r) => {
+                        get_radical_image(r, &image_cache, radical_width, &web_config).await.map(|_| ())
+                    },
+                    Subject::Vocab(v) => preload_audio(v.id, &audio_infos(&v.data.pronunciation_audios), &audio_cache, &web_config, &p_config.audio_formats, p_config.preferred_voice.as_deref()).await,
+                    Subject::KanaVocab(v) => preload_audio(v.id, &audio_infos(&v.data.pronunciation_audios), &audio_cache, &web_config, &p_config.audio_formats, p_config.preferred_voice.as_deref()).await,
+                    _ => continue,
+                };
+                match res {
+                    Ok(_) => fetched += 1,
+                    Err(_) => failed += 1,
+                }
+                let _ = term.clear_line();
+                let _ = term.write_str(&format!("Preloading. . . {}/{}", fetched + failed, total));
+            }
+            let _ = term.clear_line();
+            println!("Preloaded {} of {} items. {} failed.", fetched, total, failed);
+        },
+    }
+}
+
+fn command_backup(args: &Args, dest: &Option<PathBuf>, restore: &Option<PathBuf>) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+    let config_path = get_config_path(args);
+    if let Err(e) = &config_path {
+        eprintln!("{}", e);
+        return;
+    }
+    let config_path = config_path.unwrap();
+
+    let res = match restore {
+        Some(backup_dir) => restore_backup(&p_config, &config_path, backup_dir),
+        None => create_backup(&p_config, &config_path, dest),
+    };
+    if let Err(e) = res {
+        eprintln!("{}", e);
+    }
+}
+
+fn create_backup(p_config: &ProgramConfig, config_path: &Path, dest: &Option<PathBuf>) -> Result<(), WaniError> {
+    let mut backup_dir = match dest {
+        Some(d) => d.clone(),
+        None => {
+            let mut d = p_config.data_path.clone();
+            d.push("backups");
+            d
+        },
+    };
+    backup_dir.push(format!("wani_backup_{}", Local::now().format("%Y%m%d_%H%M%S")));
+    if let Err(e) = fs::create_dir_all(&backup_dir) {
+        return Err(WaniError::Generic(format!("Could not create backup directory at {}\nError: {}", backup_dir.display(), e)));
+    }
+
+    // The online backup API gives a consistent copy even if another wani process has the db open
+    let db_path = get_db_path(p_config)?;
+    if db_path.exists() {
+        let conn = Connection::open(&db_path)?;
+        let mut db_backup = backup_dir.clone();
+        db_backup.push("wani_cache.db");
+        conn.backup(DatabaseName::Main, &db_backup, None)?;
+    }
+    else {
+        println!("No database found at {}. Skipping.", db_path.display());
+    }
+
+    if config_path.exists() {
+        let mut config_backup = backup_dir.clone();
+        config_backup.push(".wani.conf");
+        fs::copy(config_path, &config_backup)?;
+    }
+    else {
+        println!("No config file found at {}. Skipping.", config_path.display());
+    }
+
+    println!("Backup saved to {}", backup_dir.display());
+    Ok(())
+}
+
+fn restore_backup(p_config: &ProgramConfig, config_path: &Path, backup_dir: &Path) -> Result<(), WaniError> {
+    let mut db_backup = backup_dir.to_path_buf();
+    db_backup.push("wani_cache.db");
+    let mut config_backup = backup_dir.to_path_buf();
+    config_backup.push(".wani.conf");
+    if !db_backup.exists() && !config_backup.exists() {
+        return Err(WaniError::Generic(format!("No wani backup found at {}", backup_dir.display())));
+    }
+
+    if db_backup.exists() {
+        let db_path = get_db_path(p_config)?;
+        let mut conn = Connection::open(&db_path)?;
+        conn.restore(DatabaseName::Main, &db_backup, None::<fn(rusqlite::backup::Progress)>)?;
+        println!("Restored database to {}", db_path.display());
+    }
+
+    if config_backup.exists() {
+        fs::copy(&config_backup, config_path)?;
+        println!("Restored config file to {}", config_path.display());
+    }
+
+    Ok(())
+}
+
+fn command_init(p_config: &ProgramConfig) {
+    let conn = setup_connection(&p_config);
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            match wanisql::setup_db(&c) {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!("Error setting up SQLite DB: {}", e.to_string())
+                },
+            }
+        },
+    };
+}
+
+fn build_request<'a, T: serde::Serialize + Sized>(info: &RequestInfo<'a, T>, web_config: &WaniWebConfig) -> reqwest::RequestBuilder {
+    let request = match info.method {
+        RequestMethod::Get => web_config.client.get(info.url.clone()),
+        RequestMethod::Post => web_config.client.post(info.url.clone()),
+        RequestMethod::Put => web_config.client.put(info.url.clone()),
+    };
 
     let mut request = request 
         .header("Wanikani-Revision", &web_config.revision)
@@ -3220,6 +5384,20 @@ fn build_request<'a, T: serde::Serialize + Sized>(info: &RequestInfo<'a, T>, web
 }
 
 async fn send_throttled_request<'a, T: serde::Serialize + Sized>(info: RequestInfo<'a, T>, rate_limit: RateLimitBox, web_config: WaniWebConfig) -> Result<(WaniResp, reqwest::header::HeaderMap), WaniError> {
+    send_throttled_request_checking_token(info, rate_limit, web_config, true).await
+}
+
+/// With check_token, a 401 checks the token once and retries if it's still accepted
+async fn send_throttled_request_checking_token<'a, T: serde::Serialize + Sized>(info: RequestInfo<'a, T>, rate_limit: RateLimitBox, web_config: WaniWebConfig, check_token: bool) -> Result<(WaniResp, reqwest::header::HeaderMap), WaniError> {
+    if web_config.read_only && !matches!(info.method, RequestMethod::Get) {
+        wanilog::log("read_only_blocked", &[("url", &info.url)]);
+        return Err(WaniError::Generic(format!("Read-only mode: not sending changes to WaniKani ({})", info.url)));
+    }
+    if let Some(dir) = &web_config.fixture_dir {
+        return read_fixture_response(&info, dir);
+    }
+
+    let mut revalidated = !check_token;
     loop {
         'wait: loop {
             if let Some(rl) = rate_limit.deref().lock().await.deref() {
@@ -3239,6 +5417,7 @@ async fn send_throttled_request<'a, T: serde::Serialize + Sized>(info: RequestIn
                     }
 
                     eprintln!("Waiting for {} secs.", diff);
+                    wanilog::log("rate_limit_wait", &[("secs", &diff)]);
                     tokio::time::sleep(std::time::Duration::from_secs(diff)).await;
                 }
                 else {
@@ -3251,7 +5430,13 @@ async fn send_throttled_request<'a, T: serde::Serialize + Sized>(info: RequestIn
         }
 
         let request = build_request(&info, &web_config);
-        let res = parse_response(request.send().await).await;
+        let method = match info.method {
+            RequestMethod::Get => "GET",
+            RequestMethod::Post => "POST",
+            RequestMethod::Put => "PUT",
+        };
+        wanilog::log("request", &[("method", &method), ("url", &info.url)]);
+        let res = parse_response(request.send().await, info.keep_raw).await;
         match res {
             Ok((wani, headers, new_rl)) => {
                 // Update with newest rate-limit
@@ -3299,82 +5484,1110 @@ async fn send_throttled_request<'a, T: serde::Serialize + Sized>(info: RequestIn
                             },
                         }
                     }
-                    _ => return Err(e),
+                    // A 401 almost always means a bad token, but check the token once before
+                    // giving up, so a flaky response doesn't fail a whole sync
+                    WaniError::Unauthorized() if !revalidated && token_is_valid(&rate_limit, &web_config).await => {
+                        revalidated = true;
+                        wanilog::log("unauthorized_retry", &[("url", &info.url)]);
+                    },
+                    _ => {
+                        wanilog::log("request_failed", &[("url", &info.url), ("error", &e)]);
+                        return Err(e);
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// fetches the user once to check whether WaniKani still accepts the token
+async fn token_is_valid(rate_limit: &RateLimitBox, web_config: &WaniWebConfig) -> bool {
+    let info = RequestInfo::<()> {
+        url: "https://api.wanikani.com/v2/user".to_owned(),
+        ..Default::default()
+    };
+    // Boxed, since this runs inside a throttled request. A 401 here means the token is bad, so no retry
+    Box::pin(send_throttled_request_checking_token(info, rate_limit.clone(), web_config.clone(), false)).await.is_ok()
+}
+
+/// returns the fixture file for a request, named by its endpoint.
+/// e.g. https://api.wanikani.com/v2/assignments/123/start -> <dir>/assignments_123_start.json
+fn get_fixture_path(url: &str, dir: &Path) -> PathBuf {
+    let endpoint = url.split(['?', '#']).next().unwrap_or_default();
+    let endpoint = endpoint.split_once("/v2/").map_or(endpoint, |(_, e)| e);
+    dir.join(format!("{}.json", endpoint.trim_matches('/').replace('/', "_")))
+}
+
+/// stands in for a network request when running with --fixture-dir
+fn read_fixture_response<T: serde::Serialize + Sized>(info: &RequestInfo<'_, T>, dir: &Path) -> Result<(WaniResp, reqwest::header::HeaderMap), WaniError> {
+    let path = get_fixture_path(&info.url, dir);
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| WaniError::Generic(format!("No fixture for {} at {}: {}", info.url, path.display(), e)))?;
+    let raw = serde_json::from_str::<serde_json::Value>(&text)?;
+    let mut wani = serde_json::from_value::<WaniResp>(raw.clone())?;
+    if info.keep_raw {
+        wani.raw = Some(raw);
+    }
+    Ok((wani, reqwest::header::HeaderMap::new()))
+}
+
+async fn parse_response(response: Result<Response, reqwest::Error>, keep_raw: bool) -> Result<(WaniResp, reqwest::header::HeaderMap, Option<wanidata::RateLimit>), WaniError> {
+    match response {
+        Err(s) => {
+            if s.is_connect() {
+                Err(WaniError::Connection())
+            }
+            else {
+                Err(WaniError::Generic(format!("Error with request: {}", s)))
+            }
+
+        },
+
+        Ok(r) => {
+            wanilog::log("response", &[("url", r.url()), ("status", &r.status())]);
+            match r.status() {
+                StatusCode::OK => {
+                    let headers = r.headers().to_owned();
+                    let ratelimit = wanidata::RateLimit::from(&headers);
+                    if keep_raw {
+                        let raw = r.json::<serde_json::Value>().await;
+                        return match raw {
+                            Err(s) => Err(WaniError::Generic(format!("Error parsing HTTP 200 response: {}", s))),
+                            Ok(raw) => match serde_json::from_value::<WaniResp>(raw.clone()) {
+                                Err(s) => Err(WaniError::Generic(format!("Error parsing HTTP 200 response: {}", s))),
+                                Ok(mut w) => {
+                                    w.raw = Some(raw);
+                                    Ok((w, headers, ratelimit))
+                                },
+                            },
+                        };
+                    }
+                    let wani = r.json::<WaniResp>().await;
+                    match wani {
+                        Err(s) => Err(WaniError::Generic(format!("Error parsing HTTP 200 response: {}", s))),
+                        Ok(w) => {
+                            Ok((w, headers, ratelimit))
+                        },
+                    }
+                },
+                StatusCode::CREATED => {
+                    let headers = r.headers().to_owned();
+                    let ratelimit = wanidata::RateLimit::from(&headers);
+                    let wani = r.json::<WaniResp>().await;
+                    match wani {
+                        Err(s) => Err(WaniError::Generic(format!("Error parsing HTTP 201 response: {}", s))),
+                        Ok(w) => {
+                            Ok((w, headers, ratelimit))
+                        },
+                    }
+                },
+                StatusCode::NOT_MODIFIED => {
+                    let headers = r.headers().to_owned();
+                    let ratelimit = wanidata::RateLimit::from(&headers);
+                    Ok((WaniResp {
+                        data: WaniData::Collection(wanidata::Collection { 
+                            data: vec![],
+                            pages: wanidata::PageData {
+                                next_url: None,
+                            },
+                        }),
+                        resources_updated: None,
+                        raw: None,
+                    }, headers, ratelimit))
+                },
+                StatusCode::UNAUTHORIZED => {
+                    Err(WaniError::Unauthorized())
+                },
+                StatusCode::TOO_MANY_REQUESTS => {
+                    eprintln!("Rate limit hit");
+                    let limit = wanidata::RateLimit::from(r.headers());
+                    if let None = limit {
+                        eprintln!("Expected rate limit but none hit");
+                    }
+                    Err(WaniError::RateLimit(wanidata::RateLimit::from(r.headers())))
+                },
+                StatusCode::UNPROCESSABLE_ENTITY => {
+                    Err(WaniError::Unprocessable())
+                },
+                _ => { Err(WaniError::Generic(format!("HTTP status code {}", r.status()))) },
+            }
+        },
+    }
+}
+
+async fn command_apprentice(args: &Args) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut p_config = p_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            // Refresh assignments if we can, but fall back to the local cache when offline
+            if let Ok(web_config) = get_web_config(&p_config) {
+                let rate_limit = Arc::new(Mutex::new(None));
+                let mut ass_cache_info = CacheInfo { id: wanisql::CACHE_TYPE_ASSIGNMENTS, ..Default::default() };
+                if let Ok(mut c_infos) = wanisql::get_all_cache_infos(&c, false).await {
+                    if let Some(info) = c_infos.remove(&wanisql::CACHE_TYPE_ASSIGNMENTS) {
+                        ass_cache_info = info;
+                    }
+                }
+                cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+                let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, get_max_sync_level(&p_config.user, None)).await;
+            }
+
+            let counts = select_data(wanisql::SELECT_APPRENTICE_COUNTS, &c, wanisql::parse_srs_stage_count, []).await;
+            match counts {
+                Err(e) => eprintln!("Error loading assignments. Error: {}", e),
+                Ok(counts) => {
+                    let mut total = 0;
+                    for stage in 1..=4 {
+                        let count = counts.iter()
+                            .find(|(s, _)| *s == stage)
+                            .map(|(_, c)| *c)
+                            .unwrap_or(0);
+                        total += count;
+                        println!("{}: {}", wanidata::srs_stage_name(stage), count);
+                    }
+                    println!("Total Apprentice: {}", total);
+                },
+            }
+        },
+    }
+}
+
+/// a stand-in assignment for practicing a subject without the user's real one. Practice reviews
+/// are never submitted, so it only needs to be available
+fn practice_assignment(subject_id: i32, subject_type: SubjectType, now: DateTime<Utc>) -> Assignment {
+    Assignment {
+        id: subject_id,
+        data: wanidata::AssignmentData {
+            available_at: Some(now),
+            created_at: now,
+            hidden: false,
+            srs_stage: 0,
+            started_at: None,
+            subject_id,
+            subject_type,
+            unlocked_at: None,
+            burned_at: None,
+        },
+    }
+}
+
+/// returns the audio and image caches for a practice session, and makes Ctrl+C end it
+fn setup_practice_session(p_config: &ProgramConfig, session_name: &'static str) -> Result<(PathBuf, PathBuf), WaniError> {
+    let audio_cache = get_audio_path(p_config)?;
+    let image_cache = get_image_cache(p_config)?;
+    let _ = ctrlc::set_handler(move || {
+        println!("\nreceived Ctrl+C!\nEnding {}...", session_name);
+    });
+    Ok((audio_cache, image_cache))
+}
+
+/// prints how many of the finished items had no wrong answers
+fn print_first_try_summary(reviews: &[NewReview], label: &str) {
+    let done = reviews.iter().filter(|r| matches!(r.status, ReviewStatus::Done)).collect_vec();
+    if done.is_empty() {
+        return;
+    }
+    let first_try = done.iter()
+        .filter(|r| r.incorrect_meaning_answers == 0 && r.incorrect_reading_answers == 0)
+        .count();
+    println!("Got {} of {} {} right on the first try ({}%)", first_try, done.len(), label, first_try * 100 / done.len());
+}
+
+async fn command_retention_check(args: &Args, count: usize) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut p_config = p_config.unwrap();
+
+    let rate_limit = Arc::new(Mutex::new(None));
+    let web_config = get_web_config(&p_config);
+    if let Err(e) = web_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let web_config = web_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let mut ass_cache_info = CacheInfo { id: wanisql::CACHE_TYPE_ASSIGNMENTS, ..Default::default() };
+            if let Ok(mut c_infos) = wanisql::get_all_cache_infos(&c, false).await {
+                if let Some(info) = c_infos.remove(&wanisql::CACHE_TYPE_ASSIGNMENTS) {
+                    ass_cache_info = info;
+                }
+            }
+
+            println!("Syncing assignments. . .");
+            cache_user_info(&mut p_config, &web_config, &c, &rate_limit).await;
+            let _ = sync_assignments(&c, &web_config, ass_cache_info, &rate_limit, get_max_sync_level(&p_config.user, None)).await;
+
+            let burned = select_data(wanisql::SELECT_BURNED_ASSIGNMENTS, &c, wanisql::parse_assignment, []).await;
+            if let Err(e) = burned {
+                eprintln!("Error loading assignments. Error: {}", e);
+                return;
+            }
+            let burned = burned.unwrap();
+
+            let subjects_by_id = get_subjects_for_assignments(&burned, &c).await;
+            if let Err(e) = subjects_by_id {
+                eprintln!("Error loading subjects: {}", e);
+                return;
+            }
+            let subjects_by_id = subjects_by_id.unwrap();
+
+            let mut assignments = burned.choose_multiple(&mut thread_rng(), count)
+                .filter(|a| subjects_by_id.contains_key(&a.data.subject_id))
+                .copied()
+                .collect_vec();
+            if assignments.is_empty() {
+                println!("No burned items to check. You may need to run 'wani sync'");
+                return;
+            }
+
+            let (audio_cache, image_cache) = match setup_practice_session(&p_config, "retention check") {
+                Ok(caches) => caches,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                },
+            };
+
+            let res = do_reviews(&mut assignments, subjects_by_id, audio_cache, &web_config, &p_config, &image_cache, &c, &rate_limit, None, &ReviewArgs { practice: true, ..Default::default() }).await;
+            match res {
+                Err(e) => eprintln!("{}", e),
+                Ok(reviews) => print_first_try_summary(&reviews, "burned items"),
+            }
+        },
+    }
+}
+
+async fn command_import_deck(args: &Args, path: &Path, deck: Option<String>) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            return;
+        },
+    };
+    let subjects = match wanidata::parse_custom_deck(&text) {
+        Ok(s) => s,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}", e);
+            }
+            eprintln!("Nothing was imported. Each line should be: {}", wanidata::CUSTOM_DECK_HEADER);
+            return;
+        },
+    };
+    if subjects.is_empty() {
+        println!("No items found in {}", path.display());
+        return;
+    }
+    let deck = deck.unwrap_or_else(|| path.file_stem().map_or("default".into(), |s| s.to_string_lossy().into_owned()));
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let count = subjects.len();
+            let deck_name = deck.clone();
+            let res = c.call(move |c| {
+                let mut tx = c.transaction()?;
+                tx.execute(wanisql::REMOVE_CUSTOM_DECK, params![deck_name])?;
+                for s in &subjects {
+                    wanisql::store_custom_subject(&deck_name, s, &mut tx)
+                        .map_err(|e| tokio_rusqlite::Error::Other(Box::new(e)))?;
+                }
+                tx.commit()?;
+                Ok(())
+            }).await;
+            match res {
+                Ok(_) => println!("Imported {} items into deck '{}'. Quiz them with: wani cram --deck {}", count, deck, deck),
+                Err(e) => eprintln!("Error importing deck: {}", e),
+            }
+        },
+    }
+}
+
+async fn command_cram(args: &Args, deck: &str, count: Option<usize>) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let rate_limit = Arc::new(Mutex::new(None));
+    // Custom decks have nothing to do with WaniKani, so no token is needed and nothing is sent
+    let client = build_client(&p_config);
+    if let Err(e) = client {
+        eprintln!("{}", e);
+        return;
+    }
+    let web_config = WaniWebConfig {
+        client: client.unwrap(),
+        auth: String::new(),
+        revision: p_config.revision.clone(),
+        fixture_dir: None,
+        offline: true,
+        verbose: p_config.verbose,
+        read_only: p_config.read_only,
+    };
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let deck_name = deck.to_owned();
+            let custom = select_data(wanisql::SELECT_CUSTOM_DECK, &c, wanisql::parse_custom_subject, [deck_name]).await;
+            if let Err(e) = custom {
+                eprintln!("Error loading deck. Error: {}", e);
+                return;
+            }
+            let custom = custom.unwrap();
+            if custom.is_empty() {
+                println!("No deck named '{}'. Add one with 'wani import-deck'", deck);
+                return;
+            }
+
+            let now = Utc::now();
+            let mut subjects_by_id = HashMap::with_capacity(custom.len());
+            let mut assignments = vec![];
+            for s in custom.choose_multiple(&mut thread_rng(), count.unwrap_or(custom.len())) {
+                let subject = s.to_subject();
+                let (subject_id, subject_type) = match &subject {
+                    Subject::KanaVocab(kv) => (kv.id, SubjectType::KanaVocab),
+                    Subject::Vocab(v) => (v.id, SubjectType::Vocab),
+                    _ => continue,
+                };
+                assignments.push(practice_assignment(subject_id, subject_type, now));
+                subjects_by_id.insert(subject_id, subject);
+            }
+
+            let (audio_cache, image_cache) = match setup_practice_session(&p_config, "cram session") {
+                Ok(caches) => caches,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                },
+            };
+
+            let res = do_reviews(&mut assignments, subjects_by_id, audio_cache, &web_config, &p_config, &image_cache, &c, &rate_limit, None, &ReviewArgs { practice: true, ..Default::default() }).await;
+            match res {
+                Err(e) => eprintln!("{}", e),
+                Ok(reviews) => print_first_try_summary(&reviews, "items"),
+            }
+        },
+    }
+}
+
+async fn command_practice(args: &Args, levels: &[i32], ids: &[i32], count: Option<usize>) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let rate_limit = Arc::new(Mutex::new(None));
+    let web_config = get_web_config(&p_config);
+    if let Err(e) = web_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let web_config = web_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let mut subject_ids = ids.to_vec();
+            for &level in levels {
+                match select_data(wanisql::SELECT_SUBJECT_IDS_AT_LEVEL, &c, wanisql::parse_subject_id, [level]).await {
+                    Ok(level_ids) => subject_ids.extend(level_ids),
+                    Err(e) => {
+                        eprintln!("Error loading subjects. Error: {}", e);
+                        return;
+                    },
+                }
+            }
+            let subject_ids = subject_ids.into_iter().unique().collect_vec();
+
+            let subjects = get_subjects_by_ids(subject_ids.clone(), subject_ids.clone(), subject_ids.clone(), subject_ids, &c).await;
+            if let Err(e) = subjects {
+                eprintln!("Error loading subjects: {}", e);
+                return;
+            }
+            let subjects = subjects.unwrap();
+
+            // Practice doesn't need the user's assignments, so every subject gets a stand-in one
+            let now = Utc::now();
+            let all_assignments = subjects.values()
+                .map(|subject| {
+                    let (subject_id, subject_type) = match subject {
+                        Subject::Radical(r) => (r.id, SubjectType::Radical),
+                        Subject::Kanji(k) => (k.id, SubjectType::Kanji),
+                        Subject::Vocab(v) => (v.id, SubjectType::Vocab),
+                        Subject::KanaVocab(kv) => (kv.id, SubjectType::KanaVocab),
+                    };
+                    practice_assignment(subject_id, subject_type, now)
+                })
+                .collect_vec();
+            let mut assignments = all_assignments.choose_multiple(&mut thread_rng(), count.unwrap_or(all_assignments.len()))
+                .copied()
+                .collect_vec();
+            if assignments.is_empty() {
+                println!("No subjects to practice. You may need to run 'wani sync'");
+                return;
+            }
+
+            let (audio_cache, image_cache) = match setup_practice_session(&p_config, "practice session") {
+                Ok(caches) => caches,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                },
+            };
+
+            let res = do_reviews(&mut assignments, subjects, audio_cache, &web_config, &p_config, &image_cache, &c, &rate_limit, None, &ReviewArgs { practice: true, ..Default::default() }).await;
+            match res {
+                Err(e) => eprintln!("{}", e),
+                Ok(reviews) => print_first_try_summary(&reviews, "items"),
+            }
+        },
+    }
+}
+
+async fn command_suspend(args: &Args, subject_id: Option<i32>) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let Some(subject_id) = subject_id else {
+                match select_data(wanisql::SELECT_SUSPENDED, &c, wanisql::parse_suspended, []).await {
+                    Err(e) => eprintln!("Error loading suspended subjects: {}", e),
+                    Ok(suspended) if suspended.is_empty() => println!("No suspended subjects."),
+                    Ok(suspended) => {
+                        println!("Suspended subjects:");
+                        for id in suspended {
+                            println!("{}", id);
+                        }
+                    },
+                }
+                return;
+            };
+
+            let res = c.call(move |c| {
+                c.execute(wanisql::INSERT_SUSPENDED, params![subject_id, Utc::now().to_rfc3339()])?;
+                Ok(())
+            }).await;
+            match res {
+                Err(e) => eprintln!("Error suspending subject: {}", e),
+                Ok(_) => println!("Suspended subject {}. It won't show up in reviews until you run 'wani unsuspend {}'", subject_id, subject_id),
+            }
+        },
+    }
+}
+
+async fn command_unsuspend(args: &Args, subject_id: i32) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let res = c.call(move |c| {
+                Ok(c.execute(wanisql::REMOVE_SUSPENDED, params![subject_id])?)
+            }).await;
+            match res {
+                Err(e) => eprintln!("Error unsuspending subject: {}", e),
+                Ok(0) => println!("Subject {} wasn't suspended.", subject_id),
+                Ok(_) => println!("Unsuspended subject {}.", subject_id),
+            }
+        },
+    }
+}
+
+async fn command_mark_known(args: &Args, subject_id: i32, yes: bool) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+    if p_config.read_only {
+        println!("Can't mark items known in read-only mode.");
+        return;
+    }
+
+    let rate_limit = Arc::new(Mutex::new(None));
+    let web_config = get_web_config(&p_config);
+    if let Err(e) = web_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut web_config = web_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            check_online(&mut web_config, &c, &rate_limit).await;
+            if web_config.offline {
+                println!("Marking an item known needs a connection to WaniKani.");
+                return;
+            }
+
+            let assignment = match select_data(wanisql::SELECT_UNLOCKED_ASSIGNMENT_FOR_SUBJECT, &c, wanisql::parse_assignment, [subject_id]).await {
+                Ok(assignments) => assignments.into_iter().next(),
+                Err(e) => {
+                    eprintln!("Error loading assignment: {}", e);
+                    return;
+                },
+            };
+            let assignment = match assignment {
+                None => {
+                    println!("Subject {} isn't waiting in your lessons. Try 'wani sync' if it was just unlocked.", subject_id);
+                    return;
+                },
+                Some(a) if a.data.started_at.is_some() => {
+                    println!("Subject {} has already been started. Only items waiting in lessons can be marked known.", subject_id);
+                    return;
+                },
+                Some(a) => a,
+            };
+
+            if !yes {
+                println!("This starts subject {} on WaniKani, skipping the lesson. Its first review comes due as usual.", subject_id);
+                println!("It changes your real SRS progress and can't be undone.");
+                print!("Continue? [y/N] ");
+                let _ = io::stdout().flush();
+                let confirmed = Term::stdout().read_line()
+                    .map(|input| matches!(input.trim(), "y" | "Y" | "yes"))
+                    .unwrap_or(false);
+                if !confirmed {
+                    println!("Cancelled.");
+                    return;
+                }
+            }
+
+            let lesson = NewReview {
+                id: None,
+                assignment_id: assignment.id,
+                created_at: Utc::now(),
+                incorrect_meaning_answers: 0,
+                incorrect_reading_answers: 0,
+                status: ReviewStatus::Done,
+                available_at: None,
+                is_lesson: true,
+                srs_stage: None,
+            };
+            if let Err(e) = save_lessons_to_wanikani(std::iter::once(&lesson), &rate_limit, &web_config, &c).await {
+                eprintln!("Error starting lesson: {}", e);
+                return;
+            }
+
+            // The started assignment was stored if WaniKani accepted the start
+            let started = match select_data(wanisql::SELECT_UNLOCKED_ASSIGNMENT_FOR_SUBJECT, &c, wanisql::parse_assignment, [subject_id]).await {
+                Ok(assignments) => assignments.into_iter().next().filter(|a| a.data.started_at.is_some()),
+                Err(_) => None,
+            };
+            match started {
+                Some(_) => println!("Marked subject {} known. It skipped its lesson, and its first review comes due as usual.", subject_id),
+                None => println!("Couldn't start subject {} on WaniKani. It's still in your lessons.", subject_id),
+            }
+        },
+    }
+}
+
+/// records a finished review and its wrong answer counts, for stats and today's progress
+async fn log_review_history(conn: &AsyncConnection, subject_id: i32, correct: bool, incorrect_meaning: u16, incorrect_reading: u16) {
+    let res = conn.call(move |c| {
+        c.execute(wanisql::INSERT_REVIEW_HISTORY, params![subject_id, correct, Utc::now().timestamp(), incorrect_meaning, incorrect_reading])?;
+        Ok(())
+    }).await;
+    if let Err(e) = res {
+        eprintln!("Error saving review history: {}", e);
+    }
+}
+
+/// loads the reviews finished since the start of the local day
+async fn load_today_progress(conn: &AsyncConnection) -> Option<wanidata::TodayProgress> {
+    let start_of_day = get_start_of_local_day(Utc::now()).timestamp();
+    match select_data(wanisql::SELECT_REVIEW_HISTORY_SINCE, conn, wanisql::parse_today_progress, [start_of_day]).await {
+        Ok(progress) => progress.into_iter().next(),
+        Err(e) => {
+            eprintln!("Error loading today's progress: {}", e);
+            None
+        },
+    }
+}
+
+/// records a wrong review answer for the confusions report
+async fn log_wrong_answer(conn: &AsyncConnection, subject_id: i32, is_meaning: bool, guess: String) {
+    let res = conn.call(move |c| {
+        c.execute(wanisql::INSERT_WRONG_ANSWER, params![subject_id, is_meaning, guess, Utc::now().to_rfc3339()])?;
+        Ok(())
+    }).await;
+    if let Err(e) = res {
+        eprintln!("Error logging wrong answer: {}", e);
+    }
+}
+
+async fn command_grade(args: &Args, subject_id: i32, answer: &str, is_meaning: bool, json: bool) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let ids = vec![subject_id];
+            let subject = match get_subjects_by_ids(ids.clone(), ids.clone(), ids.clone(), ids, &c).await {
+                Err(e) => {
+                    eprintln!("Error loading subjects: {}", e);
+                    return;
+                },
+                Ok(mut subjects) => subjects.remove(&subject_id),
+            };
+            let Some(subject) = subject else {
+                eprintln!("No subject with id {}. You may need to run 'wani sync'", subject_id);
+                return;
+            };
+
+            // Same conversions as typing the answer during a review
+            let mut kana_input = answer.to_kana_with_opt(wana_kana::Options { 
+                imemode: true,
+                ..Default::default()
+            });
+            if !is_meaning {
+                if let wanidata::KanaSettle::Settled(k) = wanidata::settle_kana_input(answer) {
+                    kana_input = k;
+                }
+            }
+            let guess = if is_meaning { answer } else { &kana_input }.trim().to_lowercase();
+            let user_synonyms = get_study_material(&c, subject_id).await.map(|s| s.data.meaning_synonyms).unwrap_or_default();
+            let result = wanidata::is_correct_answer(&subject, &guess, is_meaning, &kana_input, &user_synonyms, answer_options(&p_config));
+            if json {
+                println!("{}", serde_json::json!({
+                    "subject_id": subject_id,
+                    "answer": guess,
+                    "type": if is_meaning { "meaning" } else { "reading" },
+                    "result": result.name(),
+                }));
+            }
+            else {
+                println!("{}", result.name());
+            }
+        },
+    }
+}
+
+fn command_version(args: &Args) {
+    println!("wani {}", env!("CARGO_PKG_VERSION"));
+    println!("commit: {}", env!("WANI_GIT_HASH"));
+    match get_program_config(args) {
+        Ok(p_config) => println!("WaniKani API revision: {}", p_config.revision),
+        Err(_) => println!("WaniKani API revision: {} (default)", WANIKANI_REVISION),
+    }
+}
+
+fn command_dump_config(args: &Args) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let path_source = if args.configfile.is_some() {
+        "--configfile"
+    } else if std::env::var("WANI_CONFIG_PATH").is_ok() {
+        "WANI_CONFIG_PATH"
+    } else {
+        "default"
+    };
+    let file_status = if p_config.config_path.exists() { "" } else { ", not found" };
+    println!("Config file: {} (from {}{})", p_config.config_path.display(), path_source, file_status);
+
+    for (key, value, source) in &p_config.config_sources {
+        let value = match p_config.auth.as_ref() {
+            Some(auth) if *key == "auth" => {
+                // Only show enough of the token to tell which one it is
+                let tail = auth.chars().rev().take(4).collect::<String>().chars().rev().collect::<String>();
+                format!("****{}", tail)
+            },
+            _ => value.clone(),
+        };
+        println!("{}: {} ({})", key, value, source);
+    }
+    if let Some(dir) = &p_config.fixture_dir {
+        println!("fixture-dir: {} (command line)", dir.display());
+    }
+
+    if !p_config.unknown_config_keys.is_empty() {
+        println!("Unrecognized keys in config file (ignored): {}", p_config.unknown_config_keys.iter().join(", "));
+    }
+}
+
+fn command_help_keys() {
+    println!("Lesson flashcards:");
+    for hotkey in LESSON_HOTKEYS {
+        println!("  {}", hotkey);
+    }
+    println!();
+    println!("Reviews and lesson quizzes (after answering):");
+    for hotkey in REVIEW_HOTKEYS {
+        println!("  {}", hotkey);
+    }
+}
+
+/// prints a heuristic check of the terminal's support for colors and Japanese text.
+/// Glyph coverage can't be measured, so the sample text has to be checked by eye
+fn command_doctor() {
+    let term = Term::stdout();
+    let mut warnings = 0;
+    let mut report = |ok: bool, label: &str, advice: &str| {
+        if ok {
+            println!("ok       {}", label);
+        }
+        else {
+            warnings += 1;
+            println!("warning  {}", label);
+            println!("         {}", advice);
+        }
+    };
+
+    report(term.is_term(), "Output is a terminal",
+        "wani's reviews and lessons need an interactive terminal. Don't pipe or redirect its output.");
+    let utf8 = wanidata::locale_is_utf8(std::env::var("LC_ALL").ok().as_deref(), std::env::var("LC_CTYPE").ok().as_deref(), std::env::var("LANG").ok().as_deref());
+    report(utf8, "Locale uses UTF-8",
+        "Kana and kanji may not show correctly. Set a UTF-8 locale, like: export LANG=en_US.UTF-8");
+    report(term.features().colors_supported(), "Colors are supported",
+        "Item types and answer results won't be colored. Check that TERM is set, like: export TERM=xterm-256color");
+    let (rows, cols) = term.size();
+    report(cols >= 60 && rows >= 20, &format!("Terminal size is {}x{}", cols, rows),
+        "Info pages may wrap or get cut off. Make the terminal larger, or try collapse_blank_lines: true and review_align: left.");
+
+    println!();
+    println!("Sample text:  日本語  にほんご  ニホンゴ  ⺅ 亻");
+    println!("If any of these show as boxes or question marks, install a Japanese font (like Noto Sans CJK)");
+    println!("and select it in your terminal. Radicals without characters are drawn as images either way,");
+    println!("and wani shows a radical's name instead of characters that common fonts are missing.");
+    println!("If the sample looks small, try large_characters: true.");
+
+    println!();
+    if warnings == 0 {
+        println!("No problems found.");
+    }
+    else {
+        println!("{} possible problem{} found.", warnings, if warnings == 1 { "" } else { "s" });
+    }
+}
+
+async fn command_verify_reviews(args: &Args, remove_recorded: bool) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let web_config = get_web_config(&p_config);
+    if let Err(e) = web_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut web_config = web_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let local = match select_data(wanisql::SELECT_REVIEWS, &c, wanisql::parse_review, []).await {
+                Ok(local) => local,
+                Err(e) => {
+                    eprintln!("Error loading local reviews. Error: {}", e);
+                    return;
+                },
+            };
+            let Some(oldest) = local.iter().map(|r| r.created_at).min() else {
+                println!("No reviews are waiting to be submitted.");
+                return;
+            };
+
+            let rate_limit = Arc::new(Mutex::new(None));
+            check_online(&mut web_config, &c, &rate_limit).await;
+            if web_config.offline {
+                eprintln!("Can't reach WaniKani to compare reviews. Try again when you're online.");
+                return;
+            }
+
+            let server = match get_reviews_since(oldest, &web_config, &rate_limit).await {
+                Ok(server) => server,
+                Err(e) => {
+                    eprintln!("Error fetching reviews from WaniKani. Error: {}", e);
+                    return;
+                },
+            };
+
+            let result = wanidata::reconcile_reviews(&local, &server);
+            println!("Local reviews: {} finished, {} in progress", result.recorded.len() + result.unsubmitted.len(), result.in_progress);
+            println!("Already recorded by WaniKani: {}", result.recorded.len());
+            println!("Not yet recorded by WaniKani: {}", result.unsubmitted.len());
+            if !result.unsubmitted.is_empty() {
+                println!("Finished reviews are submitted the next time you start 'wani review'.");
+            }
+
+            if result.recorded.is_empty() {
+                return;
+            }
+            if !remove_recorded {
+                println!("Run 'wani verify-reviews --remove-recorded' to remove the recorded reviews locally.");
+                return;
+            }
+            let recorded = result.recorded;
+            let removed = c.call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut removed = 0;
+                for id in recorded {
+                    removed += tx.execute(wanisql::REMOVE_REVIEW, params![id])?;
+                }
+                tx.commit()?;
+                Ok(removed)
+            }).await;
+            match removed {
+                Ok(removed) => println!("Removed {} recorded reviews locally.", removed),
+                Err(e) => eprintln!("Error removing recorded reviews. Error: {}", e),
+            }
+        },
+    }
+}
+
+/// fetches the reviews WaniKani recorded since the given time
+async fn get_reviews_since(since: DateTime<Utc>, web_config: &WaniWebConfig, rate_limit: &RateLimitBox) -> Result<Vec<wanidata::Review>, WaniError> {
+    let mut next_url = Some("https://api.wanikani.com/v2/reviews".to_owned());
+    let updated_after = since.to_rfc3339();
+    let mut reviews = vec![];
+    while let Some(url) = next_url {
+        let info = RequestInfo::<()> {
+            url,
+            method: RequestMethod::Get,
+            query: Some(vec![("updated_after", &updated_after)]),
+            ..Default::default()
+        };
+
+        let (wr, _) = send_throttled_request(info, rate_limit.clone(), web_config.clone()).await?;
+        next_url = None;
+        if let WaniData::Collection(c) = wr.data {
+            next_url = c.pages.next_url;
+            for wd in c.data {
+                if let WaniData::Review(r) = wd {
+                    reviews.push(r);
                 }
             }
         }
     }
+    Ok(reviews)
 }
 
-async fn parse_response(response: Result<Response, reqwest::Error>) -> Result<(WaniResp, reqwest::header::HeaderMap, Option<wanidata::RateLimit>), WaniError> {
-    match response {
-        Err(s) => {
-            if s.is_connect() {
-                Err(WaniError::Connection())
-            }
-            else {
-                Err(WaniError::Generic(format!("Error with request: {}", s)))
-            }
-
-        },
+async fn command_tree(args: &Args, subject_id: i32) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
 
-        Ok(r) => {
-            match r.status() {
-                StatusCode::OK => {
-                    let headers = r.headers().to_owned();
-                    let ratelimit = wanidata::RateLimit::from(&headers);
-                    let wani = r.json::<WaniResp>().await;
-                    match wani {
-                        Err(s) => Err(WaniError::Generic(format!("Error parsing HTTP 200 response: {}", s))),
-                        Ok(w) => {
-                            Ok((w, headers, ratelimit))
-                        },
-                    }
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            let ids = vec![subject_id];
+            let subject = match get_subjects_by_ids(ids.clone(), ids.clone(), ids.clone(), ids, &c).await {
+                Err(e) => {
+                    eprintln!("Error loading subjects: {}", e);
+                    return;
                 },
-                StatusCode::CREATED => {
-                    let headers = r.headers().to_owned();
-                    let ratelimit = wanidata::RateLimit::from(&headers);
-                    let wani = r.json::<WaniResp>().await;
-                    match wani {
-                        Err(s) => Err(WaniError::Generic(format!("Error parsing HTTP 201 response: {}", s))),
-                        Ok(w) => {
-                            Ok((w, headers, ratelimit))
-                        },
-                    }
+                Ok(mut subjects) => subjects.remove(&subject_id),
+            };
+            let Some(subject) = subject else {
+                eprintln!("No subject with id {}. You may need to run 'wani sync'", subject_id);
+                return;
+            };
+
+            let radical_label = |r: &wanidata::Radical| format!("{} {}", r.data.characters.as_deref().unwrap_or(&r.data.slug), r.primary_meanings().join(", "));
+            let kanji_label = |k: &wanidata::Kanji| format!("{} {}", k.data.characters, k.primary_meanings().join(", "));
+            let vocab_label = |v: &wanidata::Vocab| format!("{} {}", v.data.characters, v.primary_meanings().join(", "));
+            let (root, groups) = match &subject {
+                Subject::Radical(r) => {
+                    let kanji = lookup_kanji(&c, r.data.amalgamation_subject_ids.clone()).await;
+                    (radical_label(r), vec![("Kanji", kanji.map(|k| k.iter().map(kanji_label).collect_vec()))])
                 },
-                StatusCode::NOT_MODIFIED => {
-                    let headers = r.headers().to_owned();
-                    let ratelimit = wanidata::RateLimit::from(&headers);
-                    Ok((WaniResp {
-                        data: WaniData::Collection(wanidata::Collection { 
-                            data: vec![],
-                            pages: wanidata::PageData {
-                                next_url: None,
-                            },
-                        }),
-                        resources_updated: None,
-                    }, headers, ratelimit))
+                Subject::Kanji(k) => {
+                    let radicals = lookup_radical(&c, k.data.component_subject_ids.clone()).await;
+                    let vocab = lookup_vocab(&c, k.data.amalgamation_subject_ids.clone()).await;
+                    (kanji_label(k), vec![
+                        ("Radicals", radicals.map(|r| r.iter().map(radical_label).collect_vec())),
+                        ("Vocab", vocab.map(|v| v.iter().map(vocab_label).collect_vec())),
+                    ])
                 },
-                StatusCode::UNAUTHORIZED => {
-                    Err(WaniError::Generic(format!("HTTP 401: Unauthorized. Make sure your wanikani auth token is correct, and hasn't been expired.")))
+                Subject::Vocab(v) => {
+                    let kanji = lookup_kanji(&c, v.data.component_subject_ids.clone()).await;
+                    (vocab_label(v), vec![("Kanji", kanji.map(|k| k.iter().map(kanji_label).collect_vec()))])
                 },
-                StatusCode::TOO_MANY_REQUESTS => {
-                    eprintln!("Rate limit hit");
-                    let limit = wanidata::RateLimit::from(r.headers());
-                    if let None = limit {
-                        eprintln!("Expected rate limit but none hit");
+                Subject::KanaVocab(kv) => (format!("{} {}", kv.data.characters, kv.primary_meanings().join(", ")), vec![]),
+            };
+
+            let mut found = vec![];
+            for (label, items) in groups {
+                match items {
+                    Ok(items) => found.push((label, items)),
+                    Err(e) => eprintln!("Error looking up {}: {}", label.to_lowercase(), e),
+                }
+            }
+            for line in wanidata::tree_lines(&root, &found) {
+                println!("{}", line);
+            }
+            if found.iter().all(|(_, items)| items.is_empty()) {
+                println!("No related items.");
+            }
+        },
+    }
+}
+
+async fn command_confusions(args: &Args, count: usize) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    match conn {
+        Err(e) => eprintln!("{}", e),
+        Ok(c) => {
+            match select_data(wanisql::SELECT_CONFUSIONS, &c, wanisql::parse_confusion, [count]).await {
+                Err(e) => eprintln!("Error loading confusions. Error: {}", e),
+                Ok(confusions) => {
+                    if confusions.is_empty() {
+                        println!("No confusions yet. Wrong answers are recorded as you review.");
+                        return;
+                    }
+                    for c in confusions {
+                        println!("{}", c);
                     }
-                    Err(WaniError::RateLimit(wanidata::RateLimit::from(r.headers())))
-                },
-                StatusCode::UNPROCESSABLE_ENTITY => {
-                    Err(WaniError::Unprocessable())
                 },
-                _ => { Err(WaniError::Generic(format!("HTTP status code {}", r.status()))) },
             }
         },
     }
 }
 
+async fn command_stats(args: &Args, period: wanidata::StatsPeriod, from: Option<chrono::NaiveDate>, to: Option<chrono::NaiveDate>) {
+    let p_config = get_program_config(args);
+    if let Err(e) = &p_config {
+        eprintln!("{}", e);
+        return;
+    }
+    let p_config = p_config.unwrap();
+
+    let conn = setup_async_connection(&p_config).await;
+    let c = match conn {
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+        Ok(c) => c,
+    };
+
+    let (first, last) = wanidata::stats_window(period, from, to, Local::now());
+    let totals = match select_data(wanisql::SELECT_REVIEW_HISTORY_TOTALS, &c, wanisql::parse_accuracy_totals, [first, last]).await {
+        Err(e) => {
+            eprintln!("Error loading review history. Error: {}", e);
+            return;
+        },
+        Ok(totals) => totals.into_iter().next().unwrap_or_default(),
+    };
+    let scoped = from.is_some() || to.is_some() || period != wanidata::StatsPeriod::All;
+    if totals.reviews == 0 {
+        if scoped {
+            println!("No review history in this period.");
+        }
+        else {
+            println!("No review history yet. Reviews are recorded as you finish them in wani.");
+        }
+        return;
+    }
+
+    match (from, to, period) {
+        (None, None, wanidata::StatsPeriod::All) => {},
+        (None, None, wanidata::StatsPeriod::Days(days)) => println!("Last {} days", days),
+        (from, to, _) => {
+            let day = |d: Option<chrono::NaiveDate>| d.map(|d| d.to_string());
+            let start = day(from).unwrap_or_else(|| DateTime::from_timestamp(first, 0).map_or("the start".to_owned(), |t| t.with_timezone(&Local).date_naive().to_string()));
+            println!("From {} to {}", start, day(to).unwrap_or("today".to_owned()));
+        },
+    }
+
+    println!("Reviews: {}", totals.reviews);
+    println!("Correct on the first try: {}", wanidata::format_percent(totals.correct, totals.reviews));
+    println!("Meaning misses: {}", wanidata::format_percent(totals.meaning_misses, totals.meaning_reviews));
+    println!("Reading misses: {}", wanidata::format_percent(totals.reading_misses, totals.reading_reviews));
+
+    let by_level = select_data(wanisql::SELECT_REVIEW_HISTORY_BY_LEVEL, &c, wanisql::parse_accuracy_group, [first, last]).await;
+    let by_stage = select_data(wanisql::SELECT_REVIEW_HISTORY_BY_SRS_STAGE, &c, wanisql::parse_accuracy_group, [first, last]).await;
+    for (title, groups) in [("By level:", by_level), ("By current SRS stage:", by_stage)] {
+        match groups {
+            Err(e) => eprintln!("Error loading review history. Error: {}", e),
+            // Subjects and assignments that haven't been synced can't be grouped
+            Ok(groups) if groups.is_empty() => {},
+            Ok(groups) => {
+                println!();
+                println!("{}", title);
+                for g in groups {
+                    let name = if title == "By level:" { format!("Level {}", g.key) } else { wanidata::srs_stage_name(g.key).to_owned() };
+                    println!("  {:<14} {:>6} reviews  {:>6} correct", name, g.reviews, wanidata::format_percent(g.correct, g.reviews));
+                }
+            },
+        }
+    }
+}
+
 async fn command_summary(args: &Args) {
     let p_config = get_program_config(args);
     if let Err(e) = &p_config {
@@ -3382,6 +6595,12 @@ async fn command_summary(args: &Args) {
         return;
     }
     let p_config = p_config.unwrap();
+    if p_config.repeat_last_command {
+        // The summary doesn't use the database otherwise
+        if let Ok(c) = setup_async_connection(&p_config).await {
+            remember_last_command(args, &p_config, &c).await;
+        }
+    }
     let web_config = get_web_config(&p_config);
     if let Err(e) = web_config {
         eprintln!("{}", e);
@@ -3437,6 +6656,35 @@ fn test_handle_wani_resp(w: WaniResp) -> () {
     }
 }
 
+/// prints how long until the next review is available, if any are scheduled
+async fn print_next_review_time(c: &AsyncConnection, now: DateTime<Utc>) {
+    let next = select_data(wanisql::SELECT_NEXT_AVAILABLE_AT, c, wanisql::parse_next_available_at, [now.timestamp()]).await;
+    let Ok(Some(Some(next))) = next.map(|n| n.into_iter().next()) else {
+        return;
+    };
+    let Some(next) = DateTime::<Utc>::from_timestamp(next, 0) else {
+        return;
+    };
+
+    let local = next.with_timezone(&Local);
+    let at = if local.date_naive() == now.with_timezone(&Local).date_naive() {
+        local.format("%H:%M").to_string()
+    } else {
+        local.format("%a %b %e %H:%M").to_string()
+    };
+    println!("Next reviews available in {} (at {}).", wanidata::format_wait((next - now).num_seconds()), at);
+}
+
+/// returns the start (midnight) of the local day containing `time`
+fn get_start_of_local_day(time: DateTime<Utc>) -> DateTime<Utc> {
+    let local = time.with_timezone(&Local);
+    match local.date_naive().and_hms_opt(0, 0, 0).map(|m| m.and_local_timezone(Local)) {
+        Some(chrono::LocalResult::Single(t)) => t.with_timezone(&Utc),
+        Some(chrono::LocalResult::Ambiguous(t, _)) => t.with_timezone(&Utc),
+        _ => time,
+    }
+}
+
 fn get_image_cache(p_config: &ProgramConfig) -> Result<PathBuf, WaniError> {
     let mut db_path = get_db_path(p_config)?;
     db_path.pop();
@@ -3482,9 +6730,7 @@ fn get_db_path(p_config: &ProgramConfig) -> Result<PathBuf, WaniError> {
 
 async fn setup_async_connection(p_config: &ProgramConfig) -> Result<AsyncConnection, WaniError> {
     let path = get_db_path(p_config)?;
-    if !path.exists() {
-        let _ = setup_connection(p_config);
-    }
+    setup_connection(p_config)?;
     let res = AsyncConnection::open(&path).await;
     Ok(res?)
 }
@@ -3502,13 +6748,17 @@ fn setup_connection(p_config: &ProgramConfig) -> Result<Connection, WaniError> {
                     },
                 }
             }
+            else if let Err(e) = wanisql::migrate_db(&c) {
+                return Err(WaniError::Generic(format!("Error migrating SQLite DB: {}", e)));
+            }
             Ok(c)
         },
         Err(e) => Err(WaniError::Generic(format!("{}", e))),
     }
 }
 
-fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
+/// returns the path of the .wani.conf config file, creating its containing folder if needed
+fn get_config_path(args: &Args) -> Result<PathBuf, WaniError> {
     let mut configpath = PathBuf::new();
     if let Some(path) = &args.configfile {
         configpath.push(path);
@@ -3536,17 +6786,80 @@ fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
         }
     }
     configpath.push(".wani.conf");
+    Ok(configpath)
+}
+
+/// parses a config value like "5,30"
+fn parse_usize_pair(s: &str) -> Option<(usize, usize)> {
+    let (a, b) = s.split_once(',')?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
+    let configpath = get_config_path(args)?;
+    let mut file_keys = HashSet::new();
 
     let mut auth = None;
     let mut colorblind = false;
     let mut datapath = None;
+    let mut default_command = Command::Summary;
+    let mut repeat_last_command = false;
+    let mut review_order = ReviewOrder::Random;
+    let mut shuffle = ShuffleMode::Item;
+    let mut audio_formats = vec!["audio/mpeg".to_owned(), "audio/ogg".to_owned(), "audio/webm".to_owned()];
+    let mut preferred_voice = None;
+    let mut volume = 1.0;
+    let mut no_audio = args.no_audio;
+    let mut confirm_lesson_quit = true;
+    let mut primary_only = false;
+    let mut fold_diacritics = false;
+    let mut fuzzy = wanidata::FuzzyMode::Normal;
+    let mut min_answer_ms = None;
+    let mut answer_timeout_secs = None;
+    let mut break_reminder_minutes = None;
+    let mut max_session_minutes = None;
+    let mut apprentice_ceiling = None;
+    let mut large_session_prompt = None;
+    let mut log_file = None;
+    let mut on_complete_command = None;
+    let mut accuracy = AccuracyMetric::Answers;
+    let mut focus_mode = false;
+    let mut alternate_screen = false;
+    let mut min_terminal_size = Some((40, 10));
+    let mut accuracy_precision = wanidata::PercentPrecision::Truncate;
+    let mut empty_enter = EmptyEnter::Ignore;
+    let mut fuzzy_correct_color = AnswerColor::Yellow;
+    let mut review_align = console::Alignment::Center;
+    let mut remember_half = false;
+    let mut pair_kanji_halves = false;
+    let mut persistent_toasts = false;
+    let mut lesson_preview = false;
+    let mut lesson_flow = LessonFlow::Batched;
+    let mut lesson_batch_size = 5;
+    let mut review_batch_size = 20;
+    let mut notify_on = NotifyOn::Never;
+    let mut adaptive_batch_sizes = None;
+    let mut adaptive_batch_accuracy = (75, 90);
+    let mut auto_submit_seconds = None;
+    let mut alt_readings = AltReadings::Accept;
+    let mut collapse_blank_lines = true;
+    let mut show_romaji = false;
+    let mut show_today_progress = false;
+    let mut large_characters = false;
+    let mut blind_input = false;
+    let mut proxy = None;
+    let mut danger_accept_invalid_certs = false;
+    let mut revision = WANIKANI_REVISION.to_owned();
     if let Ok(lines) = read_lines(&configpath) {
         for line in lines {
             if let Ok(s) = line {
-                let words = s.split(" ").collect::<Vec<&str>>();
+                let words = wanidata::clean_config_line(&s).split(" ").collect::<Vec<&str>>();
                 if words.len() < 2 {
                     continue;
                 }
+                if let Some(key) = words[0].strip_suffix(':') {
+                    file_keys.insert(key.to_owned());
+                }
 
                 match words[0] {
                     "auth:" => {
@@ -3564,7 +6877,257 @@ fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
                             return Err(WaniError::Generic(format!("Could not parse datapath from config file. Path: {}", words[1])));
                         }
                         datapath = Some(path.unwrap());
-                    }
+                    },
+                    "accuracy:" => {
+                        accuracy = match words[1] {
+                            "answers" => AccuracyMetric::Answers,
+                            "items" => AccuracyMetric::Items,
+                            _ => return Err(WaniError::Generic(format!("Unknown accuracy in config file: {}. Expected answers or items.", words[1]))),
+                        };
+                    },
+                    "accuracy_precision:" => {
+                        accuracy_precision = match words[1] {
+                            "truncate" => wanidata::PercentPrecision::Truncate,
+                            "round" => wanidata::PercentPrecision::Round,
+                            "decimal" => wanidata::PercentPrecision::OneDecimal,
+                            _ => return Err(WaniError::Generic(format!("Unknown accuracy_precision in config file: {}. Expected truncate, round, or decimal.", words[1]))),
+                        };
+                    },
+                    "alternate_screen:" => {
+                        alternate_screen = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "min_terminal_size:" => {
+                        min_terminal_size = wanidata::parse_terminal_size(words[1]).map_err(WaniError::Generic)?;
+                    },
+                    "focus_mode:" => {
+                        focus_mode = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "on_complete_command:" => {
+                        // The command is the rest of the line, spaces and all
+                        on_complete_command = Some(words[1..].join(" "));
+                    },
+                    "log_file:" => {
+                        match PathBuf::from_str(words[1]) {
+                            Ok(path) => log_file = Some(path),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse log_file from config file. Path: {}", words[1]))),
+                        }
+                    },
+                    "default_command:" => {
+                        default_command = match parse_default_command(words[1]) {
+                            Some(c) => c,
+                            None => return Err(WaniError::Generic(format!("Unknown default_command in config file: {}. Expected summary, review, or lesson.", words[1]))),
+                        };
+                    },
+                    "repeat_last_command:" => {
+                        repeat_last_command = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "review_order:" => {
+                        review_order = match words[1] {
+                            "random" => ReviewOrder::Random,
+                            "overdue_first" => ReviewOrder::OverdueFirst,
+                            _ => return Err(WaniError::Generic(format!("Unknown review_order in config file: {}. Expected random or overdue_first.", words[1]))),
+                        };
+                    },
+                    "shuffle:" => {
+                        shuffle = match words[1] {
+                            "item" => ShuffleMode::Item,
+                            "batch" => ShuffleMode::Batch,
+                            _ => return Err(WaniError::Generic(format!("Unknown shuffle in config file: {}. Expected item or batch.", words[1]))),
+                        };
+                    },
+                    "audio_formats:" => {
+                        audio_formats = words[1].split(',')
+                            .filter(|f| !f.is_empty())
+                            .map(|f| if f.contains('/') { f.to_owned() } else { format!("audio/{}", f) })
+                            .collect_vec();
+                    },
+                    "preferred_voice:" => {
+                        preferred_voice = Some(words[1].to_owned());
+                    },
+                    "volume:" => {
+                        volume = match words[1].parse::<f32>() {
+                            Ok(v) if v.is_finite() => v.clamp(0.0, 1.0),
+                            _ => return Err(WaniError::Generic(format!("Could not parse volume from config file: {}. Expected a number from 0.0 to 1.0.", words[1]))),
+                        };
+                    },
+                    "no_audio:" => {
+                        no_audio = args.no_audio || matches!(words[1], "true" | "True" | "t");
+                    },
+                    "confirm_lesson_quit:" => {
+                        confirm_lesson_quit = !matches!(words[1], "false" | "False" | "f");
+                    },
+                    "primary_only:" => {
+                        primary_only = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "fold_diacritics:" => {
+                        fold_diacritics = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "fuzzy:" => {
+                        fuzzy = match words[1] {
+                            "off" => wanidata::FuzzyMode::Off,
+                            "normal" => wanidata::FuzzyMode::Normal,
+                            "lenient" => wanidata::FuzzyMode::Lenient,
+                            _ => return Err(WaniError::Generic(format!("Unknown fuzzy in config file: {}. Expected off, normal, or lenient.", words[1]))),
+                        };
+                    },
+                    "min_answer_ms:" => {
+                        min_answer_ms = match words[1].parse::<u64>() {
+                            Ok(0) => None,
+                            Ok(ms) => Some(ms),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse min_answer_ms from config file: {}", words[1]))),
+                        };
+                    },
+                    "break_reminder_minutes:" => {
+                        break_reminder_minutes = match words[1].parse::<u64>() {
+                            Ok(0) => None,
+                            Ok(m) => Some(m),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse break_reminder_minutes from config file: {}", words[1]))),
+                        };
+                    },
+                    "answer_timeout_secs:" => {
+                        answer_timeout_secs = match words[1].parse::<u64>() {
+                            Ok(0) => None,
+                            Ok(s) => Some(s),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse answer_timeout_secs from config file: {}", words[1]))),
+                        };
+                    },
+                    "max_session_minutes:" => {
+                        max_session_minutes = match words[1].parse::<u64>() {
+                            Ok(0) => None,
+                            Ok(m) => Some(m),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse max_session_minutes from config file: {}", words[1]))),
+                        };
+                    },
+                    "apprentice_ceiling:" => {
+                        apprentice_ceiling = match words[1].parse::<usize>() {
+                            Ok(0) => None,
+                            Ok(c) => Some(c),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse apprentice_ceiling from config file: {}", words[1]))),
+                        };
+                    },
+                    "large_session_prompt:" => {
+                        large_session_prompt = match words[1].parse::<usize>() {
+                            Ok(0) => None,
+                            Ok(c) => Some(c),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse large_session_prompt from config file: {}", words[1]))),
+                        };
+                    },
+                    "empty_enter:" => {
+                        empty_enter = match words[1] {
+                            "ignore" => EmptyEnter::Ignore,
+                            "reveal" => EmptyEnter::Reveal,
+                            "beep" => EmptyEnter::Beep,
+                            _ => return Err(WaniError::Generic(format!("Unknown empty_enter in config file: {}. Expected ignore, reveal, or beep.", words[1]))),
+                        };
+                    },
+                    "fuzzy_correct_color:" => {
+                        fuzzy_correct_color = match words[1] {
+                            "yellow" => AnswerColor::Yellow,
+                            "green" => AnswerColor::Green,
+                            _ => return Err(WaniError::Generic(format!("Unknown fuzzy_correct_color in config file: {}. Expected yellow or green.", words[1]))),
+                        };
+                    },
+                    "review_align:" => {
+                        review_align = match words[1] {
+                            "left" => console::Alignment::Left,
+                            "center" => console::Alignment::Center,
+                            "right" => console::Alignment::Right,
+                            _ => return Err(WaniError::Generic(format!("Unknown review_align in config file: {}. Expected left, center, or right.", words[1]))),
+                        };
+                    },
+                    "remember_half:" => {
+                        remember_half = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "pair_kanji_halves:" => {
+                        pair_kanji_halves = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "persistent_toasts:" => {
+                        persistent_toasts = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "lesson_preview:" => {
+                        lesson_preview = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "lesson_batch_size:" => {
+                        lesson_batch_size = match words[1].parse::<usize>() {
+                            Ok(n) => n,
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse lesson_batch_size from config file: {}", words[1]))),
+                        };
+                    },
+                    "notify_on:" => {
+                        notify_on = match words[1] {
+                            "never" => NotifyOn::Never,
+                            "incorrect" => NotifyOn::Incorrect,
+                            "always" => NotifyOn::Always,
+                            _ => return Err(WaniError::Generic(format!("Unknown notify_on in config file: {}. Expected never, incorrect, or always.", words[1]))),
+                        };
+                    },
+                    "lesson_flow:" => {
+                        lesson_flow = match words[1] {
+                            "batched" => LessonFlow::Batched,
+                            "all_cards_first" => LessonFlow::AllCardsFirst,
+                            _ => return Err(WaniError::Generic(format!("Unknown lesson_flow in config file: {}. Expected batched or all_cards_first.", words[1]))),
+                        };
+                    },
+                    "review_batch_size:" => {
+                        review_batch_size = match words[1].parse::<usize>() {
+                            Ok(n) => n,
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse review_batch_size from config file: {}", words[1]))),
+                        };
+                    },
+                    "adaptive_batch:" => {
+                        adaptive_batch_sizes = match parse_usize_pair(words[1]) {
+                            Some((min_size, max_size)) if 0 < min_size && min_size <= max_size => Some((min_size, max_size)),
+                            _ => return Err(WaniError::Generic(format!("Could not parse adaptive_batch from config file: {}. Expected min,max batch sizes, like 5,30", words[1]))),
+                        };
+                    },
+                    "adaptive_batch_accuracy:" => {
+                        adaptive_batch_accuracy = match parse_usize_pair(words[1]) {
+                            Some((low, high)) if low <= high && high <= 100 => (low, high),
+                            _ => return Err(WaniError::Generic(format!("Could not parse adaptive_batch_accuracy from config file: {}. Expected low,high percents, like 75,90", words[1]))),
+                        };
+                    },
+                    "auto_submit_seconds:" => {
+                        auto_submit_seconds = match words[1].parse::<u64>() {
+                            Ok(0) => None,
+                            Ok(s) => Some(s),
+                            Err(_) => return Err(WaniError::Generic(format!("Could not parse auto_submit_seconds from config file: {}", words[1]))),
+                        };
+                    },
+                    "alt_readings:" => {
+                        alt_readings = match words[1] {
+                            "accept" => AltReadings::Accept,
+                            "note" => AltReadings::Note,
+                            "practice_strict" => AltReadings::PracticeStrict,
+                            _ => return Err(WaniError::Generic(format!("Unknown alt_readings in config file: {}. Expected accept, note, or practice_strict.", words[1]))),
+                        };
+                    },
+                    "collapse_blank_lines:" => {
+                        collapse_blank_lines = !matches!(words[1], "false" | "False" | "f");
+                    },
+                    "show_romaji:" => {
+                        show_romaji = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "show_today_progress:" => {
+                        show_today_progress = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "large_characters:" => {
+                        large_characters = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "blind_input:" => {
+                        blind_input = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "proxy_url:" => {
+                        proxy = match reqwest::Proxy::all(words[1]) {
+                            Ok(p) => Some((p, words[1].to_owned())),
+                            Err(e) => return Err(WaniError::Generic(format!("Could not parse proxy_url from config file: {}. Error: {}", words[1], e))),
+                        };
+                    },
+                    "danger_accept_invalid_certs:" => {
+                        danger_accept_invalid_certs = matches!(words[1], "true" | "True" | "t");
+                    },
+                    "revision:" => {
+                        revision = wanidata::parse_api_revision(words[1]).map_err(WaniError::Generic)?;
+                    },
                     _ => {},
                 }
             }
@@ -3574,6 +7137,12 @@ fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
     if let Some(a) = &args.auth {
         auth = Some(String::from(a));
     }
+    if let Some(r) = &args.revision {
+        revision = r.clone();
+    }
+    if let Some(n) = args.lesson_batch_size {
+        lesson_batch_size = n;
+    }
 
     let datapath = if let Some(dpath) = &args.datapath {
         dpath.clone()
@@ -3595,10 +7164,159 @@ fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
         }
     };
 
+    let adaptive_batch = adaptive_batch_sizes.map(|(min_size, max_size)| wanidata::AdaptiveBatch {
+        min_size,
+        max_size,
+        low_accuracy: adaptive_batch_accuracy.0,
+        high_accuracy: adaptive_batch_accuracy.1,
+    });
+
+    // The resolved values, after parsing, clamping, and command line overrides
+    let not_set = || "(not set)".to_owned();
+    let resolved: [(&'static str, String, bool); 51] = [
+        ("auth", auth.clone().unwrap_or_else(not_set), args.auth.is_some()),
+        ("colorblind", colorblind.to_string(), false),
+        ("datapath", datapath.display().to_string(), args.datapath.is_some()),
+        ("default_command", repeatable_command_name(&default_command).unwrap_or("summary").to_owned(), false),
+        ("repeat_last_command", repeat_last_command.to_string(), false),
+        ("review_order", match review_order { ReviewOrder::Random => "random", ReviewOrder::OverdueFirst => "overdue_first" }.to_owned(), false),
+        ("shuffle", match shuffle { ShuffleMode::Item => "item", ShuffleMode::Batch => "batch" }.to_owned(), false),
+        ("audio_formats", audio_formats.join(","), false),
+        ("preferred_voice", preferred_voice.clone().unwrap_or_else(not_set), false),
+        ("volume", volume.to_string(), false),
+        ("no_audio", no_audio.to_string(), args.no_audio),
+        ("confirm_lesson_quit", confirm_lesson_quit.to_string(), false),
+        ("primary_only", primary_only.to_string(), false),
+        ("fold_diacritics", fold_diacritics.to_string(), false),
+        ("fuzzy", match fuzzy { wanidata::FuzzyMode::Off => "off", wanidata::FuzzyMode::Normal => "normal", wanidata::FuzzyMode::Lenient => "lenient" }.to_owned(), false),
+        ("min_answer_ms", min_answer_ms.unwrap_or(0).to_string(), false),
+        ("answer_timeout_secs", answer_timeout_secs.unwrap_or(0).to_string(), false),
+        ("break_reminder_minutes", break_reminder_minutes.unwrap_or(0).to_string(), false),
+        ("max_session_minutes", max_session_minutes.unwrap_or(0).to_string(), false),
+        ("apprentice_ceiling", apprentice_ceiling.unwrap_or(0).to_string(), false),
+        ("large_session_prompt", large_session_prompt.unwrap_or(0).to_string(), false),
+        ("log_file", log_file.as_ref().map_or_else(not_set, |p| p.display().to_string()), false),
+        ("revision", revision.clone(), args.revision.is_some()),
+        ("on_complete_command", on_complete_command.clone().unwrap_or_else(not_set), false),
+        ("accuracy", match accuracy { AccuracyMetric::Answers => "answers", AccuracyMetric::Items => "items" }.to_owned(), false),
+        ("focus_mode", focus_mode.to_string(), false),
+        ("alternate_screen", alternate_screen.to_string(), false),
+        ("min_terminal_size", min_terminal_size.map_or_else(|| "off".to_owned(), |(w, h)| format!("{}x{}", w, h)), false),
+        ("accuracy_precision", match accuracy_precision {
+            wanidata::PercentPrecision::Truncate => "truncate",
+            wanidata::PercentPrecision::Round => "round",
+            wanidata::PercentPrecision::OneDecimal => "decimal",
+        }.to_owned(), false),
+        ("empty_enter", match empty_enter { EmptyEnter::Ignore => "ignore", EmptyEnter::Reveal => "reveal", EmptyEnter::Beep => "beep" }.to_owned(), false),
+        ("fuzzy_correct_color", match fuzzy_correct_color { AnswerColor::Green => "green", _ => "yellow" }.to_owned(), false),
+        ("review_align", match review_align {
+            console::Alignment::Left => "left",
+            console::Alignment::Center => "center",
+            console::Alignment::Right => "right",
+        }.to_owned(), false),
+        ("remember_half", remember_half.to_string(), false),
+        ("pair_kanji_halves", pair_kanji_halves.to_string(), false),
+        ("persistent_toasts", persistent_toasts.to_string(), false),
+        ("lesson_preview", lesson_preview.to_string(), false),
+        ("lesson_flow", match lesson_flow { LessonFlow::Batched => "batched", LessonFlow::AllCardsFirst => "all_cards_first" }.to_owned(), false),
+        ("lesson_batch_size", lesson_batch_size.to_string(), args.lesson_batch_size.is_some()),
+        ("review_batch_size", review_batch_size.to_string(), false),
+        ("notify_on", match notify_on { NotifyOn::Never => "never", NotifyOn::Incorrect => "incorrect", NotifyOn::Always => "always" }.to_owned(), false),
+        ("adaptive_batch", adaptive_batch_sizes.map_or_else(not_set, |(min_size, max_size)| format!("{},{}", min_size, max_size)), false),
+        ("adaptive_batch_accuracy", format!("{},{}", adaptive_batch_accuracy.0, adaptive_batch_accuracy.1), false),
+        ("auto_submit_seconds", auto_submit_seconds.unwrap_or(0).to_string(), false),
+        ("alt_readings", match alt_readings {
+            AltReadings::Accept => "accept",
+            AltReadings::Note => "note",
+            AltReadings::PracticeStrict => "practice_strict",
+        }.to_owned(), false),
+        ("collapse_blank_lines", collapse_blank_lines.to_string(), false),
+        ("show_romaji", show_romaji.to_string(), false),
+        ("show_today_progress", show_today_progress.to_string(), false),
+        ("large_characters", large_characters.to_string(), false),
+        ("blind_input", blind_input.to_string(), false),
+        ("proxy_url", proxy.as_ref().map_or_else(not_set, |(_, url)| url.clone()), false),
+        ("danger_accept_invalid_certs", danger_accept_invalid_certs.to_string(), false),
+    ];
+    let unknown_config_keys = file_keys.iter()
+        .filter(|k| !resolved.iter().any(|(key, _, _)| key == k))
+        .cloned()
+        .sorted()
+        .collect_vec();
+    let mut config_sources = resolved.into_iter()
+        .map(|(key, value, from_command_line)| {
+            let source = if from_command_line {
+                ConfigSource::CommandLine
+            } else if file_keys.contains(key) {
+                ConfigSource::ConfigFile
+            } else {
+                ConfigSource::Default
+            };
+            (key, value, source)
+        })
+        .collect_vec();
+    // Only set on the command line
+    for (key, set) in [("verbose", args.verbose), ("read-only", args.read_only)] {
+        config_sources.push((key, set.to_string(), if set { ConfigSource::CommandLine } else { ConfigSource::Default }));
+    }
+
     Ok(ProgramConfig { 
         auth, 
         data_path: datapath,
         colorblind,
+        default_command,
+        repeat_last_command,
+        review_order,
+        shuffle,
+        audio_formats,
+        preferred_voice,
+        volume,
+        no_audio,
+        confirm_lesson_quit,
+        primary_only,
+        fold_diacritics,
+        fuzzy,
+        min_answer_ms,
+        answer_timeout_secs,
+        break_reminder_minutes,
+        max_session_minutes,
+        apprentice_ceiling,
+        large_session_prompt,
+        empty_enter,
+        fuzzy_correct_color,
+        review_align,
+        remember_half,
+        pair_kanji_halves,
+        persistent_toasts,
+        on_complete_command,
+        accuracy,
+        focus_mode,
+        alternate_screen,
+        min_terminal_size,
+        accuracy_precision,
+        lesson_preview,
+        lesson_flow,
+        lesson_batch_size,
+        review_batch_size,
+        notify_on,
+        adaptive_batch,
+        auto_submit_seconds,
+        alt_readings,
+        collapse_blank_lines,
+        show_romaji,
+        show_today_progress,
+        large_characters,
+        blind_input,
+        proxy: proxy.map(|(p, _)| p),
+        danger_accept_invalid_certs,
+        revision,
+        verbose: args.verbose,
+        read_only: args.read_only,
+        log_file,
+        fixture_dir: args.fixture_dir.clone(),
+        config_path: configpath,
+        config_sources,
+        unknown_config_keys,
         user: wanidata::UserData { 
             id: "0".to_owned(), 
             subscription: wanidata::Subscription { max_level_granted: 60, period_ends_at: None }, 
@@ -3607,12 +7325,45 @@ fn get_program_config(args: &Args) -> Result<ProgramConfig, WaniError> {
     })
 }
 
+/// WaniKani API revision sent with every request
+const WANIKANI_REVISION: &str = "20170710";
+
+fn build_client(config: &ProgramConfig) -> Result<Client, WaniError> {
+    let mut builder = Client::builder();
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(proxy.clone());
+    }
+    if config.danger_accept_invalid_certs {
+        // Some commands build more than one client
+        static WARN_ONCE: std::sync::Once = std::sync::Once::new();
+        WARN_ONCE.call_once(|| eprintln!("Warning: TLS certificate checks are off (danger_accept_invalid_certs)"));
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder.build().map_err(|e| WaniError::Generic(format!("Could not set up web client: {}", e)))
+}
+
 fn get_web_config(config: &ProgramConfig) -> Result<WaniWebConfig, WaniError> {
+    if let Some(dir) = &config.fixture_dir {
+        return Ok(WaniWebConfig { 
+            client: build_client(config)?,
+            auth: config.auth.clone().unwrap_or_default(),
+            revision: config.revision.clone(),
+            fixture_dir: Some(dir.clone()),
+            offline: false,
+            verbose: config.verbose,
+            read_only: config.read_only,
+        });
+    }
+
     if let Some(a) = &config.auth {
         return Ok(WaniWebConfig { 
-            client: Client::new(),
+            client: build_client(config)?,
             auth: a.into(),
-            revision: "20170710".to_owned()
+            revision: config.revision.clone(),
+            fixture_dir: None,
+            offline: false,
+            verbose: config.verbose,
+            read_only: config.read_only,
         });
     }
     else {
@@ -3626,12 +7377,23 @@ where P: AsRef<Path>, {
     Ok(io::BufReader::new(file).lines())
 }
 
+/// width of radical images drawn as ascii art, which are cached at that width
+fn radical_width(term: &Term) -> u32 {
+    u32::from(term.size().1 * 5 / 8)
+}
+
+/// Prefer the image when the characters would likely render as tofu
+fn radical_uses_image(radical: &wanidata::Radical) -> bool {
+    !radical.data.characters.as_ref()
+        .is_some_and(|c| radical.data.character_images.is_empty() || wanidata::renders_in_common_fonts(c))
+}
+
 async fn get_chars_for_subj(subject: &wanidata::Subject, image_cache: &PathBuf, radical_width: u32, web_config: &WaniWebConfig) -> Result<Vec<String>, WaniError> {
     Ok(match subject {
         Subject::Radical(r) => { 
             let rad_chars;
-            if let Some(c) = &r.data.characters { 
-                rad_chars = vec![c.to_owned()];
+            if !radical_uses_image(r) { 
+                rad_chars = vec![r.data.characters.clone().unwrap_or_default()];
             } else { 
                 let res = get_radical_image(r, image_cache, radical_width, web_config).await;
                 match res {
@@ -3657,7 +7419,11 @@ async fn get_chars_for_subj(subject: &wanidata::Subject, image_cache: &PathBuf,
                         rad_chars = lines;
                     }
                     Err(e) => {
-                        return Err(e);
+                        if web_config.verbose {
+                            eprintln!("Couldn't show the image for radical {}: {}", r.id, e);
+                        }
+                        // The review screens show the radical's slug or meaning instead
+                        rad_chars = vec![];
                     }
                 }
             };
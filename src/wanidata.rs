@@ -6,7 +6,8 @@ use chrono::{
     DateTime,
     Utc,
 };
-use wana_kana::{IsJapaneseChar, IsJapaneseStr};
+use wana_kana::{ConvertJapanese, IsJapaneseChar, IsJapaneseStr};
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
 
 /// models a successful response from the WaniKani api
 ///
@@ -17,6 +18,9 @@ pub struct WaniResp {
     #[serde(flatten)]
     pub data: WaniData,
     pub resources_updated: Option<ResourcesUpdated>,
+    /// the untyped response, only kept when requested
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
     /*
      * Unused, but part of api
     pub url: String,
@@ -119,7 +123,7 @@ pub enum WaniData
     #[serde(rename="spaced_repetition_system")]
     SpacedRepetitionSystem,
     #[serde(rename="study_material")]
-    StudyMaterial,
+    StudyMaterial(StudyMaterial),
     #[serde(rename="user")]
     User(User),
     #[serde(rename="vocabulary")]
@@ -128,6 +132,56 @@ pub enum WaniData
     VoiceActor,
 }
 
+/// top-level keys of every api resource
+const RESOURCE_KEYS: [&str; 5] = ["id", "object", "url", "data_updated_at", "data"];
+
+/// data keys shared by all subject types
+const SUBJECT_DATA_KEYS: [&str; 10] = ["auxiliary_meanings", "created_at", "document_url", "hidden_at", 
+    "lesson_position", "level", "meaning_mnemonic", "meanings", "slug", "spaced_repetition_system_id"];
+
+const RADICAL_DATA_KEYS: [&str; 3] = ["amalgamation_subject_ids", "characters", "character_images"];
+
+const KANJI_DATA_KEYS: [&str; 8] = ["characters", "amalgamation_subject_ids", "component_subject_ids", "meaning_hint", 
+    "reading_hint", "reading_mnemonic", "readings", "visually_similar_subject_ids"];
+
+const VOCAB_DATA_KEYS: [&str; 7] = ["characters", "component_subject_ids", "context_sentences", "parts_of_speech", 
+    "pronunciation_audios", "readings", "reading_mnemonic"];
+
+const KANA_VOCAB_DATA_KEYS: [&str; 4] = ["characters", "context_sentences", "parts_of_speech", "pronunciation_audios"];
+
+/// returns the keys of a raw subject resource that our Subject structs don't know about, 
+/// formatted as "object.key" or "object.data.key"
+pub fn unrecognized_subject_keys(raw: &serde_json::Value) -> Vec<String> {
+    let object = match raw.get("object").and_then(|o| o.as_str()) {
+        Some(o) => o,
+        None => return vec![],
+    };
+    let type_keys: &[&str] = match object {
+        "radical" => &RADICAL_DATA_KEYS,
+        "kanji" => &KANJI_DATA_KEYS,
+        "vocabulary" => &VOCAB_DATA_KEYS,
+        "kana_vocabulary" => &KANA_VOCAB_DATA_KEYS,
+        _ => return vec![],
+    };
+
+    let mut unrecognized = vec![];
+    if let Some(resource) = raw.as_object() {
+        for key in resource.keys() {
+            if !RESOURCE_KEYS.contains(&key.as_str()) {
+                unrecognized.push(format!("{}.{}", object, key));
+            }
+        }
+    }
+    if let Some(data) = raw.get("data").and_then(|d| d.as_object()) {
+        for key in data.keys() {
+            if !SUBJECT_DATA_KEYS.contains(&key.as_str()) && !type_keys.contains(&key.as_str()) {
+                unrecognized.push(format!("{}.data.{}", object, key));
+            }
+        }
+    }
+    unrecognized
+}
+
 pub enum Subject
 {
     Radical(Radical),
@@ -152,14 +206,257 @@ pub struct AssignmentData {
     pub subject_id: i32,
     pub subject_type: SubjectType,
     pub unlocked_at: Option<DateTime<Utc>>,
+    pub burned_at: Option<DateTime<Utc>>,
     /*
      * Unused, but part of api
-    pub burned_at: Option<DateTime<Utc>>,
     pub passed_at: Option<DateTime<Utc>>,
     pub resurrected_at: Option<DateTime<Utc>>,
     */
 }
 
+/// returns false if the text uses codepoints that common fonts are unlikely to have, 
+/// like private-use characters or the rarer CJK extension blocks
+pub fn renders_in_common_fonts(text: &str) -> bool {
+    !text.chars().any(|c| matches!(c as u32,
+        0xE000..=0xF8FF // Private Use Area
+        | 0x20000..=0x3FFFF // CJK Unified Ideographs Extension B and later
+        | 0xF0000..=0x10FFFF)) // Supplementary Private Use Areas
+}
+
+/// whether the locale from the LC_ALL, LC_CTYPE, and LANG environment variables uses UTF-8.
+/// The first one that's set and not empty wins, like in libc
+pub fn locale_is_utf8(lc_all: Option<&str>, lc_ctype: Option<&str>, lang: Option<&str>) -> bool {
+    [lc_all, lc_ctype, lang].into_iter()
+        .flatten()
+        .find(|v| !v.is_empty())
+        .is_some_and(|v| {
+            let v = v.to_lowercase();
+            v.contains("utf-8") || v.contains("utf8")
+        })
+}
+
+/// bounds for resizing review batches based on session accuracy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveBatch {
+    pub min_size: usize,
+    pub max_size: usize,
+    /// shrink batches while accuracy (percent) is below this
+    pub low_accuracy: usize,
+    /// grow batches while accuracy (percent) is above this
+    pub high_accuracy: usize,
+}
+
+impl AdaptiveBatch {
+    /// how much a batch grows or shrinks at a time
+    const STEP: usize = 5;
+
+    /// returns the size of the next batch, given the size of the last one and the session's
+    /// guesses and misses so far
+    pub fn next_size(&self, current: usize, guesses: usize, failed: usize) -> usize {
+        let next = match (guesses.saturating_sub(failed) * 100).checked_div(guesses) {
+            Some(accuracy) if accuracy < self.low_accuracy => current.saturating_sub(Self::STEP),
+            Some(accuracy) if accuracy > self.high_accuracy => current + Self::STEP,
+            _ => current,
+        };
+        next.clamp(self.min_size, self.max_size)
+    }
+}
+
+/// returns a short description of a wait, like "2h 15m" or "3d 4h"
+pub fn format_wait(secs: i64) -> String {
+    let minutes = secs / 60;
+    let (days, hours, minutes) = (minutes / (60 * 24), minutes / 60 % 24, minutes % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => "less than a minute".to_owned(),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+/// cleans up a config file line saved by an editor that adds a UTF-8 BOM or CRLF line endings
+pub fn clean_config_line(line: &str) -> &str {
+    line.trim_start_matches('\u{feff}').trim_end_matches(['\r', ' ', '\t'])
+}
+
+/// returns the display name for an assignment's srs_stage
+pub fn srs_stage_name(srs_stage: i32) -> &'static str {
+    match srs_stage {
+        0 => "Lesson",
+        1 => "Apprentice I",
+        2 => "Apprentice II",
+        3 => "Apprentice III",
+        4 => "Apprentice IV",
+        5 => "Guru I",
+        6 => "Guru II",
+        7 => "Master",
+        8 => "Enlightened",
+        9 => "Burned",
+        _ => "Unknown",
+    }
+}
+
+/// srs stages for a stage number (1-9) or a stage group name like "apprentice"
+fn srs_stage_bounds(s: &str) -> Result<std::ops::RangeInclusive<i32>, String> {
+    match s {
+        "apprentice" => Ok(1..=4),
+        "guru" => Ok(5..=6),
+        "master" => Ok(7..=7),
+        "enlightened" => Ok(8..=8),
+        "burned" => Ok(9..=9),
+        _ => match s.parse::<i32>() {
+            Ok(n) if (1..=9).contains(&n) => Ok(n..=n),
+            _ => Err(format!("Unknown SRS stage '{}'. Expected 1-9, or apprentice, guru, master, enlightened, or burned.", s)),
+        },
+    }
+}
+
+/// parses an SRS stage range like "apprentice", "1-4", "guru-master", or "master+"
+pub fn parse_srs_range(s: &str) -> Result<std::ops::RangeInclusive<i32>, String> {
+    let s = s.trim().to_lowercase();
+    let range = if let Some(from) = s.strip_suffix('+') {
+        *srs_stage_bounds(from)?.start()..=9
+    }
+    else if let Some((from, to)) = s.split_once('-') {
+        *srs_stage_bounds(from)?.start()..=*srs_stage_bounds(to)?.end()
+    }
+    else {
+        srs_stage_bounds(&s)?
+    };
+
+    if range.is_empty() {
+        return Err(format!("SRS range '{}' is empty. Put the lower stage first.", s));
+    }
+    Ok(range)
+}
+
+/// review accuracy from the local review history, for the stats command
+#[derive(Debug, Default, PartialEq)]
+pub struct AccuracyTotals {
+    pub reviews: usize,
+    /// reviews with no wrong answers
+    pub correct: usize,
+    /// reviews that recorded which half was missed
+    pub meaning_reviews: usize,
+    pub meaning_misses: usize,
+    /// kanji and vocab reviews that recorded which half was missed
+    pub reading_reviews: usize,
+    pub reading_misses: usize,
+}
+
+/// reviews in one group, like a level or SRS stage
+#[derive(Debug, PartialEq)]
+pub struct AccuracyGroup {
+    pub key: i32,
+    pub reviews: usize,
+    /// reviews with no wrong answers
+    pub correct: usize,
+}
+
+/// formats part as a percent of total, like "87.5%", or "-" when total is 0
+pub fn format_percent(part: usize, total: usize) -> String {
+    if total == 0 {
+        return "-".to_owned();
+    }
+    format!("{:.1}%", part as f64 / total as f64 * 100.0)
+}
+
+/// checks that a cached ascii-art radical image was written completely: it isn't blank and ends in
+/// a newline. Line widths aren't checked, since the converter's lines can differ in width
+pub fn cached_ascii_is_valid(path: &std::path::Path) -> bool {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return false;
+    };
+    !contents.trim().is_empty() && contents.ends_with('\n')
+}
+
+/// how far back the stats command looks in the review history
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum StatsPeriod {
+    #[default]
+    All,
+    /// the last this many days
+    Days(u32),
+}
+
+/// parses a stats period like "7d", "30d", or "all"
+pub fn parse_stats_period(s: &str) -> Result<StatsPeriod, String> {
+    let s = s.trim().to_lowercase();
+    if s == "all" {
+        return Ok(StatsPeriod::All);
+    }
+    match s.strip_suffix('d').map(|d| d.parse::<u32>()) {
+        Some(Ok(days)) if days > 0 => Ok(StatsPeriod::Days(days)),
+        _ => Err(format!("Unknown period '{}'. Expected a number of days like 7d, or all.", s)),
+    }
+}
+
+/// first and last review history timestamps to include in stats. from and to are whole days in
+/// now's time zone, and take the place of the period's start and end
+pub fn stats_window<Tz: chrono::TimeZone>(period: StatsPeriod, from: Option<chrono::NaiveDate>, to: Option<chrono::NaiveDate>, now: DateTime<Tz>) -> (i64, i64) {
+    let start_of = |day: chrono::NaiveDate| now.timezone()
+        .from_local_datetime(&day.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map_or(i64::MIN, |t| t.timestamp());
+    let first = match (from, period) {
+        (Some(day), _) => start_of(day),
+        (None, StatsPeriod::Days(days)) => (now.clone() - chrono::Duration::days(days.into())).timestamp(),
+        (None, StatsPeriod::All) => i64::MIN,
+    };
+    let last = match to.and_then(|day| day.succ_opt()) {
+        Some(next_day) => start_of(next_day) - 1,
+        None => i64::MAX,
+    };
+    (first, last)
+}
+
+/// how a percent is rounded for display
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum PercentPrecision {
+    /// whole percent, rounded down, so 99.9% is 99%
+    #[default]
+    Truncate,
+    /// whole percent, rounded to the nearest
+    Round,
+    /// one decimal place, like 99.9%
+    OneDecimal,
+}
+
+/// formats correct out of total as a percent, like "87%". "-" before anything has been answered
+pub fn format_accuracy(correct: usize, total: usize, precision: PercentPrecision) -> String {
+    if total == 0 {
+        return "-".to_owned();
+    }
+    let percent = correct as f64 / total as f64 * 100.0;
+    match precision {
+        PercentPrecision::Truncate => format!("{}%", percent.floor()),
+        PercentPrecision::Round => format!("{}%", percent.round()),
+        PercentPrecision::OneDecimal => format!("{:.1}%", percent),
+    }
+}
+
+/// checks a WaniKani API revision, which is a date like 20170710
+pub fn parse_api_revision(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    if s.len() != 8 || !s.chars().all(|c| c.is_ascii_digit()) || chrono::NaiveDate::parse_from_str(s, "%Y%m%d").is_err() {
+        return Err(format!("Invalid WaniKani API revision '{}'. Expected a date like 20170710.", s));
+    }
+    Ok(s.to_owned())
+}
+
+/// parses a terminal size like 40x10 into (columns, rows). "off" or 0x0 means no minimum
+pub fn parse_terminal_size(s: &str) -> Result<Option<(u16, u16)>, String> {
+    let s = s.trim().to_lowercase();
+    if s == "off" {
+        return Ok(None);
+    }
+    match s.split_once('x').map(|(c, r)| (c.trim().parse::<u16>(), r.trim().parse::<u16>())) {
+        Some((Ok(0), Ok(0))) => Ok(None),
+        Some((Ok(cols), Ok(rows))) => Ok(Some((cols, rows))),
+        _ => Err(format!("Invalid terminal size '{}'. Expected columns x rows, like 40x10, or off.", s)),
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Review {
     pub data: ReviewData,
@@ -172,9 +469,9 @@ pub struct Review {
 #[derive(Deserialize, Debug)]
 pub struct ReviewData {
     pub assignment_id: i32,
+    pub created_at: DateTime<Utc>,
     /*
      * Unused, but part of the API
-    pub created_at: DateTime<Utc>,
     pub ending_srs_stage: u8,
     pub incorrect_meaning_answers: u16,
     pub incorrect_reading_answers: u16,
@@ -205,6 +502,44 @@ pub struct NewReview {
 
     #[serde(skip_serializing)]
     pub status: ReviewStatus,
+
+    /// lessons are stored alongside reviews until they're started on WaniKani
+    #[serde(skip_serializing)]
+    pub is_lesson: bool,
+
+    /// the assignment's SRS stage when the review was started, to tell if it's been reviewed
+    /// elsewhere since. None for lessons, and for reviews started before this was stored
+    #[serde(skip_serializing)]
+    pub srs_stage: Option<i32>,
+}
+
+/// how the locally stored reviews line up with the reviews WaniKani has recorded
+#[derive(Debug, Default, PartialEq)]
+pub struct ReviewReconciliation {
+    /// assignment ids of finished local reviews that WaniKani already recorded
+    pub recorded: Vec<i32>,
+    /// assignment ids of finished local reviews that WaniKani doesn't have yet
+    pub unsubmitted: Vec<i32>,
+    /// number of local reviews that haven't been finished
+    pub in_progress: usize,
+}
+
+/// compares local reviews against reviews fetched from WaniKani. A local review counts as
+/// recorded if the server has a review of the same assignment created at or after it
+pub fn reconcile_reviews(local: &[NewReview], server: &[Review]) -> ReviewReconciliation {
+    let mut result = ReviewReconciliation::default();
+    for review in local {
+        if !matches!(review.status, ReviewStatus::Done) {
+            result.in_progress += 1;
+        }
+        else if server.iter().any(|s| s.data.assignment_id == review.assignment_id && s.data.created_at >= review.created_at) {
+            result.recorded.push(review.assignment_id);
+        }
+        else {
+            result.unsubmitted.push(review.assignment_id);
+        }
+    }
+    result
 }
 
 impl Clone for NewReview {
@@ -217,6 +552,8 @@ impl Clone for NewReview {
             incorrect_meaning_answers: self.incorrect_meaning_answers,
             incorrect_reading_answers: self.incorrect_reading_answers,
             status: self.status,
+            is_lesson: self.is_lesson,
+            srs_stage: self.srs_stage,
         }
     }
 }
@@ -252,6 +589,27 @@ impl std::convert::From<usize> for ReviewStatus {
     }
 }
 
+/// returns: whether the guess is one of the subject's accepted readings, but not a primary one
+pub fn is_alt_reading(subject: &Subject, guess: &str) -> bool {
+    match subject {
+        Subject::Kanji(k) => !k.primary_readings().any(|r| r == guess) && k.alt_readings().any(|r| r == guess),
+        Subject::Vocab(v) => !v.primary_readings().any(|r| r == guess) && v.alt_readings().any(|r| r == guess),
+        Subject::Radical(_) | Subject::KanaVocab(_) => false,
+    }
+}
+
+/// picks whether to quiz a kanji/vocab's meaning (true) or reading (false).
+/// Once one half is done, the other half is always asked.
+/// last_half: the half asked last time, if it should be asked again instead of rolling
+pub fn choose_is_meaning(status: ReviewStatus, last_half: Option<bool>, roll: impl FnOnce() -> bool) -> bool {
+    match status {
+        ReviewStatus::NotStarted => last_half.unwrap_or_else(roll),
+        ReviewStatus::MeaningDone => false,
+        ReviewStatus::ReadingDone => true,
+        ReviewStatus::Done => panic!(),
+    }
+}
+
 #[derive(Deserialize, Debug, Copy, Clone)]
 pub enum SubjectType {
     #[serde(rename="radical")]
@@ -404,6 +762,13 @@ impl Kanji {
             .filter(|m| !m.primary && m.accepted_answer)
             .map(|m| &m.reading)
     }
+
+    /// readings that are not accepted as answers
+    pub fn not_accepted_readings(&self) -> impl Iterator<Item = &String> {
+        self.data.readings.iter()
+            .filter(|m| !m.accepted_answer)
+            .map(|m| &m.reading)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -444,9 +809,13 @@ impl Answer for KanjiReading {
     fn answer<'a>(&'a self) -> (&'a str, bool) {
         (&self.reading, self.accepted_answer)
     }
+
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum KanjiType
 {
     #[serde(rename="kunyomi")]
@@ -490,6 +859,13 @@ impl Vocab {
             .filter(|m| !m.primary && m.accepted_answer)
             .map(|m| &m.reading)
     }
+
+    /// readings that are not accepted as answers
+    pub fn not_accepted_readings(&self) -> impl Iterator<Item = &String> {
+        self.data.readings.iter()
+            .filter(|m| !m.accepted_answer)
+            .map(|m| &m.reading)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -575,6 +951,10 @@ impl Answer for VocabReading {
     fn answer<'a>(&'a self) -> (&'a str, bool) {
         (&self.reading, self.accepted_answer)
     }
+
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -644,9 +1024,75 @@ pub struct Lesson {
     pub subject_ids: Vec<i32>
 }
 
+/// the user's own notes for a subject, from the website
+#[derive(Deserialize, Debug)]
+pub struct StudyMaterial {
+    pub id: i32,
+    pub data: StudyMaterialData,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StudyMaterialData {
+    pub subject_id: i32,
+    pub meaning_note: Option<String>,
+    pub reading_note: Option<String>,
+    /// the user's own accepted meanings
+    #[serde(default)]
+    pub meaning_synonyms: Vec<String>,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// reviews finished so far today, across sessions
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TodayProgress {
+    pub reviews: usize,
+    /// reviews finished without any wrong answers
+    pub correct: usize,
+}
+
+impl TodayProgress {
+    pub fn record(&mut self, correct: bool) {
+        self.reviews += 1;
+        if correct {
+            self.correct += 1;
+        }
+    }
+}
+
+impl std::fmt::Display for TodayProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = (self.correct * 100).checked_div(self.reviews).unwrap_or(100);
+        write!(f, "Today: {} review{}, {}%", self.reviews, if self.reviews == 1 { "" } else { "s" }, percent)
+    }
+}
+
+/// a wrong answer that was another subject's accepted answer
+pub struct Confusion {
+    /// characters of the subject being reviewed
+    pub expected: String,
+    pub is_meaning: bool,
+    pub guess: String,
+    /// characters of the subject whose answer was given
+    pub matched: String,
+    pub times: usize,
+}
+
+impl std::fmt::Display for Confusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let answer_type = if self.is_meaning { "meaning" } else { "reading" };
+        write!(f, "You answered '{}' ({}'s {}) for {} {} time{}",
+               self.guess, self.matched, answer_type, self.expected, self.times,
+               if self.times == 1 { "" } else { "s" })
+    }
+}
+
 trait Answer {
     /// returns: (answer_text, is_accepted_answer)
     fn answer<'a>(&'a self) -> (&'a str, bool);
+
+    /// returns: whether this is a primary answer
+    fn is_primary(&self) -> bool;
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -660,6 +1106,10 @@ impl Answer for Meaning {
     fn answer<'a>(&'a self) -> (&'a str, bool) {
         (&self.meaning, self.accepted_answer)
     }
+
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
 }
 
 pub enum AnswerResult {
@@ -679,7 +1129,21 @@ pub enum AnswerResult {
     BadFormatting,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl AnswerResult {
+    /// returns a stable name for the result, for scripts
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnswerResult::Correct => "correct",
+            AnswerResult::Incorrect => "incorrect",
+            AnswerResult::FuzzyCorrect => "fuzzy_correct",
+            AnswerResult::MatchesNonAcceptedAnswer => "not_accepted",
+            AnswerResult::KanaWhenMeaning => "kana_when_meaning",
+            AnswerResult::BadFormatting => "bad_formatting",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuxMeaning {
     pub r#type: AuxMeaningType,
     pub meaning: String,
@@ -692,9 +1156,13 @@ impl Answer for AuxMeaning {
             AuxMeaningType::Blacklist => (&self.meaning, false),
         }
     }
+
+    fn is_primary(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum AuxMeaningType
 {
     #[serde(rename="whitelist")]
@@ -703,8 +1171,73 @@ pub enum AuxMeaningType
     Blacklist
 }
 
+/// result of finishing the romaji -> kana conversion of a reading guess
+pub enum KanaSettle {
+    /// the fully converted kana
+    Settled(String),
+
+    /// input ends in romaji that can't be converted yet (e.g. a lone consonant)
+    Unsettled,
+}
+
+/// converts romaji input to kana as if the user were done typing.
+/// A trailing 'n' is flushed to 'ん', but any other unconverted trailing romaji is Unsettled
+pub fn settle_kana_input(input: &str) -> KanaSettle {
+    let mut kana = input.to_kana_with_opt(wana_kana::Options { 
+        imemode: true,
+        ..Default::default()
+    });
+
+    let mut rev_chars = kana.chars().rev();
+    let last = rev_chars.next();
+    let second_last = rev_chars.next();
+    if let Some('n') = last {
+        if !second_last.is_some_and(|c| c.is_ascii_alphabetic()) {
+            kana.pop();
+            kana.push('ん');
+        }
+    }
+
+    if kana.chars().last().is_some_and(|c| c.is_ascii_alphabetic()) {
+        return KanaSettle::Unsettled;
+    }
+    KanaSettle::Settled(kana)
+}
+
+/// splits imemode kana input into the converted kana and the trailing romaji that's still
+/// waiting to become kana, e.g. "きy" -> ("き", "y")
+pub fn split_pending_romaji(kana_input: &str) -> (&str, &str) {
+    let committed = kana_input.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    kana_input.split_at(committed.len())
+}
+
+/// how far off a meaning answer can be and still be accepted as FuzzyCorrect
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FuzzyMode {
+    /// only exact answers are accepted
+    Off,
+    /// a typo or two, depending on the answer's length
+    #[default]
+    Normal,
+    /// more typos, and typos in short answers too
+    Lenient,
+}
+
+/// the user's settings for how strictly answers are checked
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AnswerOptions {
+    /// only primary meanings/readings are accepted. Other accepted answers
+    /// are treated as MatchesNonAcceptedAnswer
+    pub primary_only: bool,
+    /// meanings are compared without accents, so "cafe" matches "café"
+    pub fold_diacritics: bool,
+    pub fuzzy: FuzzyMode,
+}
+
 /// evaluates a flashcard guess
-pub fn is_correct_answer(subject: &Subject, guess: &str, is_meaning: bool, kana_input: &str) -> AnswerResult {
+/// user_synonyms: the user's own meanings for the subject, accepted like whitelisted aux meanings
+pub fn is_correct_answer(subject: &Subject, guess: &str, is_meaning: bool, kana_input: &str, user_synonyms: &[String], options: AnswerOptions) -> AnswerResult {
+    let AnswerOptions { primary_only, fold_diacritics, fuzzy } = options;
     let is_meaning = is_meaning || match subject {
         Subject::Kanji(_) => false,
         Subject::Vocab(_) => false,
@@ -715,18 +1248,23 @@ pub fn is_correct_answer(subject: &Subject, guess: &str, is_meaning: bool, kana_
     };
 
     if is_meaning {
+        let with_synonyms = |aux_meanings: &Vec<AuxMeaning>| aux_meanings.iter()
+            .cloned()
+            .chain(user_synonyms.iter().map(|s| AuxMeaning { r#type: AuxMeaningType::Whitelist, meaning: s.to_owned() }))
+            .collect_vec();
+        let guess = &if fold_diacritics { fold_diacritics_of(guess) } else { guess.to_owned() };
         return match subject {
             Subject::Radical(r) => {
-                is_correct(&r.data.meanings, &Vec::<Meaning>::new(), &r.data.aux_meanings, guess, kana_input, is_meaning)
+                is_correct(&folded(&r.data.meanings, fold_diacritics), &Vec::<Meaning>::new(), &folded(&with_synonyms(&r.data.aux_meanings), fold_diacritics), guess, kana_input, fuzzy, primary_only)
            },
             Subject::KanaVocab(kv) => {
-                is_correct(&kv.data.meanings, &Vec::<Meaning>::new(), &kv.data.aux_meanings, guess, kana_input, true)
+                is_correct(&folded(&kv.data.meanings, fold_diacritics), &Vec::<Meaning>::new(), &folded(&with_synonyms(&kv.data.aux_meanings), fold_diacritics), guess, kana_input, fuzzy, primary_only)
             },
             Subject::Kanji(k) => {
-                is_correct(&k.data.meanings, &k.data.readings, &k.data.aux_meanings, guess, kana_input, true)
+                is_correct(&folded(&k.data.meanings, fold_diacritics), &k.data.readings, &folded(&with_synonyms(&k.data.aux_meanings), fold_diacritics), guess, kana_input, fuzzy, primary_only)
             },
             Subject::Vocab(v) => {
-                is_correct(&v.data.meanings, &v.data.readings, &v.data.aux_meanings, guess, kana_input, true)
+                is_correct(&folded(&v.data.meanings, fold_diacritics), &v.data.readings, &folded(&with_synonyms(&v.data.aux_meanings), fold_diacritics), guess, kana_input, fuzzy, primary_only)
             },
         };
     }
@@ -735,50 +1273,114 @@ pub fn is_correct_answer(subject: &Subject, guess: &str, is_meaning: bool, kana_
     return match subject {
         Subject::Radical(_) => panic!("No readings for radical. should be unreachable."),
         Subject::KanaVocab(_) => panic!("No readings for kana vocab. should be unreachable."),
-        Subject::Kanji(k) => is_correct(&k.data.readings, &empty_vec, &empty_vec, guess, "", false),
-        Subject::Vocab(v) => is_correct(&v.data.readings, &empty_vec, &empty_vec, guess, "", false),
+        Subject::Kanji(k) => is_correct(&k.data.readings, &empty_vec, &empty_vec, guess, "", FuzzyMode::Off, primary_only),
+        Subject::Vocab(v) => is_correct(&v.data.readings, &empty_vec, &empty_vec, guess, "", FuzzyMode::Off, primary_only),
     };
 }
 
-fn is_correct<T, U, V>(meanings: &Vec<T>, readings: &Vec<U>, aux_meanings: &Vec<V>, guess: &str, kana_input: &str, allow_fuzzy: bool) -> AnswerResult
-where T: Answer, U: Answer, V: Answer {
-    let mut expect_numeric = false;
-    let mut best = AnswerResult::Incorrect;
-    
-    for m in meanings {
-        // Warning: this block is copy/pasted
-        let (meaning, is_accepted_answer) = m.answer();
-        if guess == meaning.trim().to_lowercase() {
-            if is_accepted_answer {
-                return AnswerResult::Correct;
-            }
+/// a kanji reading that only counts as accepted when it's of the reading type being drilled
+struct TypedReading<'a> {
+    reading: &'a KanjiReading,
+    accepted: bool,
+}
 
-            best = AnswerResult::MatchesNonAcceptedAnswer;
-        }
+impl Answer for TypedReading<'_> {
+    fn answer(&self) -> (&str, bool) {
+        (&self.reading.reading, self.accepted)
+    }
 
-        if is_accepted_answer && meaning.chars().any(|c| c.is_numeric()) {
-            expect_numeric = true;
-        }
+    fn is_primary(&self) -> bool {
+        self.reading.primary
     }
+}
 
-    for m in aux_meanings {
-        // Warning: this block is copy/pasted
-        let (meaning, is_accepted_answer) = m.answer();
-        if guess == meaning.trim().to_lowercase() {
-            if is_accepted_answer {
-                return AnswerResult::Correct;
-            }
+/// an answer copied with its text's diacritics optionally folded away
+struct FoldedAnswer {
+    answer: String,
+    accepted: bool,
+    primary: bool,
+}
 
-            best = AnswerResult::MatchesNonAcceptedAnswer;
+impl Answer for FoldedAnswer {
+    fn answer(&self) -> (&str, bool) {
+        (&self.answer, self.accepted)
+    }
+
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// copies answers, folding the diacritics out of their text when fold is set
+fn folded<T: Answer>(answers: &[T], fold: bool) -> Vec<FoldedAnswer> {
+    answers.iter()
+        .map(|a| {
+            let (answer, accepted) = a.answer();
+            let answer = if fold { fold_diacritics_of(answer) } else { answer.to_owned() };
+            FoldedAnswer { answer, accepted, primary: a.is_primary() }
+        })
+        .collect_vec()
+}
+
+/// strips accents and other combining marks from latin text, e.g. "café" -> "cafe".
+/// Kana are left alone, since decomposing them would drop their dakuten
+fn fold_diacritics_of(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            if c.is_kana() {
+                vec![c]
+            }
+            else {
+                c.nfd().filter(|c| !is_combining_mark(*c)).collect_vec()
+            }
+        })
+        .collect()
+}
+
+/// checks a kanji reading guess, only accepting readings of the given type.
+/// Kanji with no accepted reading of that type are checked against all their readings
+pub fn is_correct_kanji_reading_of_type(kanji: &Kanji, guess: &str, reading_type: KanjiType, primary_only: bool) -> AnswerResult {
+    let has_type = kanji.data.readings.iter().any(|r| r.accepted_answer && r.r#type == reading_type);
+    let readings = kanji.data.readings.iter()
+        .map(|r| TypedReading { reading: r, accepted: r.accepted_answer && (!has_type || r.r#type == reading_type) })
+        .collect_vec();
+    is_correct(&readings, &Vec::<Meaning>::new(), &Vec::<Meaning>::new(), guess, "", FuzzyMode::Off, primary_only)
+}
+
+/// every answer once, trimmed and lowercased, with whether it's accepted. An answer listed more
+/// than once (like a primary meaning that's also an aux meaning) is accepted if any copy is
+fn answer_candidates<T, V>(meanings: &[T], aux_meanings: &[V], primary_only: bool) -> Vec<(String, bool)>
+where T: Answer, V: Answer {
+    let answers = meanings.iter().map(|m| (m.answer(), m.is_primary()))
+        .chain(aux_meanings.iter().map(|m| (m.answer(), m.is_primary())));
+
+    let mut candidates: Vec<(String, bool)> = vec![];
+    for ((answer, is_accepted_answer), is_primary) in answers {
+        let answer = answer.trim().to_lowercase();
+        let is_accepted_answer = is_accepted_answer && (!primary_only || is_primary);
+        match candidates.iter_mut().find(|(a, _)| *a == answer) {
+            Some(candidate) => candidate.1 |= is_accepted_answer,
+            None => candidates.push((answer, is_accepted_answer)),
         }
+    }
+    candidates
+}
+
+fn is_correct<T, U, V>(meanings: &[T], readings: &[U], aux_meanings: &[V], guess: &str, kana_input: &str, fuzzy: FuzzyMode, primary_only: bool) -> AnswerResult
+where T: Answer, U: Answer, V: Answer {
+    let candidates = answer_candidates(meanings, aux_meanings, primary_only);
+    let expect_numeric = candidates.iter().any(|(answer, is_accepted_answer)| *is_accepted_answer && answer.chars().any(|c| c.is_numeric()));
+    let mut best = AnswerResult::Incorrect;
 
-        if is_accepted_answer && meaning.chars().any(|c| c.is_numeric()) {
-            expect_numeric = true;
+    if let Some((_, is_accepted_answer)) = candidates.iter().find(|(answer, _)| guess == answer) {
+        if *is_accepted_answer {
+            return AnswerResult::Correct;
         }
+        best = AnswerResult::MatchesNonAcceptedAnswer;
     }
 
     if meanings.len() > 0 {
-        if let AnswerResult::Correct = is_correct::<U, T, V>(readings, &vec![], &vec![], kana_input, "", false) {
+        if let AnswerResult::Correct = is_correct::<U, T, V>(readings, &[], &[], kana_input, "", FuzzyMode::Off, false) {
             return AnswerResult::KanaWhenMeaning;
         }
     }
@@ -798,26 +1400,13 @@ where T: Answer, U: Answer, V: Answer {
             return AnswerResult::BadFormatting;
         }
 
-        if !allow_fuzzy {
+        if let FuzzyMode::Off = fuzzy {
             return best;
         }
 
-        for m in meanings {
-            let (meaning, is_accepted_answer) = m.answer();
-            if fuzzy_accept(guess, &meaning.trim().to_lowercase()) {
-                if is_accepted_answer {
-                    return AnswerResult::FuzzyCorrect;
-                }
-                else {
-                    best = AnswerResult::MatchesNonAcceptedAnswer;
-                }
-            }
-        }
-
-        for m in aux_meanings {
-            let (meaning, is_accepted_answer) = m.answer();
-            if fuzzy_accept(guess, &meaning.trim().to_lowercase()) {
-                if is_accepted_answer {
+        for (answer, is_accepted_answer) in &candidates {
+            if fuzzy_accept(guess, answer, fuzzy) {
+                if *is_accepted_answer {
                     return AnswerResult::FuzzyCorrect;
                 }
                 else {
@@ -830,20 +1419,26 @@ where T: Answer, U: Answer, V: Answer {
     return best;
 }
 
-fn fuzzy_accept(guess: &str, answer: &str) -> bool {
-    match answer.len() {
-        0 | 1 | 2 | 3  => {
+fn fuzzy_accept(guess: &str, answer: &str, fuzzy: FuzzyMode) -> bool {
+    match (fuzzy, answer.len()) {
+        (FuzzyMode::Off, _) => {
+            false
+        },
+        (FuzzyMode::Normal, 0..=3) | (FuzzyMode::Lenient, 0..=2) => {
             false
         },
-        4 | 5 => {
+        (FuzzyMode::Normal, 4..=5) | (FuzzyMode::Lenient, 3..=4) => {
             edit_distance(guess, answer) <= 1
         },
-        6 | 7 => {
+        (FuzzyMode::Normal, 6..=7) | (FuzzyMode::Lenient, 5..=6) => {
             edit_distance(guess, answer) <= 2
         },
-        n => {
+        (FuzzyMode::Normal, n) => {
             edit_distance(guess, answer) <= (n / 7 + 2)
-        }
+        },
+        (FuzzyMode::Lenient, n) => {
+            edit_distance(guess, answer) <= (n / 5 + 2)
+        },
     }
 }
 
@@ -895,6 +1490,8 @@ pub struct WaniFmtArgs {
     pub meaning_args: WaniTagArgs,
     pub reading_args: WaniTagArgs,
     pub ja_args: WaniTagArgs,
+    /// show the romaji after kana readings
+    pub show_romaji: bool,
 }
 
 /// specifies an open and close tag to replace custom wanikani tags with
@@ -920,11 +1517,243 @@ pub fn format_wani_text(s: &str, args: &WaniFmtArgs) -> String {
     s.replace("</meaning>", &args.meaning_args.close_tag)
 }
 
+/// joins the kana readings for display, each followed by its romaji if show_romaji is set
+pub fn display_readings<'a>(readings: impl Iterator<Item = &'a String>, show_romaji: bool) -> String {
+    readings
+        .map(|r| if show_romaji { format!("{} ({})", r, r.to_romaji()) } else { r.to_owned() })
+        .join(", ")
+}
+
+/// lists the voice actors with audio for a vocab, like "Kyoko (female), Kenichi (male)".
+/// Each actor has several audio files (one per format), so actors are only listed once
+pub fn audio_voices_line(audios: &[PronunciationAudio]) -> Option<String> {
+    let voices = audios.iter()
+        .unique_by(|a| a.metadata.voice_actor_id)
+        .map(|a| format!("{} ({})", a.metadata.voice_actor_name, a.metadata.gender))
+        .collect_vec();
+    if voices.is_empty() {
+        None
+    }
+    else {
+        Some(format!("Audio ({}): {}", voices.len(), voices.join(", ")))
+    }
+}
+
+/// whether an audio is by the preferred voice, given as a voice actor's name (like Kyoko) or a gender (like female)
+pub fn is_preferred_voice(metadata: &PronunciationMetadata, preferred: &str) -> bool {
+    let preferred = preferred.trim().to_lowercase();
+    metadata.voice_actor_name.to_lowercase() == preferred || metadata.gender.to_lowercase() == preferred
+}
+
+/// drops blank lines that follow another blank line, and blank lines at the end
+pub fn collapse_blank_lines(lines: Vec<String>) -> Vec<String> {
+    let mut collapsed: Vec<String> = Vec::with_capacity(lines.len());
+    for line in lines {
+        let is_blank = line.trim().is_empty();
+        if is_blank && collapsed.last().is_none_or(|l| l.trim().is_empty()) {
+            continue;
+        }
+        collapsed.push(line);
+    }
+    while collapsed.last().is_some_and(|l| l.trim().is_empty()) {
+        collapsed.pop();
+    }
+    collapsed
+}
+
+/// replaces blank character lines with the subject's slug (or primary meaning, if there's no slug),
+/// so a subject with missing characters doesn't show up as a blank card
+pub fn fallback_char_lines(subject: &Subject, char_lines: Vec<String>) -> Vec<String> {
+    if char_lines.iter().any(|l| !l.trim().is_empty()) {
+        return char_lines;
+    }
+
+    let (slug, meaning) = match subject {
+        Subject::Radical(r) => (&r.data.slug, r.primary_meanings().next()),
+        Subject::Kanji(k) => (&k.data.slug, k.primary_meanings().next()),
+        Subject::Vocab(v) => (&v.data.slug, v.primary_meanings().next()),
+        Subject::KanaVocab(kv) => (&kv.data.slug, kv.primary_meanings().next()),
+    };
+    let label = if slug.trim().is_empty() { meaning.map(|m| m.as_str()).unwrap_or("?") } else { slug };
+    vec![format!("{} (characters missing)", label)]
+}
+
+/// draws a subject and its related subjects, grouped under labels, as an indented tree.
+/// Empty groups are left out
+pub fn tree_lines(root: &str, groups: &[(&str, Vec<String>)]) -> Vec<String> {
+    let mut lines = vec![root.to_owned()];
+    let groups = groups.iter().filter(|(_, items)| !items.is_empty()).collect_vec();
+    for (i, (label, items)) in groups.iter().enumerate() {
+        let last_group = i + 1 == groups.len();
+        lines.push(format!("{} {}", if last_group { "\u{2514}\u{2500}" } else { "\u{251c}\u{2500}" }, label));
+        let indent = if last_group { "   " } else { "\u{2502}  " };
+        for (j, item) in items.iter().enumerate() {
+            let branch = if j + 1 == items.len() { "\u{2514}\u{2500}" } else { "\u{251c}\u{2500}" };
+            lines.push(format!("{}{} {}", indent, branch, item));
+        }
+    }
+    lines
+}
+
+/// header of a custom deck CSV. Meanings and readings can list alternatives separated by ';'.
+/// The first one listed is the primary answer. Leave readings empty for kana-only vocab.
+pub const CUSTOM_DECK_HEADER: &str = "characters,meanings,readings";
+
+/// vocab from a user's own deck, imported with 'wani import-deck'. Never part of WaniKani SRS
+#[derive(Debug, PartialEq)]
+pub struct CustomSubject {
+    pub id: i32,
+    pub characters: String,
+    pub meanings: Vec<String>,
+    pub readings: Vec<String>,
+}
+
+/// whether a subject id belongs to a custom deck subject. See CustomSubject::to_subject
+pub fn is_custom_subject_id(id: i32) -> bool {
+    id < 0
+}
+
+impl CustomSubject {
+    /// builds a vocab subject that can be quizzed like a WaniKani one.
+    /// ids are negated so they can't collide with WaniKani subject ids
+    pub fn to_subject(&self) -> Subject {
+        let meanings = self.meanings.iter()
+            .enumerate()
+            .map(|(i, m)| Meaning { meaning: m.to_owned(), primary: i == 0, accepted_answer: true })
+            .collect_vec();
+        if self.readings.is_empty() {
+            return Subject::KanaVocab(KanaVocab {
+                id: -self.id,
+                data: KanaVocabData {
+                    aux_meanings: vec![],
+                    created_at: Utc::now(),
+                    document_url: String::new(),
+                    hidden_at: None,
+                    lesson_position: 0,
+                    level: 0,
+                    meaning_mnemonic: String::new(),
+                    meanings,
+                    slug: self.characters.clone(),
+                    spaced_repetition_system_id: 0,
+                    characters: self.characters.clone(),
+                    context_sentences: vec![],
+                    parts_of_speech: vec![],
+                    pronunciation_audios: vec![],
+                },
+            });
+        }
+
+        Subject::Vocab(Vocab {
+            id: -self.id,
+            data: VocabData {
+                aux_meanings: vec![],
+                created_at: Utc::now(),
+                document_url: String::new(),
+                hidden_at: None,
+                lesson_position: 0,
+                level: 0,
+                meaning_mnemonic: String::new(),
+                meanings,
+                slug: self.characters.clone(),
+                spaced_repetition_system_id: 0,
+                characters: self.characters.clone(),
+                component_subject_ids: vec![],
+                context_sentences: vec![],
+                parts_of_speech: vec![],
+                pronunciation_audios: vec![],
+                readings: self.readings.iter()
+                    .enumerate()
+                    .map(|(i, r)| VocabReading { reading: r.to_owned(), primary: i == 0, accepted_answer: true })
+                    .collect_vec(),
+                reading_mnemonic: String::new(),
+            },
+        })
+    }
+}
+
+/// parses a custom deck CSV (see CUSTOM_DECK_HEADER). The header line is optional.
+/// returns a message for every invalid line, so the whole file can be fixed at once
+pub fn parse_custom_deck(csv: &str) -> Result<Vec<CustomSubject>, Vec<String>> {
+    let mut subjects: Vec<CustomSubject> = vec![];
+    let mut errors = vec![];
+    for (i, line) in csv.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.trim();
+        if line.is_empty() || (subjects.is_empty() && errors.is_empty() && line.eq_ignore_ascii_case(CUSTOM_DECK_HEADER)) {
+            continue;
+        }
+
+        let fields = line.split(',').map(|f| f.trim()).collect_vec();
+        if fields.len() != 3 {
+            errors.push(format!("Line {}: expected 3 fields ({}), found {}", line_num, CUSTOM_DECK_HEADER, fields.len()));
+            continue;
+        }
+
+        let split_alternatives = |field: &str| field.split(';')
+            .map(|a| a.trim().to_owned())
+            .filter(|a| !a.is_empty())
+            .collect_vec();
+        let characters = fields[0].to_owned();
+        let meanings = split_alternatives(fields[1]);
+        let readings = split_alternatives(fields[2]);
+        if characters.is_empty() {
+            errors.push(format!("Line {}: characters are empty", line_num));
+        }
+        else if subjects.iter().any(|s| s.characters == characters) {
+            errors.push(format!("Line {}: {} is already in the deck", line_num, characters));
+        }
+        if meanings.is_empty() {
+            errors.push(format!("Line {}: needs at least one meaning", line_num));
+        }
+        if let Some(r) = readings.iter().find(|r| !r.is_kana()) {
+            errors.push(format!("Line {}: reading '{}' isn't kana", line_num, r));
+        }
+
+        subjects.push(CustomSubject { id: 0, characters, meanings, readings });
+    }
+
+    if errors.is_empty() { Ok(subjects) } else { Err(errors) }
+}
+
+/// returns a tab separated "front, back, mnemonic" line for importing the subject into Anki
+pub fn anki_tsv_row(subject: &Subject) -> String {
+    let fmt_args = WaniFmtArgs::default();
+    let (front, meanings, readings, mnemonics) = match subject {
+        Subject::Radical(r) => (
+            r.data.characters.clone().unwrap_or_else(|| r.data.slug.clone()),
+            r.primary_meanings().join(", "),
+            String::new(),
+            vec![&r.data.meaning_mnemonic]),
+        Subject::Kanji(k) => (
+            k.data.characters.clone(),
+            k.primary_meanings().join(", "),
+            k.primary_readings().join(", "),
+            vec![&k.data.meaning_mnemonic, &k.data.reading_mnemonic]),
+        Subject::Vocab(v) => (
+            v.data.characters.clone(),
+            v.primary_meanings().join(", "),
+            v.primary_readings().join(", "),
+            vec![&v.data.meaning_mnemonic, &v.data.reading_mnemonic]),
+        Subject::KanaVocab(kv) => (
+            kv.data.characters.clone(),
+            kv.primary_meanings().join(", "),
+            String::new(),
+            vec![&kv.data.meaning_mnemonic]),
+    };
+    let back = if readings.is_empty() { meanings } else { format!("{} / {}", meanings, readings) };
+    let mnemonic = mnemonics.iter().map(|m| format_wani_text(m, &fmt_args)).join(" ");
+
+    // Tabs and newlines would break the row apart
+    [front, back, mnemonic].iter()
+        .map(|field| field.replace(['\t', '\n', '\r'], " "))
+        .join("\t")
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
-    use crate::wanidata::{edit_distance, AnswerResult};
-    use super::{format_wani_text, is_correct_answer, AuxMeaning, AuxMeaningType, KanaVocab, KanaVocabData, Kanji, KanjiData, KanjiReading, Meaning, Radical, RadicalData, Subject, Vocab, VocabData, VocabReading, WaniFmtArgs};
+    use crate::wanidata::{anki_tsv_row, tree_lines, locale_is_utf8, audio_voices_line, clean_config_line, collapse_blank_lines, display_readings, is_preferred_voice, edit_distance, fallback_char_lines, format_wait, parse_custom_deck, reconcile_reviews, TodayProgress, NewReview, Review, ReviewData, ReviewReconciliation, CustomSubject, renders_in_common_fonts, settle_kana_input, split_pending_romaji, srs_stage_name, parse_srs_range, parse_api_revision, format_percent, format_accuracy, PercentPrecision, AccuracyGroup, cached_ascii_is_valid, parse_stats_period, parse_terminal_size, stats_window, StatsPeriod, unrecognized_subject_keys, choose_is_meaning, is_alt_reading, AdaptiveBatch, AnswerResult, Confusion, KanaSettle, ReviewStatus};
+    use super::{answer_candidates, fold_diacritics_of, format_wani_text, is_correct_answer, AnswerOptions, FuzzyMode, is_correct_kanji_reading_of_type, Assignment, AssignmentData, AuxMeaning, AuxMeaningType, KanaVocab, KanaVocabData, Kanji, KanjiData, KanjiReading, KanjiType, Meaning, PronunciationAudio, PronunciationMetadata, Radical, RadicalData, Subject, SubjectType, Vocab, VocabData, VocabReading, WaniFmtArgs};
 
     // #region is_correct_answer Kanji
 
@@ -932,7 +1761,7 @@ mod tests {
     fn is_correct_answer_accepted_kanji_meaning_edit_distance() {
         let is_meaning = true;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "accepterd", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accepterd", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::FuzzyCorrect));
     }
@@ -941,7 +1770,7 @@ mod tests {
     fn is_correct_answer_low_edit_dist_but_matches_non_accepted() {
         let is_meaning = true;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted1", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted1", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -950,7 +1779,7 @@ mod tests {
     fn is_correct_answer_reading_doesnt_check_edit_dist() {
         let is_meaning = false;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "はがねん", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "はがねん", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -959,7 +1788,7 @@ mod tests {
     fn is_correct_answer_high_edit_dist() {
         let is_meaning = true;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "acceptedlmno", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "acceptedlmno", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -968,7 +1797,7 @@ mod tests {
     fn is_correct_answer_short_answer_strict() {
         let is_meaning = true;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "b", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "b", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -977,7 +1806,7 @@ mod tests {
     fn is_correct_answer_shortish_answer_accepts_close() {
         let is_meaning = true;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "accr", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accr", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::FuzzyCorrect));
     }
@@ -986,7 +1815,7 @@ mod tests {
     fn is_correct_answer_shortish_answer_rejects_far() {
         let is_meaning = true;
         let kanji = get_edit_dist_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "accerp", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accerp", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -997,7 +1826,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "aux_whitelist";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1008,7 +1837,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "whitelisty";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::FuzzyCorrect));
     }
@@ -1019,7 +1848,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "aux_blacklist";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1030,7 +1859,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "blacklisty";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1041,7 +1870,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "auxnone";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1052,7 +1881,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "aux_whitelist";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::BadFormatting));
     }
@@ -1063,7 +1892,7 @@ mod tests {
         let kanji = get_aux_meaning_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "whitelist";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1075,7 +1904,7 @@ mod tests {
         let subj = Subject::Kanji(kanji);
         for guess in "0123456789!@#$%^&*()-_=+`~[[]]\\;:'\",<.>/?".chars() {
             let guess = String::from(guess);
-            let result = is_correct_answer(&subj, &guess, is_meaning, "");
+            let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
             assert!(matches!(result, AnswerResult::BadFormatting));
         }
@@ -1087,7 +1916,7 @@ mod tests {
         let kanji = get_standard_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "おn";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::BadFormatting));
     }
@@ -1098,7 +1927,7 @@ mod tests {
         let kanji = get_standard_kanji();
         let subj = Subject::Kanji(kanji);
         let guess = "おn";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::BadFormatting));
     }
@@ -1115,7 +1944,7 @@ mod tests {
 
         let subj = Subject::Kanji(kanji);
         let guess = "43";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1132,7 +1961,7 @@ mod tests {
 
         let subj = Subject::Kanji(kanji);
         let guess = "hello there";
-        let result = is_correct_answer(&subj, &guess, is_meaning, "");
+        let result = is_correct_answer(&subj, &guess, is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1141,7 +1970,7 @@ mod tests {
     fn is_correct_answer_accepted_kanji_meaning() {
         let is_meaning = true;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1150,7 +1979,7 @@ mod tests {
     fn is_correct_answer_accepted_kanji_reading() {
         let is_meaning = false;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "はがねの", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "はがねの", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1159,7 +1988,7 @@ mod tests {
     fn is_correct_answer_gave_kanji_reading_when_meaning() {
         let is_meaning = true;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "blah", is_meaning, "はがねの");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "blah", is_meaning, "はがねの", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::KanaWhenMeaning));
     }
@@ -1168,7 +1997,7 @@ mod tests {
     fn is_correct_answer_not_accepted_kanji_meaning() {
         let is_meaning = true;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "not_accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "not_accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1177,7 +2006,7 @@ mod tests {
     fn is_correct_answer_not_accepted_kanji_reading() {
         let is_meaning = false;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "not_はがねの", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "not_はがねの", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1191,7 +2020,7 @@ mod tests {
             primary: false, 
             accepted_answer: true 
         });
-        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted1", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted1", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1206,7 +2035,7 @@ mod tests {
             accepted_answer: true,
             r#type: crate::wanidata::KanjiType::Nanori,
         });
-        let result = is_correct_answer(&Subject::Kanji(kanji), "はがねのの", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "はがねのの", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1215,7 +2044,7 @@ mod tests {
     fn is_correct_answer_incorrect_kanji_meaning() {
         let is_meaning = true;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "foo", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "foo", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1224,7 +2053,7 @@ mod tests {
     fn is_correct_answer_incorrect_kanji_meaning_with_spaces() {
         let is_meaning = true;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "foo bar", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "foo bar", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1233,11 +2062,20 @@ mod tests {
     fn is_correct_answer_incorrect_kanji_reading() {
         let is_meaning = false;
         let kanji = get_standard_kanji();
-        let result = is_correct_answer(&Subject::Kanji(kanji), "foo", is_meaning, "");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "foo", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
 
+    #[test]
+    fn is_correct_answer_kanji_user_synonym() {
+        let is_meaning = true;
+        let kanji = get_standard_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "my synonym", is_meaning, "", &["My Synonym".into()], AnswerOptions::default());
+
+        assert!(matches!(result, AnswerResult::Correct));
+    }
+
     // #endregion is_correct_answer Kanji
     
     // #region is_correct_answer Vocab
@@ -1246,7 +2084,7 @@ mod tests {
     fn is_correct_answer_accepted_vocab_meaning() {
         let is_meaning = true;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1255,7 +2093,7 @@ mod tests {
     fn is_correct_answer_gave_reading_when_meaning() {
         let is_meaning = true;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "blah", is_meaning, "はがねの");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "blah", is_meaning, "はがねの", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::KanaWhenMeaning));
     }
@@ -1264,7 +2102,7 @@ mod tests {
     fn is_correct_answer_accepted_vocab_reading() {
         let is_meaning = false;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "はがねの", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "はがねの", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1273,7 +2111,7 @@ mod tests {
     fn is_correct_answer_not_accepted_vocab_meaning() {
         let is_meaning = true;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "not_accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "not_accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1282,7 +2120,7 @@ mod tests {
     fn is_correct_answer_not_accepted_vocab_reading() {
         let is_meaning = false;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "not_はがねの", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "not_はがねの", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1296,7 +2134,7 @@ mod tests {
             primary: false, 
             accepted_answer: true 
         });
-        let result = is_correct_answer(&Subject::Vocab(vocab), "accepted1", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "accepted1", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1310,7 +2148,7 @@ mod tests {
             primary: false, 
             accepted_answer: true,
         });
-        let result = is_correct_answer(&Subject::Vocab(vocab), "はがねのの", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "はがねのの", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1319,7 +2157,7 @@ mod tests {
     fn is_correct_answer_incorrect_vocab_meaning() {
         let is_meaning = true;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "foo", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "foo", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1328,7 +2166,7 @@ mod tests {
     fn is_correct_answer_incorrect_vocab_reading() {
         let is_meaning = false;
         let vocab = get_standard_vocab();
-        let result = is_correct_answer(&Subject::Vocab(vocab), "foo", is_meaning, "");
+        let result = is_correct_answer(&Subject::Vocab(vocab), "foo", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1341,7 +2179,7 @@ mod tests {
     fn is_correct_answer_accepted_kv() {
         let is_meaning = true;
         let kv = get_standard_kana_vocab();
-        let result = is_correct_answer(&Subject::KanaVocab(kv), "accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::KanaVocab(kv), "accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1350,7 +2188,7 @@ mod tests {
     fn is_correct_answer_accepted_kv_ignores_is_meaning() {
         let is_meaning = false;
         let kv = get_standard_kana_vocab();
-        let result = is_correct_answer(&Subject::KanaVocab(kv), "accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::KanaVocab(kv), "accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1359,7 +2197,7 @@ mod tests {
     fn is_correct_answer_not_accepted_kv() {
         let is_meaning = true;
         let kv = get_standard_kana_vocab();
-        let result = is_correct_answer(&Subject::KanaVocab(kv), "not_accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::KanaVocab(kv), "not_accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1373,7 +2211,7 @@ mod tests {
             primary: false, 
             accepted_answer: true 
         });
-        let result = is_correct_answer(&Subject::KanaVocab(kv), "accepted1", is_meaning, "");
+        let result = is_correct_answer(&Subject::KanaVocab(kv), "accepted1", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1382,21 +2220,68 @@ mod tests {
     fn is_correct_answer_incorrect_kv() {
         let is_meaning = true;
         let kv = get_standard_kana_vocab();
-        let result = is_correct_answer(&Subject::KanaVocab(kv), "foo", is_meaning, "");
+        let result = is_correct_answer(&Subject::KanaVocab(kv), "foo", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
 
     //
-    // #endregion is_correct_answer KanaVocab
-
-    // #region is_correct_answer Radical
-    
     #[test]
-    fn is_correct_answer_accepted_radical() {
+    fn is_correct_answer_overlapping_primary_and_aux_kv() {
         let is_meaning = true;
-        let radical = get_standard_radical();
-        let result = is_correct_answer(&Subject::Radical(radical), "accepted", is_meaning, "");
+        let meanings = vec![Meaning { meaning: "Water".into(), primary: true, accepted_answer: true }];
+        // the same answer is also listed, in a different case, as a whitelisted and a blacklisted aux meaning
+        let aux_meanings = vec![
+            AuxMeaning { r#type: AuxMeaningType::Blacklist, meaning: "water".into() },
+            AuxMeaning { r#type: AuxMeaningType::Whitelist, meaning: " WATER".into() },
+        ];
+        let kv = get_kana_vocab(meanings, aux_meanings);
+
+        assert!(matches!(is_correct_answer(&Subject::KanaVocab(kv), "water", is_meaning, "", &[], AnswerOptions::default()), AnswerResult::Correct));
+    }
+
+    #[test]
+    fn is_correct_answer_overlapping_aux_fuzzy_kv() {
+        let is_meaning = true;
+        let meanings = vec![Meaning { meaning: "water".into(), primary: false, accepted_answer: false }];
+        let aux_meanings = vec![AuxMeaning { r#type: AuxMeaningType::Whitelist, meaning: "water".into() }];
+        let kv = get_kana_vocab(meanings, aux_meanings);
+
+        // an accepted copy wins no matter which list it's in
+        assert!(matches!(is_correct_answer(&Subject::KanaVocab(kv), "watr", is_meaning, "", &[], AnswerOptions::default()), AnswerResult::FuzzyCorrect));
+    }
+
+    #[test]
+    fn is_correct_answer_aux_blacklist_of_primary_primary_only_kv() {
+        let is_meaning = true;
+        let meanings = vec![
+            Meaning { meaning: "Water".into(), primary: true, accepted_answer: true },
+            Meaning { meaning: "Aqua".into(), primary: false, accepted_answer: true },
+        ];
+        let aux_meanings = vec![
+            AuxMeaning { r#type: AuxMeaningType::Blacklist, meaning: "water".into() },
+            AuxMeaning { r#type: AuxMeaningType::Whitelist, meaning: "aqua ".into() },
+        ];
+
+        // each answer is matched once, accepted if any copy is
+        let candidates = answer_candidates(&meanings, &aux_meanings, true);
+        assert_eq!(vec![("water".to_owned(), true), ("aqua".to_owned(), false)], candidates);
+
+        let kv = Subject::KanaVocab(get_kana_vocab(meanings, aux_meanings));
+        let options = AnswerOptions { primary_only: true, ..Default::default() };
+        assert!(matches!(is_correct_answer(&kv, "water", is_meaning, "", &[], options), AnswerResult::Correct));
+        assert!(matches!(is_correct_answer(&kv, "aqua", is_meaning, "", &[], options), AnswerResult::MatchesNonAcceptedAnswer));
+    }
+
+    // #endregion is_correct_answer KanaVocab
+
+    // #region is_correct_answer Radical
+    
+    #[test]
+    fn is_correct_answer_accepted_radical() {
+        let is_meaning = true;
+        let radical = get_standard_radical();
+        let result = is_correct_answer(&Subject::Radical(radical), "accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1405,7 +2290,7 @@ mod tests {
     fn is_correct_answer_accepted_radical_ignores_is_meaning() {
         let is_meaning = false;
         let radical = get_standard_radical();
-        let result = is_correct_answer(&Subject::Radical(radical), "accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1414,7 +2299,7 @@ mod tests {
     fn is_correct_answer_not_accepted_radical() {
         let is_meaning = true;
         let radical = get_standard_radical();
-        let result = is_correct_answer(&Subject::Radical(radical), "not_accepted", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "not_accepted", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1428,7 +2313,7 @@ mod tests {
             primary: false, 
             accepted_answer: true 
         });
-        let result = is_correct_answer(&Subject::Radical(radical), "accepted1", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "accepted1", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1437,7 +2322,7 @@ mod tests {
     fn is_correct_answer_incorrect_radical() {
         let is_meaning = true;
         let radical = get_standard_radical();
-        let result = is_correct_answer(&Subject::Radical(radical), "foo", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "foo", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
@@ -1446,7 +2331,7 @@ mod tests {
     fn is_correct_answer_aux_meaning_blacklist() {
         let is_meaning = true;
         let radical = get_radical_aux_meanings();
-        let result = is_correct_answer(&Subject::Radical(radical), "aux_blacklist", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "aux_blacklist", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
     }
@@ -1455,7 +2340,7 @@ mod tests {
     fn is_correct_answer_aux_meaning_whitelist() {
         let is_meaning = true;
         let radical = get_radical_aux_meanings();
-        let result = is_correct_answer(&Subject::Radical(radical), "aux_whitelist", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "aux_whitelist", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Correct));
     }
@@ -1464,13 +2349,308 @@ mod tests {
     fn is_correct_answer_aux_meaning_guess_matches_none() {
         let is_meaning = true;
         let radical = get_radical_aux_meanings();
-        let result = is_correct_answer(&Subject::Radical(radical), "auxnone", is_meaning, "");
+        let result = is_correct_answer(&Subject::Radical(radical), "auxnone", is_meaning, "", &[], AnswerOptions::default());
 
         assert!(matches!(result, AnswerResult::Incorrect));
     }
 
     // #endregion is_correct_answer Radical
 
+    // #region is_correct_answer primary_only
+
+    #[test]
+    fn is_correct_answer_alt_meaning_accepted_normally() {
+        let is_meaning = true;
+        let kanji = get_alt_answer_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "alternate", is_meaning, "", &[], AnswerOptions::default());
+
+        assert!(matches!(result, AnswerResult::Correct));
+    }
+
+    #[test]
+    fn is_correct_answer_primary_only_rejects_alt_meaning() {
+        let is_meaning = true;
+        let kanji = get_alt_answer_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "alternate", is_meaning, "", &[], AnswerOptions { primary_only: true, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
+    }
+
+    #[test]
+    fn is_correct_answer_primary_only_accepts_primary_meaning() {
+        let is_meaning = true;
+        let kanji = get_alt_answer_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "primary", is_meaning, "", &[], AnswerOptions { primary_only: true, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Correct));
+    }
+
+    #[test]
+    fn is_correct_answer_primary_only_rejects_alt_reading() {
+        let is_meaning = false;
+        let kanji = get_alt_answer_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "こう", is_meaning, "", &[], AnswerOptions { primary_only: true, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
+    }
+
+    #[test]
+    fn is_correct_answer_primary_only_rejects_aux_whitelist() {
+        let is_meaning = true;
+        let radical = get_radical_aux_meanings();
+        let result = is_correct_answer(&Subject::Radical(radical), "aux_whitelist", is_meaning, "", &[], AnswerOptions { primary_only: true, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::MatchesNonAcceptedAnswer));
+    }
+
+    fn get_alt_answer_kanji() -> Kanji {
+        let meanings = vec![
+            Meaning {
+                meaning: "primary".into(),
+                primary: true,
+                accepted_answer: true,
+            },
+            Meaning {
+                meaning: "alternate".into(),
+                primary: false,
+                accepted_answer: true,
+            },
+        ];
+        let kanji_readings = vec![
+            KanjiReading { 
+                reading: "こ".into(), 
+                primary: true, 
+                accepted_answer: true, 
+                r#type: super::KanjiType::Onyomi 
+            },
+            KanjiReading { 
+                reading: "こう".into(), 
+                primary: false, 
+                accepted_answer: true, 
+                r#type: super::KanjiType::Onyomi 
+            },
+        ];
+        get_kanji(meanings, kanji_readings, vec![])
+    }
+
+    // #endregion is_correct_answer primary_only
+
+    // #region is_correct_answer fold_diacritics
+
+    #[test]
+    fn is_correct_answer_fold_diacritics_accepts_plain_meaning() {
+        let is_meaning = true;
+        let kanji = get_accented_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "cafe", is_meaning, "", &[], AnswerOptions { fold_diacritics: true, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Correct));
+    }
+
+    #[test]
+    fn is_correct_answer_without_fold_diacritics_plain_meaning_is_fuzzy() {
+        let is_meaning = true;
+        let kanji = get_accented_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "cafe", is_meaning, "", &[], AnswerOptions::default());
+
+        assert!(matches!(result, AnswerResult::FuzzyCorrect));
+    }
+
+    #[test]
+    fn fold_diacritics_of_keeps_dakuten() {
+        assert_eq!(fold_diacritics_of("café がぎ"), "cafe がぎ");
+    }
+
+    fn get_accented_kanji() -> Kanji {
+        let meanings = vec![
+            Meaning {
+                meaning: "café".into(),
+                primary: true,
+                accepted_answer: true,
+            },
+        ];
+        get_kanji(meanings, vec![], vec![])
+    }
+
+    // #endregion is_correct_answer fold_diacritics
+
+    // #region is_correct_answer fuzzy
+
+    #[test]
+    fn is_correct_answer_fuzzy_off_rejects_close() {
+        let is_meaning = true;
+        let kanji = get_edit_dist_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accr", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Off, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Incorrect));
+    }
+
+    #[test]
+    fn is_correct_answer_fuzzy_off_accepts_exact() {
+        let is_meaning = true;
+        let kanji = get_edit_dist_kanji();
+        let result = is_correct_answer(&Subject::Kanji(kanji), "accepted", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Off, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Correct));
+    }
+
+    #[test]
+    fn is_correct_answer_fuzzy_normal_rejects_short_typo() {
+        let is_meaning = true;
+        let kanji = get_meaning_kanji("cat");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "cot", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Normal, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Incorrect));
+    }
+
+    #[test]
+    fn is_correct_answer_fuzzy_lenient_accepts_short_typo() {
+        let is_meaning = true;
+        let kanji = get_meaning_kanji("cat");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "cot", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Lenient, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::FuzzyCorrect));
+    }
+
+    #[test]
+    fn is_correct_answer_fuzzy_normal_rejects_two_typos() {
+        let is_meaning = true;
+        let kanji = get_meaning_kanji("sword");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "swrod", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Normal, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Incorrect));
+    }
+
+    #[test]
+    fn is_correct_answer_fuzzy_lenient_accepts_two_typos() {
+        let is_meaning = true;
+        let kanji = get_meaning_kanji("sword");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "swrod", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Lenient, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::FuzzyCorrect));
+    }
+
+    #[test]
+    fn is_correct_answer_fuzzy_lenient_still_rejects_far() {
+        let is_meaning = true;
+        let kanji = get_meaning_kanji("cat");
+        let result = is_correct_answer(&Subject::Kanji(kanji), "dog", is_meaning, "", &[], AnswerOptions { fuzzy: FuzzyMode::Lenient, ..Default::default() });
+
+        assert!(matches!(result, AnswerResult::Incorrect));
+    }
+
+    fn get_meaning_kanji(meaning: &str) -> Kanji {
+        let meanings = vec![
+            Meaning {
+                meaning: meaning.into(),
+                primary: true,
+                accepted_answer: true,
+            },
+        ];
+        get_kanji(meanings, vec![], vec![])
+    }
+
+    // #endregion is_correct_answer fuzzy
+
+    // #region is_correct_kanji_reading_of_type
+
+    #[test]
+    fn is_correct_kanji_reading_of_type_rejects_kunyomi_in_onyomi_mode() {
+        let kanji = get_mixed_reading_kanji();
+
+        assert!(matches!(is_correct_kanji_reading_of_type(&kanji, "にち", KanjiType::Onyomi, false), AnswerResult::Correct));
+        assert!(matches!(is_correct_kanji_reading_of_type(&kanji, "ひ", KanjiType::Onyomi, false), AnswerResult::MatchesNonAcceptedAnswer));
+        assert!(matches!(is_correct_kanji_reading_of_type(&kanji, "ひ", KanjiType::Kunyomi, false), AnswerResult::Correct));
+    }
+
+    #[test]
+    fn is_correct_kanji_reading_of_type_falls_back_without_that_type() {
+        let kanji = get_alt_answer_kanji();
+
+        assert!(matches!(is_correct_kanji_reading_of_type(&kanji, "こ", KanjiType::Kunyomi, false), AnswerResult::Correct));
+    }
+
+    fn get_mixed_reading_kanji() -> Kanji {
+        let kanji_readings = vec![
+            KanjiReading {
+                reading: "にち".into(),
+                primary: true,
+                accepted_answer: true,
+                r#type: KanjiType::Onyomi
+            },
+            KanjiReading {
+                reading: "ひ".into(),
+                primary: false,
+                accepted_answer: true,
+                r#type: KanjiType::Kunyomi
+            },
+        ];
+        get_kanji(vec![], kanji_readings, vec![])
+    }
+
+    // #endregion is_correct_kanji_reading_of_type
+
+    // #region display_readings
+
+    #[test]
+    fn display_readings_adds_romaji() {
+        let readings = ["にち".to_owned(), "ひ".to_owned()];
+        assert_eq!("にち, ひ", display_readings(readings.iter(), false));
+        assert_eq!("にち (nichi), ひ (hi)", display_readings(readings.iter(), true));
+    }
+
+    // #endregion display_readings
+
+    // #region collapse_blank_lines
+
+    #[test]
+    fn collapse_blank_lines_collapses_runs() {
+        let lines = vec!["a".to_owned(), "".to_owned(), "  ".to_owned(), "".to_owned(), "b".to_owned()];
+        assert_eq!(vec!["a", "", "b"], collapse_blank_lines(lines));
+    }
+
+    #[test]
+    fn collapse_blank_lines_drops_leading_and_trailing() {
+        let lines = vec!["".to_owned(), "a".to_owned(), "".to_owned(), "".to_owned()];
+        assert_eq!(vec!["a"], collapse_blank_lines(lines));
+    }
+
+    #[test]
+    fn collapse_blank_lines_keeps_indented_text() {
+        let lines = vec!["\ta".to_owned(), "".to_owned(), "\tb".to_owned()];
+        assert_eq!(vec!["\ta", "", "\tb"], collapse_blank_lines(lines));
+    }
+
+    // #endregion collapse_blank_lines
+
+    // #region is_alt_reading
+
+    #[test]
+    fn is_alt_reading_alternate() {
+        let kanji = get_alt_answer_kanji();
+        assert!(is_alt_reading(&Subject::Kanji(kanji), "こう"));
+    }
+
+    #[test]
+    fn is_alt_reading_primary() {
+        let kanji = get_alt_answer_kanji();
+        assert!(!is_alt_reading(&Subject::Kanji(kanji), "こ"));
+    }
+
+    #[test]
+    fn is_alt_reading_not_a_reading() {
+        let kanji = get_alt_answer_kanji();
+        assert!(!is_alt_reading(&Subject::Kanji(kanji), "か"));
+    }
+
+    // #endregion is_alt_reading
+
+    #[test]
+    fn not_accepted_readings_kanji() {
+        let kanji = get_standard_kanji();
+        let readings: Vec<&String> = kanji.not_accepted_readings().collect();
+        assert_eq!(vec!["not_はがねの"], readings);
+    }
+
     fn get_kanji(meanings: Vec<Meaning>, readings: Vec<KanjiReading>, aux_meanings: Vec<AuxMeaning>) -> Kanji {
         Kanji {
             id: 1,
@@ -1799,6 +2979,7 @@ mod tests {
                 open_tag: "[my_ja]".to_owned(),
                 close_tag: "[/my_ja]".to_owned(),
             },
+            show_romaji: false,
         } 
     }
 
@@ -1835,6 +3016,675 @@ mod tests {
         assert_eq!(expected, &formatted);
     }
 
+    // #region settle_kana_input
+
+    #[test]
+    fn split_pending_romaji_separates_tail() {
+        assert_eq!(("き", "y"), split_pending_romaji("きy"));
+        assert_eq!(("はがね", ""), split_pending_romaji("はがね"));
+        assert_eq!(("", "sh"), split_pending_romaji("sh"));
+    }
+
+    #[test]
+    fn settle_kana_input_complete_is_unchanged() {
+        let result = settle_kana_input("hagane");
+        assert!(matches!(result, KanaSettle::Settled(k) if k == "はがね"));
+    }
+
+    #[test]
+    fn settle_kana_input_flushes_trailing_n() {
+        let result = settle_kana_input("kan");
+        assert!(matches!(result, KanaSettle::Settled(k) if k == "かん"));
+    }
+
+    #[test]
+    fn settle_kana_input_lone_n() {
+        let result = settle_kana_input("n");
+        assert!(matches!(result, KanaSettle::Settled(k) if k == "ん"));
+    }
+
+    #[test]
+    fn settle_kana_input_double_n_not_doubled() {
+        let result = settle_kana_input("kann");
+        assert!(matches!(result, KanaSettle::Settled(k) if k == "かん"));
+    }
+
+    #[test]
+    fn settle_kana_input_trailing_consonant_unsettled() {
+        assert!(matches!(settle_kana_input("kak"), KanaSettle::Unsettled));
+        assert!(matches!(settle_kana_input("kitt"), KanaSettle::Unsettled));
+    }
+
+    #[test]
+    fn settle_kana_input_trailing_consonant_after_n_unsettled() {
+        assert!(matches!(settle_kana_input("kany"), KanaSettle::Unsettled));
+    }
+
+    #[test]
+    fn settle_kana_input_empty() {
+        let result = settle_kana_input("");
+        assert!(matches!(result, KanaSettle::Settled(k) if k.is_empty()));
+    }
+
+    // #endregion settle_kana_input
+
+    // #region TodayProgress
+
+    #[test]
+    fn today_progress_records_and_displays() {
+        let mut today = TodayProgress::default();
+        assert_eq!("Today: 0 reviews, 100%", today.to_string());
+
+        today.record(true);
+        assert_eq!("Today: 1 review, 100%", today.to_string());
+
+        today.record(false);
+        today.record(true);
+        assert_eq!("Today: 3 reviews, 66%", today.to_string());
+    }
+
+    // #endregion TodayProgress
+
+    // #region reconcile_reviews
+
+    #[test]
+    fn reconcile_reviews_splits_recorded_and_unsubmitted() {
+        let now = Utc::now();
+        let local_review = |assignment_id, status| NewReview {
+            id: None,
+            assignment_id,
+            available_at: Some(now),
+            created_at: now,
+            incorrect_meaning_answers: 0,
+            incorrect_reading_answers: 0,
+            status,
+            is_lesson: false,
+            srs_stage: None,
+        };
+        let server_review = |assignment_id, created_at| Review { data: ReviewData { assignment_id, created_at } };
+        let local = vec![
+            local_review(1, ReviewStatus::Done),
+            local_review(2, ReviewStatus::Done),
+            local_review(3, ReviewStatus::Done),
+            local_review(4, ReviewStatus::MeaningDone),
+        ];
+        // assignment 2 was reviewed on the server, but before this local review
+        let server = vec![server_review(1, now + chrono::Duration::seconds(5)), server_review(2, now - chrono::Duration::days(1))];
+
+        let result = reconcile_reviews(&local, &server);
+
+        assert_eq!(ReviewReconciliation { recorded: vec![1], unsubmitted: vec![2, 3], in_progress: 1 }, result);
+    }
+
+    // #endregion reconcile_reviews
+
+    // #region new_reviews is_lesson
+
+    #[test]
+    fn new_reviews_lessons_and_reviews_load_separately() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::wanisql::setup_db(&conn).unwrap();
+        let review = |assignment_id, is_lesson, available_at| NewReview {
+            id: None,
+            assignment_id,
+            available_at,
+            created_at: Utc::now(),
+            incorrect_meaning_answers: 0,
+            incorrect_reading_answers: 1,
+            status: ReviewStatus::Done,
+            is_lesson,
+            srs_stage: if is_lesson { None } else { Some(1) },
+        };
+        let mut tx = conn.transaction().unwrap();
+        crate::wanisql::store_review(&review(1, true, None), &mut tx).unwrap();
+        // a review missing its available_at is still a review
+        crate::wanisql::store_review(&review(2, false, None), &mut tx).unwrap();
+        crate::wanisql::store_review(&review(3, false, Some(Utc::now())), &mut tx).unwrap();
+        tx.commit().unwrap();
+
+        let load = |sql| conn.prepare(sql).unwrap()
+            .query_map([], |r| Ok(crate::wanisql::parse_review(r).unwrap()))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect::<Vec<_>>();
+        let lessons = load(crate::wanisql::SELECT_LESSONS);
+        let reviews = load(crate::wanisql::SELECT_REVIEWS);
+
+        assert_eq!(vec![1], lessons.iter().map(|r| r.assignment_id).collect::<Vec<_>>());
+        assert!(lessons.iter().all(|r| r.is_lesson));
+        assert_eq!(vec![2, 3], reviews.iter().map(|r| r.assignment_id).collect::<Vec<_>>());
+        assert!(reviews.iter().all(|r| !r.is_lesson));
+        assert!(lessons.iter().all(|r| r.srs_stage.is_none()));
+        assert!(reviews.iter().all(|r| r.srs_stage == Some(1)));
+    }
+
+    // #endregion new_reviews is_lesson
+
+    // #region available assignments
+
+    #[test]
+    fn available_assignments_leave_out_unstarted_lessons() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::wanisql::setup_db(&conn).unwrap();
+        let now = Utc::now();
+        let assignment = |id, started_at| Assignment {
+            id,
+            data: AssignmentData {
+                available_at: Some(now - chrono::Duration::hours(1)),
+                created_at: now,
+                hidden: false,
+                srs_stage: 1,
+                started_at,
+                subject_id: id,
+                subject_type: SubjectType::Kanji,
+                unlocked_at: Some(now),
+                burned_at: None,
+            },
+        };
+        let mut tx = conn.transaction().unwrap();
+        crate::wanisql::store_assignment(assignment(1, Some(now)), &mut tx).unwrap();
+        // an available_at on an assignment that was never started shouldn't make it a review
+        crate::wanisql::store_assignment(assignment(2, None), &mut tx).unwrap();
+        tx.commit().unwrap();
+
+        let ids = |sql, params: &[i64]| conn.prepare(sql).unwrap()
+            .query_map(rusqlite::params_from_iter(params), |r| Ok(crate::wanisql::parse_assignment(r).unwrap().id))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![1], ids(crate::wanisql::SELECT_AVAILABLE_ASSIGNMENTS, &[now.timestamp()]));
+        assert_eq!(vec![1], ids(crate::wanisql::SELECT_AVAILABLE_ASSIGNMENTS_BETWEEN, &[(now - chrono::Duration::days(1)).timestamp(), now.timestamp()]));
+    }
+
+    // #endregion available assignments
+
+    #[test]
+    fn format_wait_units() {
+        assert_eq!("less than a minute", format_wait(59));
+        assert_eq!("5m", format_wait(5 * 60 + 30));
+        assert_eq!("2h 15m", format_wait(2 * 3600 + 15 * 60));
+        assert_eq!("3d 4h", format_wait(3 * 86400 + 4 * 3600 + 59 * 60));
+    }
+
+    #[test]
+    fn clean_config_line_strips_bom_and_cr() {
+        assert_eq!("auth: abc", clean_config_line("\u{feff}auth: abc\r"));
+        assert_eq!("shuffle: batch", clean_config_line("shuffle: batch "));
+        assert_eq!("colorblind: true", clean_config_line("colorblind: true"));
+    }
+
+    #[test]
+    fn srs_stage_name_apprentice_stages() {
+        assert_eq!("Apprentice I", srs_stage_name(1));
+        assert_eq!("Apprentice IV", srs_stage_name(4));
+    }
+
+    #[test]
+    fn parse_srs_range_forms() {
+        assert_eq!(Ok(1..=4), parse_srs_range("apprentice"));
+        assert_eq!(Ok(7..=9), parse_srs_range("Master+"));
+        assert_eq!(Ok(2..=6), parse_srs_range("2-guru"));
+        assert_eq!(Ok(5..=5), parse_srs_range("5"));
+    }
+
+    #[test]
+    fn parse_srs_range_invalid() {
+        assert!(parse_srs_range("10").is_err());
+        assert!(parse_srs_range("0").is_err());
+        assert!(parse_srs_range("master-apprentice").is_err());
+        assert!(parse_srs_range("expert").is_err());
+    }
+
+    #[test]
+    fn srs_stage_name_out_of_range() {
+        assert_eq!("Unknown", srs_stage_name(10));
+        assert_eq!("Unknown", srs_stage_name(-1));
+    }
+
+    #[test]
+    fn renders_in_common_fonts_common_kanji() {
+        assert!(renders_in_common_fonts("大人"));
+        assert!(renders_in_common_fonts("ト"));
+    }
+
+    #[test]
+    fn renders_in_common_fonts_rare_codepoints() {
+        assert!(!renders_in_common_fonts("\u{E000}"));
+        assert!(!renders_in_common_fonts("\u{20B9F}"));
+    }
+
+    #[test]
+    fn anki_tsv_row_kanji() {
+        let mut kanji = get_standard_kanji();
+        kanji.data.characters = "大".into();
+        kanji.data.meaning_mnemonic = "A <radical>tab</radical>\tand\na newline".into();
+        kanji.data.reading_mnemonic = "Reading".into();
+        let row = anki_tsv_row(&Subject::Kanji(kanji));
+        assert_eq!("大\taccepted / はがねの\tA tab and a newline Reading", row);
+    }
+
+    // #region unrecognized_subject_keys
+
+    #[test]
+    fn unrecognized_subject_keys_known_kana_vocab() {
+        let raw = serde_json::json!({
+            "id": 1,
+            "object": "kana_vocabulary",
+            "url": "https://api.wanikani.com/v2/subjects/1",
+            "data_updated_at": "2023-01-01T00:00:00.000000Z",
+            "data": {
+                "characters": "ある",
+                "meanings": [],
+                "level": 1,
+            }
+        });
+        assert!(unrecognized_subject_keys(&raw).is_empty());
+    }
+
+    #[test]
+    fn unrecognized_subject_keys_new_fields() {
+        let raw = serde_json::json!({
+            "id": 1,
+            "object": "kanji",
+            "new_top_level": true,
+            "data": {
+                "characters": "大",
+                "readings": [],
+                "new_data_field": 5,
+            }
+        });
+        let mut result = unrecognized_subject_keys(&raw);
+        result.sort();
+        assert_eq!(vec!["kanji.data.new_data_field", "kanji.new_top_level"], result);
+    }
+
+    #[test]
+    fn unrecognized_subject_keys_type_specific() {
+        // readings exist on kanji, but not on radicals
+        let raw = serde_json::json!({
+            "object": "radical",
+            "data": { "readings": [] }
+        });
+        assert_eq!(vec!["radical.data.readings"], unrecognized_subject_keys(&raw));
+    }
+
+    // #endregion unrecognized_subject_keys
+
+    // #region choose_is_meaning
+
+    #[test]
+    fn choose_is_meaning_not_started_rolls() {
+        assert!(choose_is_meaning(ReviewStatus::NotStarted, None, || true));
+        assert!(!choose_is_meaning(ReviewStatus::NotStarted, None, || false));
+    }
+
+    #[test]
+    fn choose_is_meaning_asks_remaining_half() {
+        assert!(!choose_is_meaning(ReviewStatus::MeaningDone, Some(true), || panic!()));
+        assert!(choose_is_meaning(ReviewStatus::ReadingDone, Some(false), || panic!()));
+    }
+
+    #[test]
+    fn choose_is_meaning_sequence_with_miss() {
+        // first roll picks meaning
+        let first = choose_is_meaning(ReviewStatus::NotStarted, None, || true);
+        assert!(first);
+
+        // meaning missed, so status stays NotStarted. Remembered half is asked again without rolling
+        let second = choose_is_meaning(ReviewStatus::NotStarted, Some(first), || panic!());
+        assert!(second);
+
+        // meaning answered, so reading is asked even if the last half was meaning
+        let third = choose_is_meaning(ReviewStatus::MeaningDone, Some(second), || panic!());
+        assert!(!third);
+
+        // reading missed, still only reading is left
+        let fourth = choose_is_meaning(ReviewStatus::MeaningDone, Some(third), || panic!());
+        assert!(!fourth);
+    }
+
+    // #endregion choose_is_meaning
+
+    // #region AdaptiveBatch
+
+    const ADAPTIVE: AdaptiveBatch = AdaptiveBatch { min_size: 5, max_size: 30, low_accuracy: 75, high_accuracy: 90 };
+
+    #[test]
+    fn adaptive_batch_no_guesses_keeps_size() {
+        assert_eq!(20, ADAPTIVE.next_size(20, 0, 0));
+    }
+
+    #[test]
+    fn adaptive_batch_shrinks_when_accuracy_low() {
+        assert_eq!(15, ADAPTIVE.next_size(20, 10, 5));
+    }
+
+    #[test]
+    fn adaptive_batch_grows_when_accuracy_high() {
+        assert_eq!(25, ADAPTIVE.next_size(20, 20, 0));
+    }
+
+    #[test]
+    fn adaptive_batch_keeps_size_between_thresholds() {
+        assert_eq!(20, ADAPTIVE.next_size(20, 10, 2));
+    }
+
+    #[test]
+    fn adaptive_batch_stays_within_bounds() {
+        assert_eq!(5, ADAPTIVE.next_size(5, 10, 9));
+        assert_eq!(30, ADAPTIVE.next_size(30, 10, 0));
+        assert_eq!(30, ADAPTIVE.next_size(100, 10, 2));
+    }
+
+    // #endregion AdaptiveBatch
+
+    // #region Confusion
+
+    #[test]
+    fn confusion_display_meaning() {
+        let c = Confusion { expected: "州".into(), is_meaning: true, guess: "river".into(), matched: "川".into(), times: 3 };
+        assert_eq!("You answered 'river' (川's meaning) for 州 3 times", c.to_string());
+    }
+
+    #[test]
+    fn confusion_display_reading_once() {
+        let c = Confusion { expected: "人".into(), is_meaning: false, guess: "にゅう".into(), matched: "入".into(), times: 1 };
+        assert_eq!("You answered 'にゅう' (入's reading) for 人 1 time", c.to_string());
+    }
+
+    // #endregion Confusion
+
+    // #region fallback_char_lines
+
+    #[test]
+    fn fallback_char_lines_keeps_characters() {
+        let vocab = Subject::Vocab(get_vocab(vec![], vec![], vec![]));
+        assert_eq!(vec!["鋼の".to_owned()], fallback_char_lines(&vocab, vec!["鋼の".into()]));
+    }
+
+    #[test]
+    fn fallback_char_lines_uses_primary_meaning_without_slug() {
+        let meanings = vec![Meaning { meaning: "steel".into(), primary: true, accepted_answer: true }];
+        let vocab = Subject::Vocab(get_vocab(meanings, vec![], vec![]));
+        assert_eq!(vec!["steel (characters missing)".to_owned()], fallback_char_lines(&vocab, vec!["".into()]));
+    }
+
+    #[test]
+    fn fallback_char_lines_prefers_slug() {
+        let mut vocab = get_vocab(vec![], vec![], vec![]);
+        vocab.data.slug = "鋼".into();
+        assert_eq!(vec!["鋼 (characters missing)".to_owned()], fallback_char_lines(&Subject::Vocab(vocab), vec![" ".into()]));
+    }
+
+    // #endregion fallback_char_lines
+
+    // #region tree_lines
+
+    #[test]
+    fn tree_lines_draws_groups() {
+        let groups = [
+            ("Radicals", vec!["日 sun".to_owned()]),
+            ("Kanji", vec![]),
+            ("Vocab", vec!["日本 Japan".to_owned(), "毎日 every day".to_owned()]),
+        ];
+        let expected = vec![
+            "日 sun",
+            "\u{251c}\u{2500} Radicals",
+            "\u{2502}  \u{2514}\u{2500} 日 sun",
+            "\u{2514}\u{2500} Vocab",
+            "   \u{251c}\u{2500} 日本 Japan",
+            "   \u{2514}\u{2500} 毎日 every day",
+        ];
+        assert_eq!(expected, tree_lines("日 sun", &groups));
+    }
+
+    // #endregion tree_lines
+
+    // #region review stats
+
+    #[test]
+    fn format_accuracy_precisions() {
+        assert_eq!("99%", format_accuracy(999, 1000, PercentPrecision::Truncate));
+        assert_eq!("100%", format_accuracy(999, 1000, PercentPrecision::Round));
+        assert_eq!("99.9%", format_accuracy(999, 1000, PercentPrecision::OneDecimal));
+        assert_eq!("67%", format_accuracy(2, 3, PercentPrecision::Round));
+        assert_eq!("-", format_accuracy(0, 0, PercentPrecision::OneDecimal));
+    }
+
+    #[test]
+    fn format_percent_of_total() {
+        assert_eq!("87.5%", format_percent(7, 8));
+        assert_eq!("100.0%", format_percent(3, 3));
+        assert_eq!("-", format_percent(0, 0));
+    }
+
+    #[test]
+    fn review_history_groups_by_level() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::wanisql::setup_db(&conn).unwrap();
+        conn.execute("insert into kanji (id, aux_meanings, created_at, document_url, lesson_position, level, meaning_mnemonic, meanings, slug, srs_id, amalgamation_subject_ids, characters, component_subject_ids, meaning_hint, reading_hint, reading_mnemonic, readings, visually_similar_subject_ids)
+                     values (1, '[]', '', '', 0, 3, '', '[]', '', 1, '[]', '', '[]', '', '', '', '[]', '[]')", []).unwrap();
+        for (correct, incorrect_meaning) in [(1, 0), (0, 1), (1, 0)] {
+            conn.execute(crate::wanisql::INSERT_REVIEW_HISTORY, rusqlite::params![1, correct, 0, incorrect_meaning, 0]).unwrap();
+        }
+        // history for a subject that isn't synced is left out
+        conn.execute(crate::wanisql::INSERT_REVIEW_HISTORY, rusqlite::params![2, 1, 0, 0, 0]).unwrap();
+
+        let groups = conn.prepare(crate::wanisql::SELECT_REVIEW_HISTORY_BY_LEVEL).unwrap()
+            .query_map([i64::MIN, i64::MAX], |r| Ok(crate::wanisql::parse_accuracy_group(r).unwrap()))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![AccuracyGroup { key: 3, reviews: 3, correct: 2 }], groups);
+    }
+
+    #[test]
+    fn parse_stats_period_days_or_all() {
+        assert_eq!(Ok(StatsPeriod::Days(7)), parse_stats_period("7d"));
+        assert_eq!(Ok(StatsPeriod::All), parse_stats_period("all"));
+        assert!(parse_stats_period("0d").is_err());
+        assert!(parse_stats_period("7").is_err());
+    }
+
+    #[test]
+    fn stats_window_from_period_and_dates() {
+        use chrono::TimeZone;
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        assert_eq!((i64::MIN, i64::MAX), stats_window(StatsPeriod::All, None, None, now));
+        assert_eq!((Utc.with_ymd_and_hms(2024, 3, 3, 12, 0, 0).unwrap().timestamp(), i64::MAX), stats_window(StatsPeriod::Days(7), None, None, now));
+        // --from replaces the period's start, and --to includes the whole day
+        assert_eq!((Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap().timestamp(), Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap().timestamp() - 1),
+            stats_window(StatsPeriod::Days(7), Some(day(1)), Some(day(4)), now));
+    }
+
+    // #endregion review stats
+
+    // #region reclassified subjects
+
+    #[test]
+    fn storing_subject_removes_it_from_old_type() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::wanisql::setup_db(&conn).unwrap();
+        let kana_vocab = get_standard_kana_vocab();
+        conn.execute("insert into kanji (id, aux_meanings, created_at, document_url, lesson_position, level, meaning_mnemonic, meanings, slug, srs_id, amalgamation_subject_ids, characters, component_subject_ids, meaning_hint, reading_hint, reading_mnemonic, readings, visually_similar_subject_ids)
+                     values (?1, '[]', '', '', 0, 3, '', '[]', '', 1, '[]', '', '[]', '', '', '', '[]', '[]')", [kana_vocab.id]).unwrap();
+
+        let mut tx = conn.transaction().unwrap();
+        crate::wanisql::store_kana_vocab(kana_vocab, &mut tx).unwrap();
+        tx.commit().unwrap();
+
+        let count = |table: &str| conn.query_row(&format!("select count(*) from {}", table), [], |r| r.get::<usize, i32>(0)).unwrap();
+        assert_eq!(0, count("kanji"));
+        assert_eq!(1, count("kana_vocab"));
+    }
+
+    // #endregion reclassified subjects
+
+    // #region migrate_db
+
+    #[test]
+    fn migrate_db_failed_step_keeps_old_version() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::wanisql::setup_db(&conn).unwrap();
+        // a version 10 DB, except new_reviews already has the version 12 column, so that step fails
+        conn.execute("drop table review_history", []).unwrap();
+        conn.execute("create table review_history (id integer primary key, subject_id integer not null, correct integer not null, created_at integer not null)", []).unwrap();
+        conn.pragma_update(None, "user_version", 10).unwrap();
+
+        assert!(crate::wanisql::migrate_db(&conn).is_err());
+
+        // the version 11 step that succeeded was rolled back along with the version
+        let version: i32 = conn.pragma_query_value(None, "user_version", |r| r.get(0)).unwrap();
+        assert_eq!(10, version);
+        assert!(conn.prepare("select incorrect_meaning from review_history").is_err());
+    }
+
+    // #endregion migrate_db
+
+    // #region cached_ascii_is_valid
+
+    #[test]
+    fn cached_ascii_is_valid_rejects_empty_and_partial_files() {
+        let dir = std::env::temp_dir().join(format!("wani_test_ascii_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let check = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            cached_ascii_is_valid(&path)
+        };
+        // an empty file (e.g. from an interrupted conversion) is regenerated
+        assert!(!check("empty.txt", ""));
+        assert!(!check("blank.txt", "   \n   \n"));
+        assert!(!check("partial.txt", "#### \n## #"));
+        assert!(check("valid.txt", "#### \n ## #\n"));
+        assert!(check("ragged.txt", "####\n ##\n#\n"));
+        assert!(!cached_ascii_is_valid(&dir.join("missing.txt")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // #endregion cached_ascii_is_valid
+
+    // #region parse_api_revision
+
+    #[test]
+    fn parse_api_revision_accepts_dates() {
+        assert_eq!(Ok("20170710".to_owned()), parse_api_revision("20170710"));
+        assert!(parse_api_revision("2017071").is_err());
+        assert!(parse_api_revision("20171310").is_err());
+        assert!(parse_api_revision("+2017071").is_err());
+    }
+
+    // #endregion parse_api_revision
+
+    // #region parse_terminal_size
+
+    #[test]
+    fn parse_terminal_size_reads_columns_by_rows() {
+        assert_eq!(Ok(Some((40, 10))), parse_terminal_size("40x10"));
+        assert_eq!(Ok(Some((80, 24))), parse_terminal_size(" 80 X 24 "));
+        assert_eq!(Ok(None), parse_terminal_size("off"));
+        assert_eq!(Ok(None), parse_terminal_size("0x0"));
+        assert!(parse_terminal_size("40").is_err());
+        assert!(parse_terminal_size("40x-1").is_err());
+    }
+
+    // #endregion parse_terminal_size
+
+    // #region locale_is_utf8
+
+    #[test]
+    fn locale_is_utf8_uses_first_set_variable() {
+        assert!(locale_is_utf8(None, None, Some("en_US.UTF-8")));
+        assert!(locale_is_utf8(Some(""), None, Some("ja_JP.utf8")));
+        assert!(!locale_is_utf8(Some("C"), None, Some("en_US.UTF-8")));
+        assert!(!locale_is_utf8(None, Some("POSIX"), None));
+        assert!(!locale_is_utf8(None, None, None));
+    }
+
+    // #endregion locale_is_utf8
+
+    // #region audio_voices_line
+
+    #[test]
+    fn audio_voices_line_lists_each_actor_once() {
+        let audio = |id: i32, name: &str, gender: &str, content_type: &str| PronunciationAudio {
+            url: String::new(),
+            content_type: content_type.to_owned(),
+            metadata: PronunciationMetadata {
+                gender: gender.to_owned(),
+                source_id: 0,
+                pronunciation: "さかな".to_owned(),
+                voice_actor_id: id,
+                voice_actor_name: name.to_owned(),
+                voice_description: String::new(),
+            },
+        };
+        let audios = [
+            audio(1, "Kyoko", "female", "audio/mpeg"),
+            audio(1, "Kyoko", "female", "audio/ogg"),
+            audio(2, "Kenichi", "male", "audio/mpeg"),
+        ];
+        assert_eq!(Some("Audio (2): Kyoko (female), Kenichi (male)".to_owned()), audio_voices_line(&audios));
+        assert_eq!(None, audio_voices_line(&[]));
+    }
+
+    // #endregion audio_voices_line
+
+    // #region is_preferred_voice
+
+    #[test]
+    fn is_preferred_voice_matches_name_or_gender() {
+        let metadata = PronunciationMetadata {
+            gender: "female".to_owned(),
+            source_id: 0,
+            pronunciation: "さかな".to_owned(),
+            voice_actor_id: 1,
+            voice_actor_name: "Kyoko".to_owned(),
+            voice_description: String::new(),
+        };
+        assert!(is_preferred_voice(&metadata, "kyoko"));
+        assert!(is_preferred_voice(&metadata, "Female"));
+        assert!(!is_preferred_voice(&metadata, "male"));
+        assert!(!is_preferred_voice(&metadata, "Kenichi"));
+    }
+
+    // #endregion is_preferred_voice
+
+    // #region custom deck
+
+    #[test]
+    fn parse_custom_deck_with_header() {
+        let csv = "characters,meanings,readings\n勉強, study;studying ,べんきょう\n\nありがとう,thank you,\n";
+        let expected = vec![
+            CustomSubject { id: 0, characters: "勉強".into(), meanings: vec!["study".into(), "studying".into()], readings: vec!["べんきょう".into()] },
+            CustomSubject { id: 0, characters: "ありがとう".into(), meanings: vec!["thank you".into()], readings: vec![] },
+        ];
+        assert_eq!(Ok(expected), parse_custom_deck(csv));
+    }
+
+    #[test]
+    fn parse_custom_deck_reports_every_bad_line() {
+        let csv = "勉強,study\n,study,べんきょう\n勉強,study,benkyou\n";
+        let errors = parse_custom_deck(csv).unwrap_err();
+        assert_eq!(3, errors.len());
+        assert!(errors[0].starts_with("Line 1:"));
+        assert!(errors[1].starts_with("Line 2:"));
+        assert!(errors[2].starts_with("Line 3:"));
+    }
+
+    #[test]
+    fn custom_subject_is_graded() {
+        let custom = CustomSubject { id: 3, characters: "勉強".into(), meanings: vec!["study".into()], readings: vec!["べんきょう".into()] };
+        let subject = custom.to_subject();
+        assert!(matches!(subject, Subject::Vocab(Vocab { id: -3, .. })));
+        assert!(matches!(is_correct_answer(&subject, "study", true, "", &[], AnswerOptions::default()), AnswerResult::Correct));
+        assert!(matches!(is_correct_answer(&subject, "べんきょう", false, "", &[], AnswerOptions::default()), AnswerResult::Correct));
+    }
+
+    // #endregion custom deck
+
     // #region test edit_distance
 
     #[test]
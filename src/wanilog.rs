@@ -0,0 +1,39 @@
+use std::{fmt::Display, fs::{File, OpenOptions}, io::{self, Write}, path::Path, sync::{Mutex, OnceLock}};
+use chrono::Utc;
+
+/// session log file, set up from the log_file config. Nothing is logged when unset
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// opens (or appends to) the log file and writes a header line with the wani version
+pub(crate) fn init(path: &Path) -> io::Result<()> {
+    if LOG_FILE.get().is_some() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{} start version={} commit={}", Utc::now().to_rfc3339(), env!("CARGO_PKG_VERSION"), env!("WANI_GIT_HASH"))?;
+    let _ = LOG_FILE.set(Mutex::new(file));
+    Ok(())
+}
+
+/// writes a timestamped "event key=value ..." line. Values with spaces are quoted
+pub(crate) fn log(event: &str, fields: &[(&str, &dyn Display)]) {
+    let Some(file) = LOG_FILE.get() else {
+        return;
+    };
+
+    let mut line = format!("{} {}", Utc::now().to_rfc3339(), event);
+    for (key, value) in fields {
+        let value = value.to_string();
+        if value.is_empty() || value.contains([' ', '"', '=']) {
+            line.push_str(&format!(" {}={:?}", key, value));
+        }
+        else {
+            line.push_str(&format!(" {}={}", key, value));
+        }
+    }
+
+    if let Ok(mut file) = file.lock() {
+        let _ = writeln!(file, "{}", line);
+    }
+}
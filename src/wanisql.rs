@@ -34,11 +34,21 @@ pub(crate) struct CacheInfo {
     pub etag: Option<String>,
     pub last_modified: Option<String>,
     pub updated_after: Option<String>,
+    /// highest level included in the last sync, if it was level-capped
+    pub level_cap: Option<i32>,
 }
 
 pub const CACHE_TYPE_SUBJECTS: usize = 0;
 pub const CACHE_TYPE_ASSIGNMENTS: usize = 1;
 pub const CACHE_TYPE_USER: usize = 2;
+pub const CACHE_TYPE_STUDY_MATERIALS: usize = 3;
+
+pub(crate) const SET_CACHE_LEVEL_CAP: &str = "update cache_info set level_cap = ?1 where id = ?2;";
+
+/// returns the value of the api's "levels" filter for levels 1 through max_level
+pub(crate) fn levels_query(max_level: i32) -> String {
+    (1..=max_level).map(|l| l.to_string()).collect::<Vec<_>>().join(",")
+}
 
 pub(crate) fn setup_db(c: &Connection) -> Result<(), rusqlite::Error> {
     // Arrays of non-id'ed objects will be stored as json
@@ -50,14 +60,16 @@ pub(crate) fn setup_db(c: &Connection) -> Result<(), rusqlite::Error> {
             id integer primary key,
             etag text,
             last_modified text,
-            updated_after text
+            updated_after text,
+            level_cap integer
         )", [])?;
 
-    c.execute("insert or ignore into cache_info (id) values (?1),(?2),(?3)", 
+    c.execute("insert or ignore into cache_info (id) values (?1),(?2),(?3),(?4)", 
               params![
                 CACHE_TYPE_SUBJECTS, 
                 CACHE_TYPE_ASSIGNMENTS, 
                 CACHE_TYPE_USER, 
+                CACHE_TYPE_STUDY_MATERIALS, 
               ])?;
 
     c.execute(CREATE_REVIEWS_TBL, [])?;
@@ -68,9 +80,77 @@ pub(crate) fn setup_db(c: &Connection) -> Result<(), rusqlite::Error> {
     c.execute(CREATE_ASSIGNMENTS_TBL, [])?;
     c.execute(CREATE_ASSIGNMENTS_INDEX, [])?;
     c.execute(CREATE_USER_TBL, [])?;
+    c.execute(CREATE_WRONG_ANSWERS_TBL, [])?;
+    c.execute(CREATE_SUSPENDED_TBL, [])?;
+    c.execute(CREATE_STUDY_MATERIALS_TBL, [])?;
+    c.execute(CREATE_CUSTOM_SUBJECTS_TBL, [])?;
+    c.execute(CREATE_REVIEW_HISTORY_TBL, [])?;
+    c.execute(CREATE_META_TBL, [])?;
+    c.pragma_update(None, "user_version", DB_VERSION)?;
     Ok(())
 }
 
+/// schema version of a freshly set up DB, stored in sqlite's user_version
+pub const DB_VERSION: i32 = 12;
+
+/// brings a DB created by an older version of wani up to DB_VERSION
+/// All steps and the version bump run in one transaction, so a failed step (like "database is
+/// locked" from another wani) leaves the DB at its old version to be migrated again next time
+pub(crate) fn migrate_db(c: &Connection) -> Result<(), rusqlite::Error> {
+    let c = c.unchecked_transaction()?;
+    let version: i32 = c.pragma_query_value(None, "user_version", |r| r.get(0))?;
+    if version < 1 {
+        c.execute("alter table assignments add column burned_at text", [])?;
+    }
+    if version < 2 {
+        c.execute("alter table cache_info add column level_cap integer", [])?;
+    }
+    if version < 3 {
+        c.execute(CREATE_WRONG_ANSWERS_TBL, [])?;
+    }
+    if version < 4 {
+        c.execute(CREATE_SUSPENDED_TBL, [])?;
+    }
+    if version < 5 {
+        c.execute(CREATE_STUDY_MATERIALS_TBL, [])?;
+        c.execute("insert or ignore into cache_info (id) values (?1)", params![CACHE_TYPE_STUDY_MATERIALS])?;
+    }
+    if version < 6 {
+        if version >= 5 {
+            c.execute("alter table study_materials add column meaning_synonyms text not null default '[]'", [])?;
+        }
+        // Synonyms weren't stored before, so fetch all study materials again
+        c.execute("update cache_info set etag = null, last_modified = null, updated_after = null where id = ?1", params![CACHE_TYPE_STUDY_MATERIALS])?;
+    }
+    if version < 7 {
+        c.execute(CREATE_CUSTOM_SUBJECTS_TBL, [])?;
+    }
+    if version < 8 {
+        c.execute("alter table new_reviews add column is_lesson integer not null default 0", [])?;
+        // Before this column, lessons were the reviews without an available_at
+        c.execute("update new_reviews set is_lesson = 1 where available_at is null", [])?;
+    }
+    if version < 9 {
+        c.execute(CREATE_REVIEW_HISTORY_TBL, [])?;
+    }
+    if version < 10 {
+        c.execute(CREATE_META_TBL, [])?;
+    }
+    if (9..11).contains(&version) {
+        // Older history didn't record which half was missed, so these stay null for it
+        c.execute("alter table review_history add column incorrect_meaning integer", [])?;
+        c.execute("alter table review_history add column incorrect_reading integer", [])?;
+    }
+    if version < 12 {
+        // Reviews started before this column can't tell if their assignment changed, so stay null
+        c.execute("alter table new_reviews add column srs_stage integer", [])?;
+    }
+    if version < DB_VERSION {
+        c.pragma_update(None, "user_version", DB_VERSION)?;
+    }
+    c.commit()
+}
+
 pub(crate) const CREATE_USER_TBL: &str = "create table if not exists user (
             id integer primary key,
             user text not null
@@ -98,7 +178,9 @@ pub(crate) const CREATE_REVIEWS_TBL: &str = "create table if not exists new_revi
             incorrect_meaning_answers int not null,
             incorrect_reading_answers int not null,
             status integer not null,
-            available_at text
+            available_at text,
+            is_lesson integer not null default 0,
+            srs_stage integer
         )";
 
 pub(crate) const INSERT_REVIEW: &str = "replace into new_reviews
@@ -108,8 +190,10 @@ pub(crate) const INSERT_REVIEW: &str = "replace into new_reviews
                              incorrect_meaning_answers,
                              incorrect_reading_answers,
                              status,
-                             available_at)
-                            values (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+                             available_at,
+                             is_lesson,
+                             srs_stage)
+                            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
 
 pub(crate) const INSERT_REVIEW_NO_ID: &str = "insert into new_reviews
                             (assignment_id,
@@ -117,8 +201,10 @@ pub(crate) const INSERT_REVIEW_NO_ID: &str = "insert into new_reviews
                              incorrect_meaning_answers,
                              incorrect_reading_answers,
                              status,
-                             available_at)
-                            values (?1, ?2, ?3, ?4, ?5, ?6)";
+                             available_at,
+                             is_lesson,
+                             srs_stage)
+                            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
 
 pub(crate) const SELECT_REVIEWS: &str = "select 
                             id,
@@ -127,7 +213,9 @@ pub(crate) const SELECT_REVIEWS: &str = "select
                             incorrect_meaning_answers,
                             incorrect_reading_answers,
                             status,
-                            available_at from new_reviews where available_at is not null;";
+                            available_at,
+                            is_lesson,
+                            srs_stage from new_reviews where is_lesson = 0;";
 
 pub(crate) const SELECT_LESSONS: &str = "select 
                             id,
@@ -136,7 +224,9 @@ pub(crate) const SELECT_LESSONS: &str = "select
                             incorrect_meaning_answers,
                             incorrect_reading_answers,
                             status,
-                            available_at from new_reviews where available_at is null;";
+                            available_at,
+                            is_lesson,
+                            srs_stage from new_reviews where is_lesson = 1;";
 
 pub(crate) const REMOVE_REVIEW: &str = "delete from new_reviews where assignment_id = ?1;";
 
@@ -155,6 +245,8 @@ pub(crate) fn parse_review(r: &rusqlite::Row<'_>) -> Result<wanidata::NewReview,
             else { 
                 None 
             },
+        is_lesson: r.get::<usize, bool>(7)?,
+        srs_stage: r.get::<usize, Option<i32>>(8)?,
     });
 }
 
@@ -170,6 +262,8 @@ pub(crate) fn store_review(r: &wanidata::NewReview, stmt: &mut Transaction<'_>)
             r.incorrect_reading_answers,
             status,
             if let Some(available_at) = r.available_at { Some(available_at.to_rfc3339()) } else { None },
+            r.is_lesson,
+            r.srs_stage,
             );
         return stmt.execute(INSERT_REVIEW, p);
     }
@@ -181,6 +275,8 @@ pub(crate) fn store_review(r: &wanidata::NewReview, stmt: &mut Transaction<'_>)
             r.incorrect_reading_answers,
             status,
             if let Some(available_at) = r.available_at { Some(available_at.to_rfc3339()) } else { None },
+            r.is_lesson,
+            r.srs_stage,
             );
         return stmt.execute(INSERT_REVIEW_NO_ID, p);
     }
@@ -195,7 +291,8 @@ pub(crate) const CREATE_ASSIGNMENTS_TBL: &str = "create table if not exists assi
             started_at text,
             subject_id integer not null,
             subject_type integer not null,
-            unlocked_at text
+            unlocked_at text,
+            burned_at text
         )";
 
 pub(crate) const CREATE_ASSIGNMENTS_INDEX: &str = 
@@ -211,8 +308,9 @@ pub(crate) const INSERT_ASSIGNMENT: &str = "replace into assignments
                              started_at,
                              subject_id,
                              subject_type,
-                             unlocked_at)
-                            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+                             unlocked_at,
+                             burned_at)
+                            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
 
 pub(crate) const SELECT_LESSON_ASSIGNMENTS: &str = "select 
                             id,
@@ -222,9 +320,22 @@ pub(crate) const SELECT_LESSON_ASSIGNMENTS: &str = "select
                             srs_stage,
                             started_at,
                             subject_id,
-                            subject_type from assignments 
+                            subject_type,
+                            burned_at from assignments 
                         where started_at is null and unlocked_at is not null;";
 
+pub(crate) const SELECT_UNLOCKED_ASSIGNMENT_FOR_SUBJECT: &str = "select 
+                            id,
+                            available_at,
+                            created_at,
+                            hidden,
+                            srs_stage,
+                            started_at,
+                            subject_id,
+                            subject_type,
+                            burned_at from assignments 
+                        where subject_id = ?1 and unlocked_at is not null;";
+
 pub(crate) const SELECT_AVAILABLE_ASSIGNMENTS: &str = "select 
                             id,
                             available_at,
@@ -233,8 +344,298 @@ pub(crate) const SELECT_AVAILABLE_ASSIGNMENTS: &str = "select
                             srs_stage,
                             started_at,
                             subject_id,
-                            subject_type from assignments 
-                        where available_at < ?1;";// and started_at is not null;";
+                            subject_type,
+                            burned_at from assignments 
+                        where available_at < ?1 and started_at is not null;";
+
+pub(crate) const SELECT_AVAILABLE_ASSIGNMENTS_BETWEEN: &str = "select 
+                            id,
+                            available_at,
+                            created_at,
+                            hidden,
+                            srs_stage,
+                            started_at,
+                            subject_id,
+                            subject_type,
+                            burned_at from assignments 
+                        where available_at >= ?1 and available_at < ?2 and started_at is not null;";
+
+/// when the next review becomes available, leaving out suspended subjects
+pub(crate) const SELECT_NEXT_AVAILABLE_AT: &str = "select min(available_at) from assignments
+                        where available_at >= ?1 and started_at is not null and hidden = 0
+                            and subject_id not in (select subject_id from suspended);";
+
+pub(crate) fn parse_next_available_at(r: &rusqlite::Row<'_>) -> Result<Option<i64>, WaniSqlError> {
+    Ok(r.get::<usize, Option<i64>>(0)?)
+}
+
+pub(crate) const SELECT_BURNED_ASSIGNMENTS: &str = "select 
+                            id,
+                            available_at,
+                            created_at,
+                            hidden,
+                            srs_stage,
+                            started_at,
+                            subject_id,
+                            subject_type,
+                            burned_at from assignments 
+                        where srs_stage = 9 and hidden = 0;";
+
+pub(crate) const SELECT_APPRENTICE_COUNTS: &str = "select 
+                            srs_stage,
+                            count(*) from assignments 
+                        where srs_stage between 1 and 4 and hidden = 0
+                        group by srs_stage;";
+
+/// wrong review answers, for the confusions report
+pub(crate) const CREATE_WRONG_ANSWERS_TBL: &str = "create table if not exists wrong_answers (
+            id integer primary key,
+            subject_id integer not null,
+            is_meaning integer not null,
+            guess text not null,
+            created_at text not null
+        )";
+
+pub(crate) const INSERT_WRONG_ANSWER: &str = "insert into wrong_answers
+                            (subject_id, is_meaning, guess, created_at)
+                            values (?1, ?2, ?3, ?4)";
+
+/// Wrong answers that match an accepted answer of a different subject.
+/// Returns: expected subject characters, is_meaning, guess, matched subject characters, times
+pub(crate) const SELECT_CONFUSIONS: &str = "with answers as (
+                            select id, characters, meanings as answers, 1 as is_meaning from radicals
+                            union all select id, characters, meanings, 1 from kanji
+                            union all select id, characters, readings, 0 from kanji
+                            union all select id, characters, meanings, 1 from vocab
+                            union all select id, characters, readings, 0 from vocab
+                            union all select id, characters, meanings, 1 from kana_vocab
+                        ),
+                        accepted as (
+                            select distinct a.id, a.characters, a.is_meaning,
+                                lower(trim(coalesce(json_extract(j.value, '$.meaning'), json_extract(j.value, '$.reading')))) as answer
+                            from answers a, json_each(a.answers) j
+                            where json_extract(j.value, '$.accepted_answer')
+                        ),
+                        subjects as (
+                            select id, characters, slug from radicals
+                            union all select id, characters, slug from kanji
+                            union all select id, characters, slug from vocab
+                            union all select id, characters, slug from kana_vocab
+                        )
+                        select
+                            coalesce(s.characters, s.slug),
+                            w.is_meaning,
+                            w.guess,
+                            acc.characters,
+                            count(*) as times from wrong_answers w
+                        join accepted acc on acc.answer = w.guess and acc.is_meaning = w.is_meaning and acc.id != w.subject_id
+                        join subjects s on s.id = w.subject_id
+                        where acc.characters is not null
+                        group by w.subject_id, w.is_meaning, w.guess, acc.id
+                        order by times desc
+                        limit ?1;";
+
+pub(crate) fn parse_confusion(r: &rusqlite::Row<'_>) -> Result<wanidata::Confusion, WaniSqlError> {
+    Ok(wanidata::Confusion {
+        expected: r.get::<usize, String>(0)?,
+        is_meaning: r.get::<usize, bool>(1)?,
+        guess: r.get::<usize, String>(2)?,
+        matched: r.get::<usize, String>(3)?,
+        times: r.get::<usize, usize>(4)?,
+    })
+}
+
+/// every finished (non-practice) review, for stats like today's progress
+pub(crate) const CREATE_REVIEW_HISTORY_TBL: &str = "create table if not exists review_history (
+            id integer primary key,
+            subject_id integer not null,
+            correct integer not null,
+            created_at integer not null,
+            incorrect_meaning integer,
+            incorrect_reading integer
+        )";
+
+pub(crate) const INSERT_REVIEW_HISTORY: &str = "insert into review_history
+                            (subject_id, correct, created_at, incorrect_meaning, incorrect_reading)
+                            values (?1, ?2, ?3, ?4, ?5)";
+
+/// Params: ?1 and ?2 are the first and last created_at to include
+/// Returns: reviews, first-try correct reviews, reviews that recorded misses, reviews with
+/// meaning misses, kanji/vocab reviews that recorded misses, and reviews with reading misses
+pub(crate) const SELECT_REVIEW_HISTORY_TOTALS: &str = "select count(*),
+                            coalesce(sum(h.correct), 0),
+                            count(h.incorrect_meaning),
+                            coalesce(sum(h.incorrect_meaning > 0), 0),
+                            coalesce(sum(h.incorrect_reading is not null and a.subject_type in (1, 2)), 0),
+                            coalesce(sum(h.incorrect_reading > 0), 0)
+                        from review_history h
+                        left join assignments a on a.subject_id = h.subject_id
+                        where h.created_at between ?1 and ?2;";
+
+pub(crate) fn parse_accuracy_totals(r: &rusqlite::Row<'_>) -> Result<wanidata::AccuracyTotals, WaniSqlError> {
+    Ok(wanidata::AccuracyTotals {
+        reviews: r.get::<usize, usize>(0)?,
+        correct: r.get::<usize, usize>(1)?,
+        meaning_reviews: r.get::<usize, usize>(2)?,
+        meaning_misses: r.get::<usize, usize>(3)?,
+        reading_reviews: r.get::<usize, usize>(4)?,
+        reading_misses: r.get::<usize, usize>(5)?,
+    })
+}
+
+/// Params: ?1 is the level
+/// Returns: the ids of every subject at that level, of any type
+pub(crate) const SELECT_SUBJECT_IDS_AT_LEVEL: &str = "select id from radicals where level = ?1
+                        union all select id from kanji where level = ?1
+                        union all select id from vocab where level = ?1
+                        union all select id from kana_vocab where level = ?1;";
+
+pub(crate) fn parse_subject_id(r: &rusqlite::Row<'_>) -> Result<i32, WaniSqlError> {
+    Ok(r.get::<usize, i32>(0)?)
+}
+
+/// Params: ?1 and ?2 are the first and last created_at to include
+/// Returns: subject level, reviews, and first-try correct reviews for each level
+pub(crate) const SELECT_REVIEW_HISTORY_BY_LEVEL: &str = "with levels as (
+                            select id, level from radicals
+                            union all select id, level from kanji
+                            union all select id, level from vocab
+                            union all select id, level from kana_vocab)
+                        select l.level, count(*), sum(h.correct)
+                        from review_history h
+                        join levels l on l.id = h.subject_id
+                        where h.created_at between ?1 and ?2
+                        group by l.level
+                        order by l.level;";
+
+/// Params: ?1 and ?2 are the first and last created_at to include
+/// Returns: current SRS stage, reviews, and first-try correct reviews for each stage
+pub(crate) const SELECT_REVIEW_HISTORY_BY_SRS_STAGE: &str = "select a.srs_stage, count(*), sum(h.correct)
+                        from review_history h
+                        join assignments a on a.subject_id = h.subject_id
+                        where h.created_at between ?1 and ?2
+                        group by a.srs_stage
+                        order by a.srs_stage;";
+
+pub(crate) fn parse_accuracy_group(r: &rusqlite::Row<'_>) -> Result<wanidata::AccuracyGroup, WaniSqlError> {
+    Ok(wanidata::AccuracyGroup {
+        key: r.get::<usize, i32>(0)?,
+        reviews: r.get::<usize, usize>(1)?,
+        correct: r.get::<usize, usize>(2)?,
+    })
+}
+
+/// Returns: number of reviews finished since ?1, and how many of them had no wrong answers
+pub(crate) const SELECT_REVIEW_HISTORY_SINCE: &str = "select count(*), coalesce(sum(correct), 0)
+                        from review_history where created_at >= ?1;";
+
+pub(crate) fn parse_today_progress(r: &rusqlite::Row<'_>) -> Result<wanidata::TodayProgress, WaniSqlError> {
+    Ok(wanidata::TodayProgress {
+        reviews: r.get::<usize, usize>(0)?,
+        correct: r.get::<usize, usize>(1)?,
+    })
+}
+
+/// small bits of local state, by key
+pub(crate) const CREATE_META_TBL: &str = "create table if not exists meta (
+            key text primary key,
+            value text not null
+        )";
+
+/// the last of summary, review, or lesson that was run, for repeat_last_command
+pub(crate) const META_LAST_COMMAND: &str = "last_command";
+
+pub(crate) const INSERT_META: &str = "replace into meta (key, value) values (?1, ?2)";
+
+pub(crate) const SELECT_META: &str = "select value from meta where key = ?1;";
+
+/// subjects the user has parked, client-side, so they're left out of reviews
+pub(crate) const CREATE_SUSPENDED_TBL: &str = "create table if not exists suspended (
+            subject_id integer primary key,
+            suspended_at text not null
+        )";
+
+pub(crate) const INSERT_SUSPENDED: &str = "replace into suspended
+                            (subject_id, suspended_at)
+                            values (?1, ?2)";
+
+pub(crate) const REMOVE_SUSPENDED: &str = "delete from suspended where subject_id = ?1;";
+
+pub(crate) const SELECT_SUSPENDED: &str = "select subject_id from suspended order by suspended_at;";
+
+pub(crate) fn parse_suspended(r: &rusqlite::Row<'_>) -> Result<i32, WaniSqlError> {
+    Ok(r.get::<usize, i32>(0)?)
+}
+
+/// the user's meaning/reading notes, synced from /v2/study_materials
+pub(crate) const CREATE_STUDY_MATERIALS_TBL: &str = "create table if not exists study_materials (
+            id integer primary key,
+            subject_id integer not null,
+            meaning_note text,
+            reading_note text,
+            meaning_synonyms text not null default '[]',
+            hidden integer not null
+        )";
+
+pub(crate) const INSERT_STUDY_MATERIAL: &str = "replace into study_materials
+                            (id, subject_id, meaning_note, reading_note, meaning_synonyms, hidden)
+                            values (?1, ?2, ?3, ?4, ?5, ?6)";
+
+pub(crate) const SELECT_STUDY_MATERIAL: &str = "select id, subject_id, meaning_note, reading_note, meaning_synonyms, hidden
+                            from study_materials where subject_id = ?1 and hidden = 0;";
+
+pub(crate) fn store_study_material(s: wanidata::StudyMaterial, stmt: &mut Transaction<'_>) -> Result<usize, WaniSqlError> {
+    let synonyms = serde_json::to_string(&s.data.meaning_synonyms)?;
+    Ok(stmt.execute(INSERT_STUDY_MATERIAL, params![s.id, s.data.subject_id, s.data.meaning_note, s.data.reading_note, synonyms, s.data.hidden])?)
+}
+
+pub(crate) fn parse_study_material(r: &rusqlite::Row<'_>) -> Result<wanidata::StudyMaterial, WaniSqlError> {
+    Ok(wanidata::StudyMaterial {
+        id: r.get::<usize, i32>(0)?,
+        data: wanidata::StudyMaterialData {
+            subject_id: r.get::<usize, i32>(1)?,
+            meaning_note: r.get::<usize, Option<String>>(2)?,
+            reading_note: r.get::<usize, Option<String>>(3)?,
+            meaning_synonyms: serde_json::from_str(&r.get::<usize, String>(4)?)?,
+            hidden: r.get::<usize, bool>(5)?,
+        },
+    })
+}
+
+/// vocab from the user's own decks. Kept apart from WaniKani subjects and never submitted
+pub(crate) const CREATE_CUSTOM_SUBJECTS_TBL: &str = "create table if not exists custom_subjects (
+            id integer primary key,
+            deck text not null,
+            characters text not null,
+            meanings text not null,
+            readings text not null
+        )";
+
+pub(crate) const INSERT_CUSTOM_SUBJECT: &str = "insert into custom_subjects
+                            (deck, characters, meanings, readings)
+                            values (?1, ?2, ?3, ?4)";
+
+pub(crate) const REMOVE_CUSTOM_DECK: &str = "delete from custom_subjects where deck = ?1;";
+
+pub(crate) const SELECT_CUSTOM_DECK: &str = "select id, characters, meanings, readings from custom_subjects where deck = ?1;";
+
+pub(crate) fn store_custom_subject(deck: &str, s: &wanidata::CustomSubject, stmt: &mut Transaction<'_>) -> Result<usize, WaniSqlError> {
+    Ok(stmt.execute(INSERT_CUSTOM_SUBJECT, params![deck, s.characters, serde_json::to_string(&s.meanings)?, serde_json::to_string(&s.readings)?])?)
+}
+
+pub(crate) fn parse_custom_subject(r: &rusqlite::Row<'_>) -> Result<wanidata::CustomSubject, WaniSqlError> {
+    Ok(wanidata::CustomSubject {
+        id: r.get::<usize, i32>(0)?,
+        characters: r.get::<usize, String>(1)?,
+        meanings: serde_json::from_str(&r.get::<usize, String>(2)?)?,
+        readings: serde_json::from_str(&r.get::<usize, String>(3)?)?,
+    })
+}
+
+/// parses a (srs_stage, count) row
+pub(crate) fn parse_srs_stage_count(r: &rusqlite::Row<'_>) -> Result<(i32, usize), WaniSqlError> {
+    Ok((r.get::<usize, i32>(0)?, r.get::<usize, usize>(1)?))
+}
 
 pub(crate) fn parse_assignment(r: &rusqlite::Row<'_>) -> Result<wanidata::Assignment, WaniSqlError> {
     return Ok(wanidata::Assignment {
@@ -264,6 +665,13 @@ pub(crate) fn parse_assignment(r: &rusqlite::Row<'_>) -> Result<wanidata::Assign
             subject_id: r.get::<usize, i32>(6)?,
             subject_type: wanidata::SubjectType::from(r.get::<usize, usize>(7)?),
             unlocked_at: None,
+            burned_at: 
+                if let Some(t) = r.get::<usize, Option<String>>(8)? { 
+                    Some(DateTime::parse_from_rfc3339(&t)?.with_timezone(&Utc))
+                } 
+                else { 
+                    None 
+                },
         }
     });
 }
@@ -281,6 +689,7 @@ pub(crate) fn store_assignment(r: wanidata::Assignment, stmt: &mut Transaction<'
         r.data.subject_id,
         subj_type,
         if let Some(unlocked_at) = r.data.unlocked_at { Some(unlocked_at.to_rfc3339()) } else { None },
+        r.data.burned_at.map(|b| b.to_rfc3339()),
         );
     return stmt.execute(INSERT_ASSIGNMENT, p);
 }
@@ -319,6 +728,34 @@ pub(crate) const INSERT_RADICALS: &str = "replace into radicals
                              character_images)
                             values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)";
 
+/// tables that subjects are stored in, one per subject type
+const SUBJECT_TABLES: [&str; 4] = ["radicals", "kanji", "vocab", "kana_vocab"];
+
+/// WaniKani occasionally changes a subject's type (e.g. vocab to kana_vocabulary). The subject's
+/// row under its old type is removed, so it can't be loaded in place of the new one
+fn remove_from_other_subject_tables(id: i32, table: &str, stmt: &mut Transaction<'_>) -> Result<(), WaniSqlError> {
+    for other in SUBJECT_TABLES.iter().filter(|t| **t != table) {
+        stmt.prepare_cached(&format!("delete from {} where id = ?1;", other))?.execute([id])?;
+    }
+    Ok(())
+}
+
+pub(crate) const SELECT_RADICALS: &str = "select 
+                   id,
+                   aux_meanings,
+                   created_at,
+                   document_url,
+                   hidden_at,
+                   lesson_position,
+                   level,
+                   meaning_mnemonic,
+                   meanings,
+                   slug,
+                   srs_id,
+                   amalgamation_subject_ids,
+                   characters,
+                   character_images from radicals;";
+
 pub(crate) fn select_radicals_by_id(n: usize) -> String {
     return format!("select 
                    id,
@@ -341,6 +778,7 @@ pub(crate) fn select_radicals_by_id(n: usize) -> String {
 
 pub(crate) fn store_radical(r: wanidata::Radical, stmt: &mut Transaction<'_>) -> Result<usize, WaniSqlError>
 {
+    remove_from_other_subject_tables(r.id, "radicals", stmt)?;
     let p = rusqlite::params!(
         format!("{}", r.id),
         serde_json::to_string(&r.data.aux_meanings)?,
@@ -358,7 +796,7 @@ pub(crate) fn store_radical(r: wanidata::Radical, stmt: &mut Transaction<'_>) ->
         serde_json::to_string(&r.data.character_images)?,
         );
 
-    match stmt.execute(INSERT_RADICALS, p) {
+    match stmt.prepare_cached(INSERT_RADICALS).and_then(|mut s| s.execute(p)) {
         Ok(u) => Ok(u),
         Err(e) => Err(WaniSqlError::Sql(e)),
     }
@@ -461,6 +899,7 @@ pub(crate) fn select_kanji_by_id(n: usize) -> String {
 
 pub(crate) fn store_kanji(k: wanidata::Kanji, stmt: &mut Transaction<'_>) -> Result<usize, WaniSqlError>
 {
+    remove_from_other_subject_tables(k.id, "kanji", stmt)?;
     let p = rusqlite::params!(
         format!("{}", k.id),
         serde_json::to_string(&k.data.aux_meanings)?,
@@ -483,7 +922,7 @@ pub(crate) fn store_kanji(k: wanidata::Kanji, stmt: &mut Transaction<'_>) -> Res
         serde_json::to_string(&k.data.visually_similar_subject_ids)?,
         );
 
-    match stmt.execute(INSERT_KANJI, p) {
+    match stmt.prepare_cached(INSERT_KANJI).and_then(|mut s| s.execute(p)) {
         Ok(u) => Ok(u),
         Err(e) => Err(WaniSqlError::Sql(e)),
     }
@@ -588,6 +1027,7 @@ pub(crate) fn select_vocab_by_id(n: usize) -> String {
 
 pub(crate) fn store_vocab(v: wanidata::Vocab, stmt: &mut Transaction<'_>) -> Result<usize, WaniSqlError>
 {
+    remove_from_other_subject_tables(v.id, "vocab", stmt)?;
     let p = rusqlite::params!(
         format!("{}", v.id),
         serde_json::to_string(&v.data.aux_meanings)?,
@@ -609,7 +1049,7 @@ pub(crate) fn store_vocab(v: wanidata::Vocab, stmt: &mut Transaction<'_>) -> Res
         v.data.reading_mnemonic
         );
 
-    match stmt.execute(INSERT_VOCAB, p) {
+    match stmt.prepare_cached(INSERT_VOCAB).and_then(|mut s| s.execute(p)) {
         Ok(u) => Ok(u),
         Err(e) => Err(WaniSqlError::Sql(e)),
     }
@@ -684,6 +1124,7 @@ pub(crate) const INSERT_KANA_VOCAB: &str = "replace into kana_vocab
 
 pub(crate) fn store_kana_vocab(v: wanidata::KanaVocab, stmt: &mut Transaction<'_>) -> Result<usize, WaniSqlError>
 {
+    remove_from_other_subject_tables(v.id, "kana_vocab", stmt)?;
     let p = rusqlite::params!(
         format!("{}", v.id),
         serde_json::to_string(&v.data.aux_meanings)?,
@@ -702,7 +1143,7 @@ pub(crate) fn store_kana_vocab(v: wanidata::KanaVocab, stmt: &mut Transaction<'_
         serde_json::to_string(&v.data.pronunciation_audios)?
         );
 
-    match stmt.execute(INSERT_KANA_VOCAB, p) {
+    match stmt.prepare_cached(INSERT_KANA_VOCAB).and_then(|mut s| s.execute(p)) {
         Ok(u) => Ok(u),
         Err(e) => Err(WaniSqlError::Sql(e)),
     }
@@ -762,13 +1203,14 @@ pub(crate) async fn get_all_cache_infos(conn: &AsyncConnection, ignore_cache: bo
     }
 
     Ok(conn.call(|conn| {
-        let mut stmt = conn.prepare("select i.id, i.last_modified, i.updated_after, i.etag from cache_info i;")?;
+        let mut stmt = conn.prepare("select i.id, i.last_modified, i.updated_after, i.etag, i.level_cap from cache_info i;")?;
         let infos = stmt.query_map([],
                                    |r| Ok(CacheInfo {
                                        id: r.get::<usize, usize>(0)?,
                                        last_modified: r.get::<usize, Option<String>>(1)?, 
                                        updated_after: r.get::<usize, Option<String>>(2)?,
-                                       etag: r.get::<usize, Option<String>>(3)? }))?;
+                                       etag: r.get::<usize, Option<String>>(3)?,
+                                       level_cap: r.get::<usize, Option<i32>>(4)? }))?;
 
         let mut map = HashMap::new();
         for info in infos {